@@ -2,4 +2,33 @@
 pub const DEFAULT_PAGE: u32 = 1;
 pub const DEFAULT_LIMIT: u32 = 20;
 
+// Paginación contra el Registry (parámetro `n` y cabecera Link)
+pub const DEFAULT_REGISTRY_PAGE_SIZE: u32 = 100;
+pub const DEFAULT_REGISTRY_MAX_PAGES: u32 = 1000;
 
+// Segundos que una entrada de la caché de repositorios se considera vigente
+pub const DEFAULT_CACHE_TTL: u64 = 300;
+
+// Número de eventos del Registry que se conservan en el feed de actividad
+pub const ACTIVITY_FEED_SIZE: usize = 200;
+
+// Peticiones simultáneas al Registry al enriquecer repositorios y tags
+pub const REGISTRY_CONCURRENCY: usize = 16;
+
+// Ejecuciones de trabajos en segundo plano que se conservan en memoria
+pub const JOB_HISTORY_SIZE: usize = 100;
+
+// Copias de imágenes entre repositorios o Registries que se conservan en memoria
+pub const COPY_HISTORY_SIZE: usize = 100;
+
+// Entradas de auditoría que se conservan en memoria para consultarlas
+pub const DEFAULT_AUDIT_MEMORY_ENTRIES: usize = 10_000;
+
+// Vida por defecto del JWT de acceso y del refresh token
+pub const DEFAULT_ACCESS_TOKEN_MINUTES: i64 = 15;
+pub const DEFAULT_REFRESH_TOKEN_DAYS: i64 = 7;
+
+// Fallos de login antes de bloquear un usuario o una IP, y duración del primer bloqueo
+pub const DEFAULT_LOGIN_MAX_FAILURES: u32 = 5;
+pub const DEFAULT_LOGIN_MAX_FAILURES_PER_IP: u32 = 20;
+pub const DEFAULT_LOGIN_LOCKOUT_SECONDS: u64 = 30;
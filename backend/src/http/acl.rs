@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing, Extension, Json, Router,
+};
+use tracing::info;
+
+use crate::http::jwt_auth::require_admin;
+use crate::models::{Account, AclRule, ApiResponse, AppState, AuditAction, AuditEntry};
+
+// Reglas de acceso por repositorio: solo las gestionan los administradores
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_rules).put(put_rules))
+        .layer(middleware::from_fn(require_admin))
+}
+
+async fn get_rules(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    ApiResponse::success("Reglas de acceso", Some(app_state.acl.rules()))
+}
+
+async fn put_rules(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Json(rules): Json<Vec<AclRule>>,
+) -> impl IntoResponse {
+    info!("Replacing {} ACL rules", rules.len());
+    let count = rules.len();
+    // Una regla de un Registry que no existe no protegería nada
+    let unknown = rules
+        .iter()
+        .find(|rule| rule.registry.as_deref().is_some_and(|r| app_state.registries.get(r).is_none()));
+    let result = match unknown {
+        Some(rule) => Err(format!(
+            "El Registry '{}' de la regla de '{}' no existe",
+            rule.registry.as_deref().unwrap_or_default(),
+            rule.repository
+        )),
+        None => app_state.acl.replace(rules),
+    };
+    let entry = AuditEntry::new(&account.username, AuditAction::AclUpdate);
+    app_state.audit.record(match &result {
+        Ok(()) => entry.detail(format!("{} reglas", count)),
+        Err(message) => entry.failed(message.as_str()),
+    });
+    match result {
+        Ok(()) => ApiResponse::success("Reglas de acceso guardadas", Some(app_state.acl.rules())).into_response(),
+        Err(message) => ApiResponse::error(StatusCode::BAD_REQUEST, &message).into_response(),
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing, Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::http::jwt_auth::require_session;
+use crate::models::{Account, ApiResponse, AppState, AuditAction, AuditEntry, Permission, Role};
+
+// Tokens de API personales. Cada usuario gestiona los suyos; un admin ve y revoca todos.
+// Un token de API no puede gestionar tokens.
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_tokens).post(create_token))
+        .route("/{id}", routing::delete(revoke_token))
+        .layer(middleware::from_fn(require_session))
+}
+
+#[derive(Deserialize)]
+struct NewToken {
+    name: String,
+    scopes: Vec<Permission>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+async fn get_tokens(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> impl IntoResponse {
+    let owner = (account.role != Role::Admin).then_some(account.username.as_str());
+    ApiResponse::success("Tokens de API", Some(app_state.api_tokens.list(owner)))
+}
+
+async fn create_token(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Json(new_token): Json<NewToken>,
+) -> Response {
+    info!("User {} creates API token {} ({:?})", account.username, new_token.name, new_token.scopes);
+    let result = app_state
+        .api_tokens
+        .create(&account.username, &new_token.name, new_token.scopes, new_token.expires_at);
+    app_state.audit.record(
+        AuditEntry::new(&account.username, AuditAction::ApiTokenCreate)
+            .target(&new_token.name)
+            .result(&result),
+    );
+    match result {
+        // El valor del token solo se devuelve ahora
+        Ok((view, token)) => (
+            StatusCode::CREATED,
+            ApiResponse::success("Token de API creado", Some(serde_json::json!({"token": token, "info": view}))),
+        )
+            .into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+async fn revoke_token(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Path(id): Path<String>,
+) -> Response {
+    // Los tokens ajenos no se distinguen de los inexistentes
+    let owned = app_state
+        .api_tokens
+        .get(&id)
+        .is_some_and(|token| token.owner == account.username || account.role == Role::Admin);
+    if !owned {
+        return ApiResponse::<Value>::error(StatusCode::NOT_FOUND, &format!("El token '{}' no existe", id))
+            .into_response();
+    }
+    info!("User {} revokes API token {}", account.username, id);
+    let result = app_state.api_tokens.revoke(&id);
+    app_state
+        .audit
+        .record(AuditEntry::new(&account.username, AuditAction::ApiTokenRevoke).target(&id).result(&result));
+    match result {
+        Ok(()) => ApiResponse::<Value>::success("Token de API revocado", None).into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::jwt_auth;
+    use crate::models::TokenClaims;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use axum::{body::Body, http::{header, Request}};
+    use tower::ServiceExt;
+
+    fn app(app_state: Arc<AppState>) -> Router {
+        Router::new()
+            .nest("/tokens", router())
+            .route("/registry/tags", routing::delete(|| async { "ok" }).layer(middleware::from_fn(jwt_auth::require_editor)))
+            .route("/registry", routing::get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), jwt_auth::auth))
+            .with_state(app_state)
+    }
+
+    async fn send(app_state: &Arc<AppState>, method: &str, uri: &str, token: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_default())
+            .unwrap();
+        let response = app(app_state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    // JWT de una sesión de login de `username`
+    fn session_token(app_state: &AppState, username: &str) -> String {
+        let (sid, _) = app_state.sessions.create(username);
+        let claims = TokenClaims {
+            sub: username.to_string(),
+            iat: chrono::Utc::now().timestamp() as usize,
+            exp: (chrono::Utc::now() + chrono::Duration::minutes(5)).timestamp() as usize,
+            sid: Some(sid),
+        };
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(app_state.secret.as_bytes())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_token_lifecycle_and_scopes() {
+        let app_state = Arc::new(AppState::for_tests());
+        let session = session_token(&app_state, "editor");
+
+        let body = serde_json::json!({"name": "ci-read", "scopes": ["read"]});
+        let (status, created) = send(&app_state, "POST", "/tokens", &session, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let read = created["data"]["token"].as_str().unwrap().to_string();
+        assert!(created["data"]["info"].get("hash").is_none());
+        let body = serde_json::json!({"name": "ci-delete", "scopes": ["read", "delete"]});
+        let (_, created) = send(&app_state, "POST", "/tokens", &session, Some(body)).await;
+        let delete = created["data"]["token"].as_str().unwrap().to_string();
+        let delete_id = created["data"]["info"]["id"].as_str().unwrap().to_string();
+
+        // Un token de API no puede crear otros tokens
+        let body = serde_json::json!({"name": "other", "scopes": ["read"]});
+        assert_eq!(send(&app_state, "POST", "/tokens", &delete, Some(body)).await.0, StatusCode::FORBIDDEN);
+
+        assert_eq!(send(&app_state, "GET", "/registry", &read, None).await.0, StatusCode::OK);
+        // `read` se queda en viewer aunque el dueño sea editor
+        assert_eq!(send(&app_state, "DELETE", "/registry/tags", &read, None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(send(&app_state, "DELETE", "/registry/tags", &delete, None).await.0, StatusCode::OK);
+
+        // Los tokens ajenos no se ven ni se pueden revocar
+        let viewer = session_token(&app_state, "viewer");
+        let (_, listed) = send(&app_state, "GET", "/tokens", &viewer, None).await;
+        assert!(listed["data"].as_array().unwrap().is_empty());
+        let uri = format!("/tokens/{}", delete_id);
+        assert_eq!(send(&app_state, "DELETE", &uri, &viewer, None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&app_state, "DELETE", &uri, &session, None).await.0, StatusCode::OK);
+        assert_eq!(send(&app_state, "GET", "/registry", &delete, None).await.0, StatusCode::UNAUTHORIZED);
+
+        // Deshabilitar al dueño invalida sus tokens
+        app_state.users.set_disabled("editor", true).await.unwrap();
+        assert_eq!(send(&app_state, "GET", "/registry", &read, None).await.0, StatusCode::UNAUTHORIZED);
+    }
+}
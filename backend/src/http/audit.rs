@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing, Router,
+};
+
+use crate::http::jwt_auth::require_admin;
+use crate::models::{AppState, AuditQuery, PagedResponse, Pagination};
+
+// Registro de auditoría: solo lo consultan los administradores
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_audit))
+        .layer(middleware::from_fn(require_admin))
+}
+
+async fn get_audit(State(app_state): State<Arc<AppState>>, Query(query): Query<AuditQuery>) -> impl IntoResponse {
+    let (total, entries) = app_state.audit.query(&query);
+    let pagination = Pagination::new(&query, total as i64, &query.base_path("/api/v1/audit"));
+    PagedResponse::new(
+        StatusCode::OK,
+        "Registro de auditoría",
+        serde_json::to_value(entries).ok(),
+        pagination,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AuditAction, AuditEntry};
+    use axum::{body::Body, http::Request};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn get(app_state: Arc<AppState>, as_user: &str, uri: &str) -> (StatusCode, Value) {
+        let mut request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        request.extensions_mut().insert(app_state.users.get(as_user).unwrap());
+        let response = router().with_state(app_state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_admin_reads_filtered_pages() {
+        let app_state = Arc::new(AppState::for_tests());
+        for tag in ["v1", "v2", "v3"] {
+            app_state.audit.record(AuditEntry::new("editor", AuditAction::TagDelete).repository("app").tag(tag));
+        }
+        app_state.audit.record(AuditEntry::new("admin", AuditAction::AclUpdate));
+
+        assert_eq!(get(app_state.clone(), "editor", "/").await.0, StatusCode::FORBIDDEN);
+        let (status, body) = get(app_state.clone(), "admin", "/?action=tag_delete&limit=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pagination"]["records"], 3);
+        assert_eq!(body["data"][0]["tag"], "v3");
+        assert_eq!(body["pagination"]["next"], "/api/v1/audit?action=tag_delete&page=2&limit=2");
+    }
+}
@@ -1,47 +1,267 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     body,
-    extract::State,
-    http::{header, StatusCode},
-    response::{IntoResponse, Response},
-    routing, Json, Router,
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse, Response},
+    routing, Extension, Json, Router,
 };
-use bcrypt::verify;
-use tracing::{debug, error};
+use serde::Deserialize;
+use tracing::{debug, error, info, warn};
 use serde_json::Value;
 
-use axum_extra::extract::cookie::{Cookie, SameSite};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 
-use crate::models::{ApiResponse, AppState, TokenClaims, User};
+use crate::models::{
+    Account, ApiResponse, AppState, AuditAction, AuditEntry, LDAP_PROVIDER, LdapProvider, LdapUser, OIDC_PROVIDER, PENDING_LOGIN_TTL,
+    SessionStore, TokenClaims,
+    User, verify_password,
+};
+
+type SessionCookies = AppendHeaders<[(HeaderName, String); 2]>;
+
+const REFRESH_COOKIE: &str = "refresh_token";
+// El refresh token solo lo necesitan /auth/refresh y /auth/logout
+const REFRESH_COOKIE_PATH: &str = "/api/v1/auth";
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+const OIDC_STATE_COOKIE_PATH: &str = "/api/v1/auth/oidc";
 
 pub fn router() -> Router<Arc<AppState>> {
     Router::new()
         .route("/login", routing::post(login))
-        .route("/logout", routing::get(logout))
+        .route("/login/totp", routing::post(login_totp))
+        .route("/refresh", routing::post(refresh))
+        .route("/logout", routing::post(logout))
+        .route("/oidc/login", routing::get(oidc_login))
+        .route("/oidc/callback", routing::get(oidc_callback))
+}
+
+#[derive(Deserialize)]
+struct TotpLogin {
+    mfa_token: String,
+    code: String,
 }
 
-type Result = std::result::Result<ApiResponse<Value>, ApiResponse<Value>>;
+#[derive(Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
 
-pub async fn login(State(app_state): State<Arc<AppState>>, Json(user_pass): Json<User>) -> Result {
-    //) -> Result<Json<serde_json::Value>,(StatusCode, Json<serde_json::Value>)>{
+pub async fn login(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    Json(user_pass): Json<User>,
+) -> Response {
     tracing::info!("init login");
-    tracing::info!("User pass: {:?}", user_pass);
-    let registered_user = app_state.user.clone();
-    if !verify(&user_pass.hashed_password, &registered_user.hashed_password).unwrap() {
-        let message = "Invalid name or password";
-        error!("{}", message);
-        return Err(ApiResponse::error(StatusCode::FORBIDDEN, message));
+    debug!("Login attempt: {}", user_pass.username);
+    let guard = &app_state.login_guard;
+    let ip = client_ip(&app_state, &headers, connect_info);
+    if let Err(remaining) = guard.check(&ip, &user_pass.username) {
+        warn!("Login blocked for {} from {}: locked out", user_pass.username, ip);
+        audit_lockout(&app_state, &user_pass.username, &ip);
+        return too_many_attempts(remaining);
+    }
+
+    // Las cuentas locales se comprueban con bcrypt; el resto, contra LDAP si está activo
+    let local = app_state
+        .users
+        .get(&user_pass.username)
+        .filter(|account| account.provider.is_none());
+    let ldap_user = match (&app_state.ldap, &local) {
+        (Some(ldap), None) => match ldap.find(&user_pass.username).await {
+            Ok(user) => user,
+            Err((status, message)) => {
+                audit_login(&app_state, &user_pass.username, &ip, Err(&message));
+                return ApiResponse::<Value>::error(status, &message).into_response();
+            }
+        },
+        _ => None,
+    };
+    // Con LDAP, el bloqueo por usuario va con el nombre del directorio: escribirlo
+    // con otras mayúsculas o con un alias no da intentos nuevos
+    let username = ldap_user.as_ref().map_or(user_pass.username.as_str(), |user| user.username.as_str());
+    if username != user_pass.username
+        && let Err(remaining) = guard.check(&ip, username)
+    {
+        warn!("Login blocked for {} from {}: locked out", username, ip);
+        audit_lockout(&app_state, username, &ip);
+        return too_many_attempts(remaining);
+    }
+    let registered_user = match (&app_state.ldap, local, &ldap_user) {
+        (_, Some(account), _) => login_local(account, &user_pass.hashed_password).await,
+        (Some(ldap), None, Some(user)) => match login_ldap(&app_state, ldap, user, &user_pass.hashed_password).await {
+            Ok(account) => account,
+            Err(response) => {
+                audit_login(&app_state, username, &ip, Err(&response.message));
+                return response.into_response();
+            }
+        },
+        _ => None,
+    };
+    let Some(registered_user) = registered_user else {
+        let lockout = guard.record_failure(&ip, username);
+        warn!(
+            "Failed login for {} from {}{}",
+            username,
+            ip,
+            lockout.map(|d| format!(", locked out for {}s", d.as_secs())).unwrap_or_default()
+        );
+        audit_login(&app_state, username, &ip, Err("Invalid name or password"));
+        return ApiResponse::<Value>::error(StatusCode::UNAUTHORIZED, "Invalid name or password").into_response();
+    };
+    // Con 2FA la contraseña solo abre el segundo paso; el JWT llega con el código
+    if registered_user.totp_enabled() {
+        debug!("Password accepted for {}, waiting for TOTP code", registered_user.username);
+        let value = serde_json::json!({
+            "mfa_required": true,
+            "mfa_token": app_state.totp.challenge(&registered_user.username),
+        });
+        return ApiResponse::success("TOTP code required", Some(value)).into_response();
+    }
+    // Se limpian los fallos del mismo nombre con el que se contaron
+    guard.record_success(&ip, username);
+    audit_login(&app_state, &registered_user.username, &ip, Ok("password"));
+
+    match start_session(&app_state, &registered_user) {
+        Ok((value, cookies)) => (cookies, ApiResponse::success("Ok", Some(value))).into_response(),
+        Err(response) => response.into_response(),
     }
+}
+
+// Segundo paso del login: código TOTP o de recuperación para el `mfa_token` del primero
+async fn login_totp(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    Json(request): Json<TotpLogin>,
+) -> Response {
+    let Some(username) = app_state.totp.pending(&request.mfa_token) else {
+        return ApiResponse::<Value>::error(StatusCode::UNAUTHORIZED, "Login expired, please start again")
+            .into_response();
+    };
+    let guard = &app_state.login_guard;
+    let ip = client_ip(&app_state, &headers, connect_info);
+    if let Err(remaining) = guard.check(&ip, &username) {
+        warn!("TOTP login blocked for {} from {}: locked out", username, ip);
+        audit_lockout(&app_state, &username, &ip);
+        return too_many_attempts(remaining);
+    }
+
+    let verified = app_state.users.update_account(&username, |account| {
+        let valid = !account.disabled
+            && account
+                .totp
+                .as_mut()
+                .is_some_and(|totp| totp.enabled && totp.verify(&request.code));
+        match valid {
+            true => Ok(account.clone()),
+            false => Err((StatusCode::UNAUTHORIZED, "Invalid code".to_string())),
+        }
+    })
+    .await;
+    let account = match verified {
+        Ok(account) => account,
+        Err((status, message)) => {
+            app_state.totp.failed(&request.mfa_token);
+            let lockout = guard.record_failure(&ip, &username);
+            warn!(
+                "Failed TOTP code for {} from {}{}",
+                username,
+                ip,
+                lockout.map(|d| format!(", locked out for {}s", d.as_secs())).unwrap_or_default()
+            );
+            audit_login(&app_state, &username, &ip, Err("Invalid TOTP code"));
+            return ApiResponse::<Value>::error(status, &message).into_response();
+        }
+    };
+    app_state.totp.complete(&request.mfa_token);
+    guard.record_success(&ip, &username);
+    audit_login(&app_state, &username, &ip, Ok("password + totp"));
+
+    match start_session(&app_state, &account) {
+        Ok((value, cookies)) => (cookies, ApiResponse::success("Ok", Some(value))).into_response(),
+        Err(response) => response.into_response(),
+    }
+}
+
+// Intento de login en la auditoría: `Ok` con el método usado, `Err` con el motivo del fallo
+// Un cliente que insiste contra un bloqueo se anota una vez por intervalo
+fn audit_lockout(app_state: &AppState, username: &str, ip: &str) {
+    let entry = AuditEntry::new(username, AuditAction::Login).ip(ip).failed("Locked out");
+    app_state.audit.record_throttled(entry);
+}
+
+fn audit_login(app_state: &AppState, username: &str, ip: &str, outcome: Result<&str, &str>) {
+    let entry = AuditEntry::new(username, AuditAction::Login).ip(ip);
+    app_state.audit.record(match outcome {
+        Ok(method) => entry.detail(method),
+        Err(reason) => entry.failed(reason),
+    });
+}
+
+fn client_ip(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> String {
+    app_state
+        .login_guard
+        .client_ip(headers, connect_info.map(|Extension(ConnectInfo(peer))| peer))
+}
 
+// 429 con `Retry-After` mientras dura el bloqueo
+fn too_many_attempts(remaining: Duration) -> Response {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    (
+        AppendHeaders([(header::RETRY_AFTER, seconds.to_string())]),
+        ApiResponse::<Value>::error(StatusCode::TOO_MANY_REQUESTS, "Too many failed login attempts, try again later"),
+    )
+        .into_response()
+}
+
+async fn login_local(account: Account, password: &str) -> Option<Account> {
+    if account.disabled {
+        return None;
+    }
+    verify_password(&account, password).await.then_some(account)
+}
+
+async fn login_ldap(
+    app_state: &AppState,
+    ldap: &LdapProvider,
+    user: &LdapUser,
+    password: &str,
+) -> Result<Option<Account>, ApiResponse<Value>> {
+    let identity = ldap
+        .authenticate(user, password)
+        .await
+        .map_err(|(status, message)| ApiResponse::error(status, &message))?;
+    let Some(identity) = identity else {
+        return Ok(None);
+    };
+    app_state
+        .users
+        .upsert_external(&identity.username, LDAP_PROVIDER, None, identity.role, identity.groups)
+        .await
+        .map(Some)
+        .map_err(|(status, message)| ApiResponse::error(status, &message))
+}
+
+// JWT de acceso de corta duración ligado a la sesión `sid`
+fn issue_token(app_state: &AppState, username: &str, sid: &str) -> Result<String, ApiResponse<Value>> {
     let now = chrono::Utc::now();
     let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
+    let exp = (now + app_state.sessions.access_ttl).timestamp() as usize;
     let claims: TokenClaims = TokenClaims {
-        sub: registered_user.username.to_string(),
+        sub: username.to_string(),
         exp,
         iat,
+        sid: Some(sid.to_string()),
     };
 
     encode(
@@ -54,27 +274,488 @@ pub async fn login(State(app_state): State<Arc<AppState>>, Json(user_pass): Json
         error!("{}", message);
         ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, &message)
     })
-    .map(|token| {
-        let value = serde_json::json!({"token": token});
-        ApiResponse::success("Ok", Some(value))
-    })
 }
 
-pub async fn logout() -> impl IntoResponse {
-    debug!("Logout");
-    let cookie = Cookie::build(("token", ""))
+// Cookies `token` (JWT de acceso, para toda la app) y `refresh_token`
+// (solo viaja a /api/v1/auth). Con `access`/`refresh` vacíos las borran.
+fn session_cookies(app_state: &AppState, access: &str, refresh: &str) -> SessionCookies {
+    let sessions = &app_state.sessions;
+    let max_age = |ttl: chrono::Duration, value: &str| {
+        if value.is_empty() {
+            cookie::time::Duration::ZERO
+        } else {
+            cookie::time::Duration::seconds(ttl.num_seconds())
+        }
+    };
+    let access_cookie = Cookie::build(("token", access.to_string()))
         .path("/")
-        .max_age(cookie::time::Duration::ZERO)
+        .max_age(max_age(sessions.access_ttl, access))
         .same_site(SameSite::Lax)
+        .secure(sessions.secure_cookies)
         .http_only(true)
         .build();
+    let refresh_cookie = Cookie::build((REFRESH_COOKIE, refresh.to_string()))
+        .path(REFRESH_COOKIE_PATH)
+        .max_age(max_age(sessions.refresh_ttl, refresh))
+        .same_site(SameSite::Strict)
+        .secure(sessions.secure_cookies)
+        .http_only(true)
+        .build();
+    AppendHeaders([
+        (header::SET_COOKIE, access_cookie.to_string()),
+        (header::SET_COOKIE, refresh_cookie.to_string()),
+    ])
+}
 
-    tracing::info!("The cookie: {}", cookie.to_string());
+// Abre una sesión para la cuenta: datos de la respuesta de login y sus cookies
+fn start_session(
+    app_state: &AppState,
+    account: &Account,
+) -> Result<(Value, SessionCookies), ApiResponse<Value>> {
+    let (sid, refresh) = app_state.sessions.create(&account.username);
+    let token = issue_token(app_state, &account.username, &sid)?;
+    let cookies = session_cookies(app_state, &token, &refresh);
+    let value = serde_json::json!({
+        "token": token,
+        "role": account.role,
+        "expires_in": app_state.sessions.access_ttl.num_seconds(),
+    });
+    Ok((value, cookies))
+}
+
+// Canjea la cookie `refresh_token` por un JWT de acceso nuevo y rota el refresh token
+async fn refresh(State(app_state): State<Arc<AppState>>, cookie_jar: CookieJar) -> Response {
+    let unauthorized = |message: &str| {
+        (
+            session_cookies(&app_state, "", ""),
+            ApiResponse::<Value>::error(StatusCode::UNAUTHORIZED, message),
+        )
+            .into_response()
+    };
+    let Some(refresh_token) = cookie_jar.get(REFRESH_COOKIE).map(|c| c.value().to_string()) else {
+        return unauthorized("You are not logged in, please provide token");
+    };
+    let (session, refresh_token) = match app_state.sessions.rotate(&refresh_token) {
+        Ok(rotated) => rotated,
+        Err(e) => {
+            debug!("Refresh rejected: {:?}", e);
+            return unauthorized("Session expired or revoked");
+        }
+    };
+    let Some(account) = app_state.users.active(&session.username) else {
+        app_state.sessions.revoke(&session.id);
+        return unauthorized("The user belonging to this token no longer exists");
+    };
 
+    match issue_token(&app_state, &account.username, &session.id) {
+        Ok(token) => {
+            let value = serde_json::json!({
+                "token": token,
+                "role": account.role,
+                "expires_in": app_state.sessions.access_ttl.num_seconds(),
+            });
+            (
+                session_cookies(&app_state, &token, &refresh_token),
+                ApiResponse::success("Ok", Some(value)),
+            )
+                .into_response()
+        }
+        Err(response) => response.into_response(),
+    }
+}
+
+fn redirect(location: &str) -> Response {
     Response::builder()
         .status(StatusCode::SEE_OTHER)
-        .header(header::LOCATION, "/")
-        .header(header::SET_COOKIE, cookie.to_string())
+        .header(header::LOCATION, location)
         .body(body::Body::empty())
         .unwrap()
 }
+
+// Cookie que liga el `state` al navegador que empezó el login: un callback con
+// un `state` ajeno (login CSRF) no la trae
+fn oidc_state_cookie(app_state: &AppState, state: &str) -> AppendHeaders<[(HeaderName, String); 1]> {
+    let max_age = match state.is_empty() {
+        true => cookie::time::Duration::ZERO,
+        false => cookie::time::Duration::seconds(PENDING_LOGIN_TTL.as_secs() as i64),
+    };
+    let cookie = Cookie::build((OIDC_STATE_COOKIE, state.to_string()))
+        .path(OIDC_STATE_COOKIE_PATH)
+        .max_age(max_age)
+        .same_site(SameSite::Lax)
+        .secure(app_state.sessions.secure_cookies)
+        .http_only(true)
+        .build();
+    AppendHeaders([(header::SET_COOKIE, cookie.to_string())])
+}
+
+// Inicia el login OIDC: redirige al proveedor con `state`, `nonce` y el reto PKCE
+async fn oidc_login(State(app_state): State<Arc<AppState>>) -> Response {
+    let Some(oidc) = &app_state.oidc else {
+        return ApiResponse::<Value>::error(StatusCode::NOT_FOUND, "OIDC login is not enabled").into_response();
+    };
+    match oidc.authorization_url().await {
+        Ok((url, state)) => (oidc_state_cookie(&app_state, &state), redirect(&url)).into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+// Vuelta del proveedor: canjea el código, sincroniza la cuenta y abre la sesión con cookies
+async fn oidc_callback(
+    State(app_state): State<Arc<AppState>>,
+    cookie_jar: CookieJar,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    let Some(oidc) = &app_state.oidc else {
+        return ApiResponse::<Value>::error(StatusCode::NOT_FOUND, "OIDC login is not enabled").into_response();
+    };
+    if let Some(error) = params.error {
+        error!("OIDC provider returned an error: {}", error);
+        return ApiResponse::<Value>::error(StatusCode::UNAUTHORIZED, &error).into_response();
+    }
+    let (Some(code), Some(state)) = (params.code, params.state) else {
+        return ApiResponse::<Value>::error(StatusCode::BAD_REQUEST, "Missing code or state").into_response();
+    };
+    let clear_state = oidc_state_cookie(&app_state, "");
+    if cookie_jar.get(OIDC_STATE_COOKIE).map(|c| c.value()) != Some(state.as_str()) {
+        warn!("OIDC callback state does not match the browser cookie");
+        return (
+            clear_state,
+            ApiResponse::<Value>::error(StatusCode::BAD_REQUEST, "OIDC state does not match this browser"),
+        )
+            .into_response();
+    }
+
+    let account = match oidc.exchange(&code, &state).await {
+        Ok(identity) => {
+            app_state
+                .users
+                .upsert_external(
+                    &identity.username,
+                    OIDC_PROVIDER,
+                    Some(&identity.subject),
+                    identity.role,
+                    identity.groups,
+                )
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    let account = match account {
+        Ok(account) => account,
+        Err((status, message)) => {
+            app_state
+                .audit
+                .record(AuditEntry::new("", AuditAction::Login).detail("oidc").failed(message.as_str()));
+            return (clear_state, ApiResponse::<Value>::error(status, &message)).into_response();
+        }
+    };
+    info!("OIDC login for {} ({:?})", account.username, account.role);
+    app_state.audit.record(AuditEntry::new(&account.username, AuditAction::Login).detail("oidc"));
+
+    match start_session(&app_state, &account) {
+        Ok((_, cookies)) => (clear_state, cookies, redirect("/")).into_response(),
+        Err(response) => response.into_response(),
+    }
+}
+
+// Cierra la sesión en el servidor (el JWT de acceso deja de valer al momento) y borra las cookies
+pub async fn logout(State(app_state): State<Arc<AppState>>, cookie_jar: CookieJar) -> impl IntoResponse {
+    debug!("Logout");
+    let from_refresh = cookie_jar
+        .get(REFRESH_COOKIE)
+        .and_then(|c| SessionStore::session_id(c.value()).map(String::from));
+    let claims = cookie_jar.get("token").and_then(|c| {
+        decode::<TokenClaims>(
+            c.value(),
+            &DecodingKey::from_secret(app_state.secret.as_bytes()),
+            &Validation::default(),
+        )
+        .ok()
+        .map(|data| data.claims)
+    });
+    let from_access = claims.as_ref().and_then(|claims| claims.sid.clone());
+    for sid in from_refresh.iter().chain(from_access.iter()) {
+        if app_state.sessions.revoke(sid) {
+            info!("Session {} revoked", sid);
+        }
+    }
+    if let Some(claims) = claims {
+        app_state.audit.record(AuditEntry::new(&claims.sub, AuditAction::Logout));
+    }
+
+    (session_cookies(&app_state, "", ""), ApiResponse::<Value>::success("Logged out", None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AuditOutcome, AuditQuery, OidcProvider, Role, TotpSettings, totp_code};
+    use crate::models::{LdapProvider, oidc_mock, test_directory::TestDirectory};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_oidc_callback_provisions_account_and_sets_cookie() {
+        let idp = oidc_mock::spawn(serde_json::json!({
+            "sub": "1234",
+            "preferred_username": "carol",
+            "groups": ["registry-admins"],
+        }))
+        .await;
+        let mut app_state = AppState::for_tests();
+        app_state.oidc = Some(OidcProvider::new(idp.config()));
+        let app_state = Arc::new(app_state);
+
+        let request = Request::builder().uri("/oidc/login").body(body::Body::empty()).unwrap();
+        let response = router().with_state(app_state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let state_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(state_cookie.starts_with("oidc_state=") && state_cookie.contains("HttpOnly"));
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        let state = idp.authorize(location);
+
+        let callback = |cookie: String| {
+            Request::builder()
+                .uri(format!("/oidc/callback?code=good-code&state={}", state))
+                .header(header::COOKIE, cookie)
+                .body(body::Body::empty())
+                .unwrap()
+        };
+        // Un navegador que no empezó el login no puede completarlo (login CSRF)
+        let request = callback(format!("oidc_state={}x", state));
+        let response = router().with_state(app_state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(app_state.users.active("carol").is_none());
+
+        let request = callback(format!("oidc_state={}", state));
+        let response = router().with_state(app_state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookies: Vec<_> = response.headers().get_all(header::SET_COOKIE).iter().map(|c| c.to_str().unwrap()).collect();
+        assert!(cookies.iter().any(|c| c.starts_with("oidc_state=;") && c.contains("Max-Age=0")));
+        assert!(cookies.iter().any(|c| c.starts_with("token=") && c.contains("HttpOnly")));
+
+        let account = app_state.users.active("carol").unwrap();
+        assert_eq!(account.role, Role::Admin);
+        assert_eq!(account.provider.as_deref(), Some(OIDC_PROVIDER));
+        assert!(account.subject.is_some_and(|s| s.ends_with(" 1234")));
+    }
+
+    async fn login_status(app_state: Arc<AppState>, username: &str, password: &str) -> StatusCode {
+        let body = serde_json::json!({"username": username, "hashed_password": password});
+        let request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body::Body::from(body.to_string()))
+            .unwrap();
+        router().with_state(app_state).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_ldap_login_alongside_local_accounts() {
+        let mut app_state = AppState::for_tests();
+        app_state.ldap = Some(LdapProvider::new(TestDirectory::config(), Box::new(TestDirectory::example())));
+        let app_state = Arc::new(app_state);
+
+        assert_eq!(login_status(app_state.clone(), "dave", "dave-password").await, StatusCode::OK);
+        let account = app_state.users.active("dave").unwrap();
+        assert_eq!(account.role, Role::Editor);
+        assert_eq!(account.provider.as_deref(), Some(LDAP_PROVIDER));
+
+        assert_eq!(login_status(app_state.clone(), "dave", "wrong").await, StatusCode::UNAUTHORIZED);
+        // Las cuentas locales siguen usando su contraseña bcrypt
+        assert_eq!(login_status(app_state.clone(), "admin", "password").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ldap_lockout_uses_the_directory_name() {
+        let mut app_state = AppState::for_tests();
+        app_state.ldap = Some(LdapProvider::new(TestDirectory::config(), Box::new(TestDirectory::example())));
+        let app_state = Arc::new(app_state);
+
+        // Cambiar las mayúsculas no da intentos nuevos: los tres fallos son de `dave`
+        for username in ["dave", "Dave", "DAVE"] {
+            assert_eq!(login_status(app_state.clone(), username, "wrong").await, StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(login_status(app_state.clone(), "dAvE", "dave-password").await, StatusCode::TOO_MANY_REQUESTS);
+        assert!(app_state.users.get("dave").is_none());
+
+        // La cuenta se crea con el nombre del directorio
+        let mut app_state = AppState::for_tests();
+        app_state.ldap = Some(LdapProvider::new(TestDirectory::config(), Box::new(TestDirectory::example())));
+        let app_state = Arc::new(app_state);
+        assert_eq!(login_status(app_state.clone(), "DAVE", "dave-password").await, StatusCode::OK);
+        assert!(app_state.users.get("dave").is_some());
+        assert!(app_state.users.get("DAVE").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ldap_success_clears_failures_of_the_directory_name() {
+        let mut app_state = AppState::for_tests();
+        app_state.ldap = Some(LdapProvider::new(TestDirectory::config(), Box::new(TestDirectory::example())));
+        let app_state = Arc::new(app_state);
+
+        for username in ["dave", "Dave"] {
+            assert_eq!(login_status(app_state.clone(), username, "wrong").await, StatusCode::UNAUTHORIZED);
+        }
+        // Entrar como `DAVE` limpia los fallos de `dave`, no los de `DAVE`
+        assert_eq!(login_status(app_state.clone(), "DAVE", "dave-password").await, StatusCode::OK);
+        for _ in 0..2 {
+            assert_eq!(login_status(app_state.clone(), "dave", "wrong").await, StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(login_status(app_state.clone(), "dave", "dave-password").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_failed_logins_lock_out_with_retry_after() {
+        let app_state = Arc::new(AppState::for_tests());
+        // Una cuenta con un hash corrupto falla limpiamente en lugar de provocar un pánico
+        app_state.users.insert(Account::new("broken", "not-a-bcrypt-hash", Role::Viewer));
+        assert_eq!(login_status(app_state.clone(), "broken", "password").await, StatusCode::UNAUTHORIZED);
+
+        // El estado de test bloquea al tercer fallo
+        assert_eq!(login_status(app_state.clone(), "viewer", "wrong").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(login_status(app_state.clone(), "viewer", "wrong").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(login_status(app_state.clone(), "viewer", "wrong").await, StatusCode::UNAUTHORIZED);
+        // Bloqueado aunque ahora la contraseña sea correcta
+        let body = serde_json::json!({"username": "viewer", "hashed_password": "password"});
+        let request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body::Body::from(body.to_string()))
+            .unwrap();
+        let response = router().with_state(app_state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+        // Otros usuarios siguen pudiendo entrar
+        assert_eq!(login_status(app_state.clone(), "admin", "password").await, StatusCode::OK);
+
+        // Todos los intentos quedan en la auditoría
+        let query = AuditQuery { user: Some("viewer".to_string()), ..Default::default() };
+        let (total, entries) = app_state.audit.query(&query);
+        assert_eq!(total, 4);
+        assert!(entries.iter().all(|e| e.action == AuditAction::Login && e.outcome == AuditOutcome::Failure));
+        let query = AuditQuery { user: Some("admin".to_string()), ..Default::default() };
+        assert_eq!(app_state.audit.query(&query).1[0].outcome, AuditOutcome::Success);
+    }
+
+    async fn post_json(app_state: Arc<AppState>, uri: &str, body: Value) -> (StatusCode, Value, Option<String>) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body::Body::from(body.to_string()))
+            .unwrap();
+        let response = router().with_state(app_state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let cookie = cookie_value(&response, "token");
+        let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap(), cookie)
+    }
+
+    #[tokio::test]
+    async fn test_two_step_login_with_totp() {
+        let app_state = Arc::new(AppState::for_tests());
+        let (settings, recovery_codes) = app_state
+            .users
+            .update_account("editor", |account| {
+                let mut totp = TotpSettings::generate();
+                totp.enabled = true;
+                let codes = totp.reset_recovery_codes();
+                account.totp = Some(totp.clone());
+                Ok((totp, codes))
+            })
+            .await
+            .unwrap();
+        let password = serde_json::json!({"username": "editor", "hashed_password": "password"});
+
+        // La contraseña sola no emite el JWT
+        let (status, body, cookie) = post_json(app_state.clone(), "/login", password.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["mfa_required"], true);
+        assert!(body["data"].get("token").is_none());
+        assert!(cookie.is_none());
+        let mfa_token = body["data"]["mfa_token"].as_str().unwrap().to_string();
+
+        let wrong = serde_json::json!({"mfa_token": mfa_token, "code": "not-a-code"});
+        assert_eq!(post_json(app_state.clone(), "/login/totp", wrong).await.0, StatusCode::UNAUTHORIZED);
+        let code = totp_code(&settings);
+        let valid = serde_json::json!({"mfa_token": mfa_token, "code": code});
+        let (status, body, cookie) = post_json(app_state.clone(), "/login/totp", valid.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["token"].is_string());
+        assert!(cookie.is_some_and(|c| !c.is_empty()));
+        // El paso intermedio solo vale una vez
+        assert_eq!(post_json(app_state.clone(), "/login/totp", valid).await.0, StatusCode::UNAUTHORIZED);
+
+        // El mismo código no vale para otro login, pero un código de recuperación sí
+        let (_, body, _) = post_json(app_state.clone(), "/login", password).await;
+        let mfa_token = body["data"]["mfa_token"].as_str().unwrap().to_string();
+        let replay = serde_json::json!({"mfa_token": mfa_token, "code": code});
+        assert_eq!(post_json(app_state.clone(), "/login/totp", replay).await.0, StatusCode::UNAUTHORIZED);
+        let recovery = serde_json::json!({"mfa_token": mfa_token, "code": recovery_codes[0]});
+        assert_eq!(post_json(app_state.clone(), "/login/totp", recovery).await.0, StatusCode::OK);
+    }
+
+    fn cookie_value(response: &Response, name: &str) -> Option<String> {
+        response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| Cookie::parse(v.to_str().unwrap().to_string()).ok())
+            .find(|c| c.name() == name)
+            .map(|c| c.value().to_string())
+    }
+
+    async fn with_refresh_cookie(app_state: Arc<AppState>, method: &str, uri: &str, refresh: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, format!("{}={}", REFRESH_COOKIE, refresh))
+            .body(body::Body::empty())
+            .unwrap();
+        router().with_state(app_state).oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let app_state = Arc::new(AppState::for_tests());
+        let body = serde_json::json!({"username": "viewer", "hashed_password": "password"});
+        let request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body::Body::from(body.to_string()))
+            .unwrap();
+        let response = router().with_state(app_state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let set_cookie: Vec<_> = response.headers().get_all(header::SET_COOKIE).iter().collect();
+        assert!(set_cookie.iter().all(|c| {
+            let c = c.to_str().unwrap();
+            c.contains("HttpOnly") && c.contains("Secure") && c.contains("SameSite")
+        }));
+        let first = cookie_value(&response, REFRESH_COOKIE).unwrap();
+        assert!(cookie_value(&response, "token").is_some_and(|t| !t.is_empty()));
+
+        // El refresh rota el token: el nuevo vale, el viejo revoca la sesión
+        let response = with_refresh_cookie(app_state.clone(), "POST", "/refresh", &first).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let second = cookie_value(&response, REFRESH_COOKIE).unwrap();
+        assert_ne!(first, second);
+        let response = with_refresh_cookie(app_state.clone(), "POST", "/refresh", &second).await;
+        let third = cookie_value(&response, REFRESH_COOKIE).unwrap();
+
+        let sid = SessionStore::session_id(&third).unwrap().to_string();
+        // Un GET (un enlace o una imagen de otra web) no cierra la sesión
+        let response = with_refresh_cookie(app_state.clone(), "GET", "/logout", &third).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(app_state.sessions.is_active(&sid));
+        let response = with_refresh_cookie(app_state.clone(), "POST", "/logout", &third).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!app_state.sessions.is_active(&sid));
+        let response = with_refresh_cookie(app_state.clone(), "POST", "/refresh", &third).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
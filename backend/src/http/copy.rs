@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing, Extension, Json, Router,
+};
+use serde_json::Value;
+use tracing::{error, info};
+
+use super::registry::{check_access, valid_reference, valid_repository, valid_tag};
+use crate::http::jwt_auth::require_editor;
+use crate::models::{
+    Account, ApiResponse, AppState, AuditAction, AuditEntry, CopyProgress, CopyRequest, Permission, RegistryClient,
+    check_copy_target, copy_image,
+};
+
+// Copia de imágenes entre repositorios o Registries. La copia sigue en segundo
+// plano y su progreso se consulta con `GET /copy/{id}`.
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/",
+            routing::get(get_copies).merge(routing::post(start_copy).layer(middleware::from_fn(require_editor))),
+        )
+        .route("/{id}", routing::get(get_copy))
+}
+
+fn registry_client(app_state: &AppState, name: Option<&str>) -> Result<RegistryClient, (StatusCode, String)> {
+    app_state.registries.resolve(name).cloned().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("El Registry '{}' no existe", name.unwrap_or_default()),
+        )
+    })
+}
+
+// Clientes de origen y destino, una vez comprobados los nombres y los permisos.
+// Los nombres se validan antes que las reglas de acceso, como en `check_access`.
+fn prepare(
+    app_state: &AppState,
+    account: &Account,
+    request: &CopyRequest,
+) -> Result<(RegistryClient, RegistryClient), (StatusCode, String)> {
+    for repository in [&request.source.repository, &request.target.repository] {
+        if !valid_repository(repository) {
+            return Err((StatusCode::BAD_REQUEST, format!("'{}' no es un nombre de repositorio válido", repository)));
+        }
+    }
+    if !valid_reference(&request.source.reference) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("'{}' no es un tag ni un digest válido", request.source.reference),
+        ));
+    }
+    if !valid_tag(&request.target.reference) {
+        return Err((StatusCode::BAD_REQUEST, "El destino tiene que ser un tag".to_string()));
+    }
+    let source = registry_client(app_state, request.source.registry.as_deref())?;
+    let target = registry_client(app_state, request.target.registry.as_deref())?;
+    let registries = &app_state.registries;
+    let source_registry = request.source.registry.as_deref().unwrap_or(registries.default_name());
+    let target_registry = request.target.registry.as_deref().unwrap_or(registries.default_name());
+    // Escribir en el destino exige lo mismo que borrar en él
+    check_access(app_state, account, source_registry, &request.source.repository, Permission::Read)?;
+    check_access(app_state, account, target_registry, &request.target.repository, Permission::Delete)?;
+    Ok((source, target))
+}
+
+async fn start_copy(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Json(request): Json<CopyRequest>,
+) -> Response {
+    let (source_client, target_client) = match prepare(&app_state, &account, &request) {
+        Ok(clients) => clients,
+        Err((status, message)) => return ApiResponse::<Value>::error(status, &message).into_response(),
+    };
+    let force = request.force;
+    if let Err((status, message)) =
+        check_copy_target(&source_client, &request.source, &target_client, &request.target, force).await
+    {
+        return ApiResponse::<Value>::error(status, &message).into_response();
+    }
+
+    let progress = app_state.copies.start(&account.username, request.source, request.target);
+    info!("User {} copies {} to {}", account.username, progress.source, progress.target);
+    let task = progress.clone();
+    tokio::spawn(async move {
+        let result = copy_image(
+            &source_client,
+            &task.source,
+            &target_client,
+            &task.target,
+            force,
+            &app_state.copies,
+            task.id,
+        )
+        .await;
+        app_state.copies.finish(task.id, &result);
+        let registry = task.target.registry.as_deref().unwrap_or(app_state.registries.default_name());
+        let entry = AuditEntry::new(&task.user, AuditAction::ImageCopy)
+            .registry(registry)
+            .repository(&task.target.repository)
+            .tag(&task.target.reference);
+        app_state.audit.record(match result {
+            Ok(digest) => entry.digest(&digest).detail(format!("from {}", task.source)),
+            Err((_, message)) => {
+                error!("Copy #{} of {} failed: {}", task.id, task.source, message);
+                entry.failed(format!("{} (from {})", message, task.source))
+            }
+        });
+    });
+    (StatusCode::ACCEPTED, ApiResponse::success("Copia iniciada", Some(progress))).into_response()
+}
+
+// Si el usuario puede ver el origen y el destino de una copia
+fn can_see(app_state: &AppState, account: &Account, copy: &CopyProgress) -> bool {
+    let default = app_state.registries.default_name();
+    [&copy.source, &copy.target].into_iter().all(|image| {
+        let registry = image.registry.as_deref().unwrap_or(default);
+        app_state.acl.allows(account, registry, &image.repository, Permission::Read)
+    })
+}
+
+// Solo las copias entre repositorios que el usuario puede ver
+async fn get_copies(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> impl IntoResponse {
+    let copies: Vec<_> = app_state
+        .copies
+        .list()
+        .into_iter()
+        .filter(|c| can_see(&app_state, &account, c))
+        .collect();
+    ApiResponse::success("Copias de imágenes", Some(copies))
+}
+
+async fn get_copy(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Path(id): Path<u64>,
+) -> Response {
+    let copy = app_state.copies.get(id).filter(|c| can_see(&app_state, &account, c));
+    match copy {
+        Some(copy) => ApiResponse::success("Progreso de la copia", Some(copy)).into_response(),
+        None => ApiResponse::<Value>::error(StatusCode::NOT_FOUND, &format!("La copia {} no existe", id))
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn post(app_state: Arc<AppState>, as_user: &str, body: Value) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        request.extensions_mut().insert(app_state.users.get(as_user).unwrap());
+        let response = router().with_state(app_state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_start_copy_validation() {
+        let app_state = Arc::new(AppState::for_tests());
+        let request = |registry: &str, tag: &str| {
+            serde_json::json!({
+                "source": {"repository": "app", "reference": "rc-42"},
+                "target": {"registry": registry, "repository": "app", "reference": tag},
+            })
+        };
+        assert_eq!(post(app_state.clone(), "viewer", request("default", "1.4.0")).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(post(app_state.clone(), "editor", request("default", "sha256:abc")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(post(app_state.clone(), "editor", request("prod", "1.4.0")).await.0, StatusCode::NOT_FOUND);
+        // El origen se valida igual que el destino, antes de mirar las reglas de acceso
+        for source in [
+            serde_json::json!({"repository": "a/../private", "reference": "v1"}),
+            serde_json::json!({"repository": "app", "reference": "../v1"}),
+            serde_json::json!({"repository": "app", "reference": "sha256:abc"}),
+        ] {
+            let body = serde_json::json!({"source": source, "target": {"repository": "app", "reference": "v2"}});
+            assert_eq!(post(app_state.clone(), "editor", body).await.0, StatusCode::BAD_REQUEST);
+        }
+
+        // El Registry de pruebas no responde: con `force` no se mira el destino antes,
+        // así que la copia se acepta y termina con error
+        let mut forced = request("default", "1.4.0");
+        forced["force"] = Value::Bool(true);
+        let (status, body) = post(app_state.clone(), "editor", forced).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let id = body["data"]["id"].as_u64().unwrap();
+        for _ in 0..50 {
+            if app_state.copies.get(id).unwrap().finished_at.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let copy = app_state.copies.get(id).unwrap();
+        assert!(copy.error.is_some());
+    }
+}
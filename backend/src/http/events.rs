@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing, Router,
+};
+use serde_json::Value;
+use tracing::{debug, error};
+
+use crate::models::{ApiResponse, AppState, EventEnvelope};
+
+// Receptor de notificaciones del Registry. No usa JWT: el Registry se autentica
+// con el secreto compartido configurado en `notifications.endpoints[].headers`:
+//
+//   headers:
+//     Authorization: [Bearer <EVENTS_SECRET>]
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new().route("/", routing::post(receive_events))
+}
+
+async fn receive_events(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let Some(secret) = app_state.events_secret.as_deref() else {
+        return ApiResponse::error(StatusCode::NOT_FOUND, "Registry notifications are not enabled")
+            .into_response();
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !constant_time_eq(provided.as_bytes(), secret.as_bytes()) {
+        error!("Registry notification with invalid secret");
+        return ApiResponse::error(StatusCode::UNAUTHORIZED, "Invalid secret").into_response();
+    }
+
+    // El Registry envía `application/vnd.docker.distribution.events.v1+json`,
+    // que el extractor Json no acepta, así que parseamos el cuerpo a mano.
+    let envelope: EventEnvelope = match serde_json::from_slice(&body) {
+        Ok(e) => e,
+        Err(e) => {
+            error!("Invalid notification envelope: {}", e);
+            return ApiResponse::error(StatusCode::BAD_REQUEST, &format!("Invalid envelope: {}", e))
+                .into_response();
+        }
+    };
+
+    let mut recorded = 0;
+    for event in envelope.events.iter().filter(|e| e.is_manifest_event()) {
+        debug!("Registry event {} {} on {}", event.id, event.action, event.target.repository);
+        if event.changes_repository() {
+            app_state
+                .registries
+                .default_client()
+                .invalidate_repository(&event.target.repository);
+        }
+        if app_state.activity.push(event.into()) {
+            recorded += 1;
+        }
+    }
+
+    ApiResponse::<Value>::success(
+        "Events processed",
+        Some(serde_json::json!({
+            "received": envelope.events.len(),
+            "recorded": recorded,
+        })),
+    )
+    .into_response()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    const ENVELOPE: &str = r#"{"events":[{"id":"1","timestamp":"2024-01-01T00:00:00Z","action":"push","target":{"mediaType":"application/vnd.docker.distribution.manifest.v2+json","digest":"sha256:abc","repository":"app","tag":"1.0"},"actor":{"name":"ci"}}]}"#;
+
+    fn request(secret: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", secret))
+            .header(header::CONTENT_TYPE, "application/vnd.docker.distribution.events.v1+json")
+            .body(Body::from(ENVELOPE))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events_are_recorded() {
+        let app_state = Arc::new(AppState::for_tests());
+        let app = router().with_state(app_state.clone());
+        let response = app.clone().oneshot(request("events-secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Un reintento del Registry no duplica la actividad
+        app.oneshot(request("events-secret")).await.unwrap();
+        let recent = app_state.activity.recent(10, |_| true);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].tag.as_deref(), Some("1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_secret_is_rejected() {
+        let app_state = Arc::new(AppState::for_tests());
+        let response = router().with_state(app_state.clone()).oneshot(request("wrong")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(app_state.activity.recent(10, |_| true).is_empty());
+    }
+}
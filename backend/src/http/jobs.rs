@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing, Extension, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::constants::JOB_HISTORY_SIZE;
+use crate::http::jwt_auth::require_admin;
+use crate::models::{Account, ApiResponse, AppState, AuditAction, AuditEntry, JobTrigger, run_retention_job};
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_jobs))
+        .route("/pause", routing::post(pause).layer(middleware::from_fn(require_admin)))
+        .route("/resume", routing::post(resume).layer(middleware::from_fn(require_admin)))
+        // La retención recorre todos los repositorios sin filtrar por ACL: sólo administradores
+        .route("/run", routing::post(run).layer(middleware::from_fn(require_admin)))
+}
+
+#[derive(Deserialize)]
+struct JobsParams {
+    limit: Option<usize>,
+}
+
+fn status(app_state: &AppState, limit: usize) -> Value {
+    let jobs = &app_state.jobs;
+    let runs: Vec<_> = jobs.runs().into_iter().take(limit).collect();
+    json!({
+        "paused": jobs.is_paused(),
+        "schedule": jobs.expression(),
+        "next_run": jobs.next_run(),
+        "runs": runs,
+    })
+}
+
+async fn get_jobs(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<JobsParams>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(JOB_HISTORY_SIZE);
+    ApiResponse::success("Trabajos programados", Some(status(&app_state, limit)))
+}
+
+async fn pause(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> impl IntoResponse {
+    info!("Pausing background jobs");
+    app_state.jobs.set_paused(true);
+    app_state.audit.record(AuditEntry::new(&account.username, AuditAction::JobsPause));
+    ApiResponse::success("Trabajos en pausa", Some(status(&app_state, 0)))
+}
+
+async fn resume(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> impl IntoResponse {
+    info!("Resuming background jobs");
+    app_state.jobs.set_paused(false);
+    app_state.audit.record(AuditEntry::new(&account.username, AuditAction::JobsResume));
+    ApiResponse::success("Trabajos reanudados", Some(status(&app_state, 0)))
+}
+
+// Lanza la retención en el momento, con el mismo registro que las ejecuciones programadas
+async fn run(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> impl IntoResponse {
+    match run_retention_job(&app_state, JobTrigger::Manual, &account.username).await {
+        Ok(run) => ApiResponse::success("Retención ejecutada", Some(run)).into_response(),
+        Err(message) => ApiResponse::<Value>::error(StatusCode::CONFLICT, &message).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AclRule, Permission};
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn post_as(app_state: Arc<AppState>, uri: &str, username: &str) -> StatusCode {
+        let mut request = Request::builder().method("POST").uri(uri).body(Body::empty()).unwrap();
+        request.extensions_mut().insert(app_state.users.get(username).unwrap());
+        router().with_state(app_state).oneshot(request).await.unwrap().status()
+    }
+
+    async fn post(app_state: Arc<AppState>, uri: &str) -> StatusCode {
+        post_as(app_state, uri, "admin").await
+    }
+
+    #[tokio::test]
+    async fn test_run_while_paused_is_conflict() {
+        let app_state = Arc::new(AppState::for_tests());
+        assert_eq!(post(app_state.clone(), "/pause").await, StatusCode::OK);
+        assert!(app_state.jobs.is_paused());
+        assert_eq!(post(app_state.clone(), "/run").await, StatusCode::CONFLICT);
+        assert_eq!(post(app_state.clone(), "/resume").await, StatusCode::OK);
+        assert!(!app_state.jobs.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_editor_cannot_run_retention() {
+        let app_state = Arc::new(AppState::for_tests());
+        // Un editor sin permiso de borrado en un repositorio no puede lanzar la retención sobre él
+        app_state
+            .acl
+            .replace(vec![AclRule {
+                registry: None,
+                repository: "private/*".to_string(),
+                users: vec!["editor".to_string()],
+                groups: Vec::new(),
+                permissions: vec![Permission::Read],
+            }])
+            .unwrap();
+        assert_eq!(post_as(app_state.clone(), "/run", "editor").await, StatusCode::FORBIDDEN);
+        assert!(app_state.jobs.runs().is_empty());
+    }
+}
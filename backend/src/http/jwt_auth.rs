@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::CookieJar;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde_json::Value;
+use tracing::{debug, error};
+
+use crate::models::{API_TOKEN_PREFIX, Account, ApiResponse, ApiTokenView, AppState, Role, TokenClaims};
+
+/// Middleware que exige un JWT válido emitido por `auth::login` o un token de API.
+///
+/// El token se busca primero en la cabecera `Authorization: Bearer <token>`
+/// y, si no está, en la cookie `token`. Los claims decodificados se insertan
+/// en las extensiones de la petición para que los handlers puedan usar
+/// `Extension<TokenClaims>`. Con un token de API se inserta además su
+/// `ApiTokenView`, y la cuenta lleva el rol limitado por sus scopes.
+pub async fn auth(
+    cookie_jar: CookieJar,
+    State(app_state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiResponse<Value>> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.trim().to_string())
+        .or_else(|| cookie_jar.get("token").map(|cookie| cookie.value().to_string()))
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| {
+            debug!("Request without token");
+            ApiResponse::error(StatusCode::UNAUTHORIZED, "You are not logged in, please provide token")
+        })?;
+
+    if token.starts_with(API_TOKEN_PREFIX) {
+        return api_token_auth(&app_state, &token, req, next).await;
+    }
+
+    let claims = decode::<TokenClaims>(
+        &token,
+        &DecodingKey::from_secret(app_state.secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|e| {
+        error!("Invalid token: {}", e);
+        ApiResponse::error(StatusCode::UNAUTHORIZED, "Invalid token")
+    })?
+    .claims;
+
+    // La sesión se revoca en el servidor al hacer logout
+    if !claims.sid.as_deref().is_some_and(|sid| app_state.sessions.is_active(sid)) {
+        debug!("Token for a closed session: {:?}", claims.sid);
+        return Err(ApiResponse::error(StatusCode::UNAUTHORIZED, "Session expired or revoked"));
+    }
+
+    // Se consulta la cuenta en cada petición para que deshabilitarla surta efecto al momento
+    let Some(account) = app_state.users.active(&claims.sub) else {
+        error!("Token for unknown or disabled user: {}", claims.sub);
+        return Err(ApiResponse::error(
+            StatusCode::UNAUTHORIZED,
+            "The user belonging to this token no longer exists",
+        ));
+    };
+
+    req.extensions_mut().insert(claims);
+    req.extensions_mut().insert(account);
+    Ok(next.run(req).await)
+}
+
+async fn api_token_auth(
+    app_state: &AppState,
+    token: &str,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiResponse<Value>> {
+    let api_token = app_state.api_tokens.verify(token).ok_or_else(|| {
+        debug!("Invalid, expired or revoked API token");
+        ApiResponse::error(StatusCode::UNAUTHORIZED, "Invalid token")
+    })?;
+    let Some(account) = app_state.users.active(&api_token.owner) else {
+        error!("API token {} for unknown or disabled user: {}", api_token.name, api_token.owner);
+        return Err(ApiResponse::error(
+            StatusCode::UNAUTHORIZED,
+            "The user belonging to this token no longer exists",
+        ));
+    };
+
+    let claims = TokenClaims {
+        sub: api_token.owner.clone(),
+        iat: chrono::Utc::now().timestamp() as usize,
+        exp: api_token.expires_at.map_or(usize::MAX, |exp| exp.timestamp() as usize),
+        sid: None,
+    };
+    req.extensions_mut().insert(claims);
+    req.extensions_mut().insert(api_token.restrict(account));
+    req.extensions_mut().insert(ApiTokenView::from(&api_token));
+    Ok(next.run(req).await)
+}
+
+/// Exige que el usuario autenticado por `auth` tenga al menos el rol `required`.
+fn check_role(req: &Request<Body>, required: Role) -> Result<(), ApiResponse<Value>> {
+    match req.extensions().get::<Account>() {
+        Some(account) if account.role.allows(required) => Ok(()),
+        Some(account) => {
+            debug!("User {} ({:?}) lacks role {:?}", account.username, account.role, required);
+            Err(ApiResponse::error(StatusCode::FORBIDDEN, "You do not have permission to perform this action"))
+        }
+        None => Err(ApiResponse::error(StatusCode::UNAUTHORIZED, "You are not logged in, please provide token")),
+    }
+}
+
+/// Middleware para las rutas que modifican el Registry (borrar tags, aplicar retención...).
+pub async fn require_editor(req: Request<Body>, next: Next) -> Result<Response, ApiResponse<Value>> {
+    check_role(&req, Role::Editor)?;
+    Ok(next.run(req).await)
+}
+
+/// Middleware para la administración de usuarios y la configuración.
+pub async fn require_admin(req: Request<Body>, next: Next) -> Result<Response, ApiResponse<Value>> {
+    check_role(&req, Role::Admin)?;
+    Ok(next.run(req).await)
+}
+
+/// Middleware para las rutas de la propia cuenta (tokens de API, 2FA): no admite tokens de API.
+pub async fn require_session(req: Request<Body>, next: Next) -> Result<Response, ApiResponse<Value>> {
+    if req.extensions().get::<ApiTokenView>().is_some() {
+        debug!("API token used on a session-only route: {}", req.uri());
+        return Err(ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "This action requires a login session, not an API token",
+        ));
+    }
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing, Router};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use tower::ServiceExt;
+
+    const SECRET: &str = "test-secret";
+
+    // Estado compartido para que las sesiones de `token` sean visibles desde `app`
+    fn state() -> Arc<AppState> {
+        static STATE: std::sync::OnceLock<Arc<AppState>> = std::sync::OnceLock::new();
+        STATE.get_or_init(|| Arc::new(AppState::for_tests())).clone()
+    }
+
+    fn app() -> Router {
+        let app_state = state();
+        Router::new()
+            .nest(
+                "/registry",
+                Router::new()
+                    .route("/", routing::get(|| async { "ok" }))
+                    .route("/tags", routing::delete(|| async { "ok" }).layer(middleware::from_fn(require_editor))),
+            )
+            .nest(
+                "/users",
+                Router::new()
+                    .route("/", routing::get(|| async { "ok" }))
+                    .layer(middleware::from_fn(require_admin)),
+            )
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+            .with_state(app_state)
+    }
+
+    fn token_for_session(sub: &str, minutes: i64, secret: &str, sid: Option<String>) -> String {
+        let now = chrono::Utc::now();
+        let claims = TokenClaims {
+            sub: sub.to_string(),
+            iat: now.timestamp() as usize,
+            exp: (now + chrono::Duration::minutes(minutes)).timestamp() as usize,
+            sid,
+        };
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    fn token(sub: &str, minutes: i64, secret: &str) -> String {
+        let (sid, _) = state().sessions.create(sub);
+        token_for_session(sub, minutes, secret, Some(sid))
+    }
+
+    async fn status_for(request: Request<Body>) -> StatusCode {
+        app().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_missing_token_is_rejected() {
+        let request = Request::builder().uri("/registry").body(Body::empty()).unwrap();
+        assert_eq!(status_for(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_valid_bearer_token_is_accepted() {
+        let request = Request::builder()
+            .uri("/registry")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token("admin", 60, SECRET)))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status_for(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_valid_cookie_token_is_accepted() {
+        let request = Request::builder()
+            .uri("/registry")
+            .header(header::COOKIE, format!("token={}", token("admin", 60, SECRET)))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status_for(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_rejected() {
+        let request = Request::builder()
+            .uri("/registry")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token("admin", -120, SECRET)))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status_for(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_forged_token_is_rejected() {
+        let request = Request::builder()
+            .uri("/registry")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token("admin", 60, "other-secret")))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status_for(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_cleared_cookie_is_rejected() {
+        let request = Request::builder()
+            .uri("/registry")
+            .header(header::COOKIE, "token=")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status_for(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    fn request(method: &str, uri: &str, sub: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token(sub, 60, SECRET)))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_roles_are_enforced() {
+        assert_eq!(status_for(request("GET", "/registry", "viewer")).await, StatusCode::OK);
+        assert_eq!(status_for(request("DELETE", "/registry/tags", "viewer")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status_for(request("DELETE", "/registry/tags", "editor")).await, StatusCode::OK);
+        assert_eq!(status_for(request("GET", "/users", "editor")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status_for(request("GET", "/users", "admin")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_user_is_rejected() {
+        assert_eq!(status_for(request("GET", "/registry", "disabled")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_for(request("GET", "/registry", "ghost")).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_session_is_rejected() {
+        let (sid, _) = state().sessions.create("admin");
+        let token = token_for_session("admin", 60, SECRET, Some(sid.clone()));
+        let bearer = |token: &str| {
+            Request::builder()
+                .uri("/registry")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(status_for(bearer(&token)).await, StatusCode::OK);
+        state().sessions.revoke(&sid);
+        assert_eq!(status_for(bearer(&token)).await, StatusCode::UNAUTHORIZED);
+        // Los tokens sin sesión tampoco valen
+        let sessionless = token_for_session("admin", 60, SECRET, None);
+        assert_eq!(status_for(bearer(&sessionless)).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -3,6 +3,17 @@ use crate::models::ApiResponse;
 pub mod health;
 pub mod auth;
 pub mod registry;
+pub mod registries;
+pub mod copy;
+pub mod jwt_auth;
+pub mod events;
+pub mod retention;
+pub mod jobs;
+pub mod users;
+pub mod acl;
+pub mod api_tokens;
+pub mod totp;
+pub mod audit;
 
 pub async fn fallback_404() -> impl axum::response::IntoResponse {
     ApiResponse::<serde_json::Value>::success( "Not found",None
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    routing, Extension, Router,
+};
+use tracing::debug;
+
+use super::registry;
+use crate::models::{Account, ApiResponse, AppState, CatalogQuery, Permission};
+
+// Todos los Registries configurados. Cada uno tiene las mismas rutas que
+// `/registry` bajo `/registries/{registry}`.
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_registries))
+        .route("/_catalog", routing::get(get_catalog))
+        .nest("/{registry}", registry::router())
+}
+
+async fn get_registries(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    ApiResponse::success("Registries", Some(app_state.registries.list()))
+}
+
+async fn get_catalog(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Query(catalog_query): Query<CatalogQuery>,
+) -> impl IntoResponse {
+    debug!("Fetching combined catalog: {:?}", catalog_query);
+    app_state
+        .registries
+        .get_catalog(&catalog_query, |registry, name| {
+            app_state.acl.allows(&account, registry, name, Permission::Read)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AclRule, Permission, RegistryClient, Registries};
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn get(app_state: Arc<AppState>, uri: &str) -> (StatusCode, Value) {
+        let mut request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        request.extensions_mut().insert(app_state.users.get("viewer").unwrap());
+        let response = router().with_state(app_state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_scoped_routes() {
+        let mut app_state = AppState::for_tests();
+        let mut registries =
+            Registries::new("prod", RegistryClient::new("http://127.0.0.1:1".into(), String::new())).unwrap();
+        registries
+            .add("staging", RegistryClient::new("http://127.0.0.1:1".into(), String::new()))
+            .unwrap();
+        app_state.registries = registries;
+        let app_state = Arc::new(app_state);
+
+        let (status, body) = get(app_state.clone(), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][1]["name"], "staging");
+        assert_eq!(get(app_state.clone(), "/unknown/app/tags").await.0, StatusCode::NOT_FOUND);
+        // El Registry existe pero no responde: el error es del Registry, no de la ruta
+        let (status, body) = get(app_state.clone(), "/staging/app/manifests/latest").await;
+        assert_ne!(status, StatusCode::NOT_FOUND);
+        assert_ne!(body["message"], "El Registry 'staging' no existe");
+        let (_, body) = get(app_state.clone(), "/_catalog").await;
+        assert_eq!(body["data"]["unavailable"], serde_json::json!(["prod", "staging"]));
+        // Solo el Registry principal recibe notificaciones
+        assert_eq!(get(app_state.clone(), "/prod/activity").await.0, StatusCode::OK);
+        assert_eq!(get(app_state.clone(), "/staging/activity").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_acl_rules_scoped_to_a_registry() {
+        let mut app_state = AppState::for_tests();
+        let mut registries =
+            Registries::new("prod", RegistryClient::new("http://127.0.0.1:1".into(), String::new())).unwrap();
+        registries
+            .add("staging", RegistryClient::new("http://127.0.0.1:1".into(), String::new()))
+            .unwrap();
+        app_state.registries = registries;
+        app_state
+            .acl
+            .replace(vec![AclRule {
+                registry: Some("staging".into()),
+                repository: "app".into(),
+                users: vec!["admin".into()],
+                groups: vec![],
+                permissions: vec![Permission::Read],
+            }])
+            .unwrap();
+        let app_state = Arc::new(app_state);
+
+        let (status, body) = get(app_state.clone(), "/staging/app/tags").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["message"], "Repositorio no encontrado");
+        // En `prod` la regla no aplica: la petición llega al Registry
+        let (_, body) = get(app_state.clone(), "/prod/app/tags").await;
+        assert_ne!(body["message"], "Repositorio no encontrado");
+    }
+}
@@ -2,19 +2,39 @@ use serde::Deserialize;
 use super::ApiResponse;
 use axum::{
     Router,
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{FromRequestParts, Path, Query, State},
+    middleware,
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
     routing,
+    Extension,
+    Json,
 };
 
 use crate::AppState;
+use crate::constants::ACTIVITY_FEED_SIZE;
+use crate::http::jwt_auth::require_editor;
+use crate::models::{Account, AuditAction, AuditEntry, CatalogQuery, Permission, RegistryClient, TagQuery};
 
-use std::sync::Arc;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
 use tracing::debug;
 
+// `{repo}` es un único segmento: los repositorios con espacio de nombres
+// (`team/app`) se piden con la barra codificada, `team%2Fapp`. Axum solo admite
+// comodines al final de la ruta, así que no hay otra forma de seguir con `/tags/{tag}`.
 pub fn router() -> Router<Arc<AppState>> {
-    Router::new().route("/", routing::get(get_repositories))
+    Router::new()
+        .route("/", routing::get(get_repositories))
+        .route("/cache/refresh", routing::post(refresh_cache).layer(middleware::from_fn(require_editor)))
+        .route("/activity", routing::get(get_activity))
+        .route("/{repo}/tags", routing::get(get_tags))
+        .route(
+            "/{repo}/tags/{tag}",
+            routing::put(put_tag).delete(delete_tag).layer(middleware::from_fn(require_editor)),
+        )
+        .route("/{repo}/manifests/{reference}", routing::get(get_manifest))
 }
 
 #[derive(Deserialize)]
@@ -22,10 +42,122 @@ struct Params {
     repository: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct ActivityParams {
+    limit: Option<usize>,
+}
+
+// Los parámetros de ruta van por nombre porque bajo `/registries/{registry}` llega uno más
+#[derive(Deserialize)]
+struct RepoPath {
+    repo: String,
+}
+
+#[derive(Deserialize)]
+struct TagPath {
+    repo: String,
+    tag: String,
+}
+
+// Cuerpo de `PUT /{repo}/tags/{tag}`: el tag o digest al que tiene que apuntar
+#[derive(Deserialize)]
+struct RetagRequest {
+    source: String,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+struct ManifestPath {
+    repo: String,
+    reference: String,
+}
+
+// Registry de la petición: el de `/registries/{registry}/...` o el principal en `/registry/...`
+pub struct SelectedRegistry {
+    name: String,
+    client: RegistryClient,
+}
+
+impl FromRequestParts<Arc<AppState>> for SelectedRegistry {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, app_state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let name = Path::<HashMap<String, String>>::from_request_parts(parts, app_state)
+            .await
+            .ok()
+            .and_then(|Path(mut params)| params.remove("registry"));
+        let registries = &app_state.registries;
+        let name = name.unwrap_or_else(|| registries.default_name().to_string());
+        match registries.get(&name) {
+            Some(client) => Ok(Self { client: client.clone(), name }),
+            None => Err(ApiResponse::error(StatusCode::NOT_FOUND, &format!("El Registry '{}' no existe", name))
+                .into_response()),
+        }
+    }
+}
+
+// Los repositorios que el usuario no puede ver se tratan como inexistentes;
+// si puede verlos pero no tiene el permiso pedido, 403.
+// El nombre se valida antes que nada: la ruta llega ya decodificada, así que
+// `a%2F..%2Fsecret` sería `a/../secret` y casaría con las reglas de `a/*`.
+pub fn check_access(
+    app_state: &AppState,
+    account: &Account,
+    registry: &str,
+    repo: &str,
+    permission: Permission,
+) -> Result<(), (StatusCode, String)> {
+    if !valid_repository(repo) {
+        return Err((StatusCode::BAD_REQUEST, format!("'{}' no es un nombre de repositorio válido", repo)));
+    }
+    if app_state.acl.allows(account, registry, repo, permission) {
+        return Ok(());
+    }
+    debug!("User {} denied {:?} on {}/{}", account.username, permission, registry, repo);
+    if permission == Permission::Read || !app_state.acl.allows(account, registry, repo, Permission::Read) {
+        return Err((StatusCode::NOT_FOUND, "Repositorio no encontrado".to_string()));
+    }
+    Err((StatusCode::FORBIDDEN, "No tienes permiso sobre este repositorio".to_string()))
+}
+
+// Gramática de nombres de repositorio de la distribution spec: componentes en
+// minúsculas separados por `/`, sin segmentos vacíos, `.` ni `..`
+pub fn valid_repository(name: &str) -> bool {
+    static NAME: LazyLock<Regex> = LazyLock::new(|| {
+        let component = r"[a-z0-9]+(?:(?:\.|_|__|-+)[a-z0-9]+)*";
+        Regex::new(&format!("^{0}(?:/{0})*$", component)).unwrap()
+    });
+    name.len() <= 255 && NAME.is_match(name)
+}
+
+// Gramática de tags de la distribution spec
+pub fn valid_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    tag.len() <= 128
+        && chars.next().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+// Digest sha256 completo, el único algoritmo que usan los Registries
+pub fn valid_digest(digest: &str) -> bool {
+    digest
+        .strip_prefix("sha256:")
+        .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')))
+}
+
+// Referencia a una imagen: un tag o un digest
+pub fn valid_reference(reference: &str) -> bool {
+    valid_tag(reference) || valid_digest(reference)
+}
+
 #[axum::debug_handler]
 async fn get_repositories(
     State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
     Query(params): Query<Params>,
+    Query(catalog_query): Query<CatalogQuery>,
 ) -> impl IntoResponse {
     match &params.repository {
         Some(repo) => {
@@ -37,20 +169,435 @@ async fn get_repositories(
                 )
                 .into_response();
             }
+            if let Err((status, message)) = check_access(&app_state, &account, &registry.name, repo, Permission::Read) {
+                return ApiResponse::error(status, &message).into_response();
+            }
             debug!("Fetching tags for repository: {}", repo);
-            app_state
-                .registry_client
-                .clone()
+            registry
+                .client
                 .get_tags(repo)
                 .await
                 .into_response()
         }
         _ => {
-            debug!("Fetching all repositories");
-            app_state.registry_client.clone()
-                .get_catalog()
+            debug!("Fetching repositories: {:?}", catalog_query);
+            registry
+                .client
+                .get_catalog(&catalog_query, |name| {
+                    app_state.acl.allows(&account, &registry.name, name, Permission::Read)
+                })
                 .await
                 .into_response()
         }
     }
 }
+
+
+async fn delete_tag(
+    State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
+    Path(TagPath { repo, tag }): Path<TagPath>,
+) -> impl IntoResponse {
+    // El tag acaba en la URL del manifiesto: `..%2F..%2Fother%2Fmanifests%2Flatest` saldría de `repo`
+    if !valid_tag(&tag) {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, &format!("'{}' no es un tag válido", tag)).into_response();
+    }
+    if let Err((status, message)) = check_access(&app_state, &account, &registry.name, &repo, Permission::Delete) {
+        return ApiResponse::error(status, &message).into_response();
+    }
+    debug!("Deleting tag {} from repository {}", tag, repo);
+    let result = registry.client.delete_manifest(&repo, &tag).await;
+    let entry = AuditEntry::new(&account.username, AuditAction::TagDelete)
+        .registry(&registry.name)
+        .repository(&repo)
+        .tag(&tag);
+    app_state.audit.record(match &result {
+        Ok(digest) => entry.digest(digest),
+        Err(_) => entry.result(&result),
+    });
+    match result {
+        Ok(digest) => ApiResponse::success(
+            "Tag eliminado correctamente",
+            Some(serde_json::json!({
+                "registry": registry.name,
+                "repository": repo,
+                "tag": tag,
+                "digest": digest,
+            })),
+        )
+        .into_response(),
+        Err((status, message)) => ApiResponse::error(status, &message).into_response(),
+    }
+}
+
+async fn put_tag(
+    State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
+    Path(TagPath { repo, tag }): Path<TagPath>,
+    Json(request): Json<RetagRequest>,
+) -> impl IntoResponse {
+    if !valid_tag(&tag) {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, &format!("'{}' no es un tag válido", tag)).into_response();
+    }
+    // `source` acaba en la URL del manifiesto: sin validar, `../..` saldría de `repo`
+    if !valid_reference(&request.source) {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            &format!("'{}' no es un tag ni un digest válido", request.source),
+        )
+        .into_response();
+    }
+    if let Err((status, message)) = check_access(&app_state, &account, &registry.name, &repo, Permission::Delete) {
+        return ApiResponse::error(status, &message).into_response();
+    }
+    debug!("Tagging {} in repository {} as {}", request.source, repo, tag);
+    let result = registry.client.retag(&repo, &request.source, &tag, request.force).await;
+    let entry = AuditEntry::new(&account.username, AuditAction::TagCreate)
+        .registry(&registry.name)
+        .repository(&repo)
+        .tag(&tag);
+    app_state.audit.record(match &result {
+        Ok(digest) => entry.digest(digest).detail(format!("from {}", request.source)),
+        Err(_) => entry.result(&result),
+    });
+    match result {
+        Ok(digest) => (
+            StatusCode::CREATED,
+            ApiResponse::success(
+                "Tag creado correctamente",
+                Some(serde_json::json!({
+                    "registry": registry.name,
+                    "repository": repo,
+                    "tag": tag,
+                    "source": request.source,
+                    "digest": digest,
+                })),
+            ),
+        )
+            .into_response(),
+        Err((status, message)) => ApiResponse::error(status, &message).into_response(),
+    }
+}
+
+async fn get_manifest(
+    State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
+    Path(ManifestPath { repo, reference }): Path<ManifestPath>,
+) -> impl IntoResponse {
+    if !valid_reference(&reference) {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            &format!("'{}' no es un tag ni un digest válido", reference),
+        )
+        .into_response();
+    }
+    if let Err((status, message)) = check_access(&app_state, &account, &registry.name, &repo, Permission::Read) {
+        return ApiResponse::error(status, &message).into_response();
+    }
+    debug!("Fetching manifest {} for repository {}", reference, repo);
+    registry
+        .client
+        .fetch_manifest_info(&repo, &reference)
+        .await
+        .into_response()
+}
+
+async fn get_tags(
+    State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
+    Path(RepoPath { repo }): Path<RepoPath>,
+    Query(tag_query): Query<TagQuery>,
+) -> impl IntoResponse {
+    if let Err((status, message)) = check_access(&app_state, &account, &registry.name, &repo, Permission::Read) {
+        return ApiResponse::error(status, &message).into_response();
+    }
+    debug!("Fetching tags for repository {}: {:?}", repo, tag_query);
+    registry
+        .client
+        .get_tags_page(&repo, &tag_query)
+        .await
+        .into_response()
+}
+
+async fn refresh_cache(
+    State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
+    Query(params): Query<Params>,
+) -> impl IntoResponse {
+    if let Some(repo) = params.repository.as_deref().filter(|r| !r.is_empty())
+        && let Err((status, message)) = check_access(&app_state, &account, &registry.name, repo, Permission::Read)
+    {
+        return ApiResponse::error(status, &message).into_response();
+    }
+    debug!("Refreshing cache of {}: {:?}", registry.name, params.repository);
+    registry
+        .client
+        .refresh_cache(params.repository.as_deref().filter(|r| !r.is_empty()))
+        .await
+        .into_response()
+}
+
+// Las notificaciones (`/events`) solo se reciben del Registry principal, así que
+// los demás no tienen feed
+async fn get_activity(
+    State(app_state): State<Arc<AppState>>,
+    registry: SelectedRegistry,
+    Extension(account): Extension<Account>,
+    Query(params): Query<ActivityParams>,
+) -> impl IntoResponse {
+    if registry.name != app_state.registries.default_name() {
+        return ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            &format!("El Registry '{}' no envía notificaciones; solo el principal tiene actividad", registry.name),
+        )
+        .into_response();
+    }
+    let activity = app_state
+        .activity
+        .recent(params.limit.unwrap_or(ACTIVITY_FEED_SIZE), |repo| {
+            app_state.acl.allows(&account, &registry.name, repo, Permission::Read)
+        });
+    ApiResponse::success("Actividad reciente", Some(activity)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AclRule;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn send(app_state: Arc<AppState>, as_user: &str, method: &str, uri: &str) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        request.extensions_mut().insert(app_state.users.get(as_user).unwrap());
+        router().with_state(app_state).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_retag_keeps_manifest_bytes() {
+        use crate::models::{RegistryClient, Registries};
+        use axum::{body::Bytes, http::HeaderMap};
+        use sha2::{Digest, Sha256};
+        use std::sync::Mutex;
+
+        // Espacios y orden de claves que una nueva serialización no conservaría
+        const MANIFEST: &str = "{\n  \"schemaVersion\": 2,\n  \"layers\": [],\n  \"config\": {\"size\": 2, \"mediaType\": \"application/vnd.oci.image.config.v1+json\", \"digest\": \"sha256:cfg\"}\n}";
+        let pushed = Arc::new(Mutex::new(Vec::new()));
+        let recorder = pushed.clone();
+        let fake = Router::new().route(
+            "/v2/app/manifests/{reference}",
+            routing::get(|| async { MANIFEST })
+                .head(|Path(reference): Path<String>| async move {
+                    let mut headers = HeaderMap::new();
+                    if reference != "taken" {
+                        return (StatusCode::NOT_FOUND, headers);
+                    }
+                    headers.insert("Docker-Content-Digest", "sha256:other".parse().unwrap());
+                    (StatusCode::OK, headers)
+                })
+                .put(move |Path(reference): Path<String>, body: Bytes| async move {
+                    recorder.lock().unwrap().push((reference, body));
+                    StatusCode::CREATED
+                }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, fake).await.unwrap() });
+        let mut app_state = AppState::for_tests();
+        app_state.registries = Registries::new("default", RegistryClient::new(url, String::new())).unwrap();
+        let app_state = Arc::new(app_state);
+
+        let put = |uri: &'static str, body: &'static str| {
+            let app_state = app_state.clone();
+            async move {
+                let mut request = Request::builder()
+                    .method("PUT")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap();
+                request.extensions_mut().insert(app_state.users.get("editor").unwrap());
+                let response = router().with_state(app_state).oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, body) = put("/app/tags/1.4.0", r#"{"source": "rc-42"}"#).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let digest = format!("sha256:{}", hex::encode(Sha256::digest(MANIFEST.as_bytes())));
+        assert_eq!(body["data"]["digest"], digest);
+        assert_eq!(pushed.lock().unwrap()[0], ("1.4.0".to_string(), Bytes::from(MANIFEST)));
+
+        // Un tag que ya apunta a otra imagen solo se mueve con `force`
+        assert_eq!(put("/app/tags/taken", r#"{"source": "rc-42"}"#).await.0, StatusCode::CONFLICT);
+        assert_eq!(put("/app/tags/taken", r#"{"source": "rc-42", "force": true}"#).await.0, StatusCode::CREATED);
+        assert_eq!(put("/app/tags/not:a:tag", r#"{"source": "rc-42"}"#).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(put("/app/tags/1.5.0", r#"{"source": "../../other/manifests/v1"}"#).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(put("/app/tags/1.5.0", r#"{"source": "sha256:abc"}"#).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(pushed.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_namespaced_repository_is_percent_encoded() {
+        use crate::models::{RegistryClient, Registries};
+        use axum::http::HeaderMap;
+        use std::sync::Mutex;
+
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let recorder = deleted.clone();
+        let fake = Router::new().route(
+            "/v2/team/app/manifests/{reference}",
+            routing::head(|| async {
+                let mut headers = HeaderMap::new();
+                headers.insert("Docker-Content-Digest", "sha256:abc".parse().unwrap());
+                (StatusCode::OK, headers)
+            })
+            .delete(move |Path(reference): Path<String>| async move {
+                recorder.lock().unwrap().push(reference);
+                StatusCode::ACCEPTED
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, fake).await.unwrap() });
+        let mut app_state = AppState::for_tests();
+        app_state.registries = Registries::new("default", RegistryClient::new(url, String::new())).unwrap();
+        let app_state = Arc::new(app_state);
+
+        // `team/app` ocupa un solo segmento de la ruta codificado como `team%2Fapp`
+        assert_eq!(send(app_state.clone(), "editor", "DELETE", "/team%2Fapp/tags/v1").await, StatusCode::OK);
+        assert_eq!(*deleted.lock().unwrap(), vec!["sha256:abc".to_string()]);
+        // Sin codificar, la ruta no existe
+        assert_eq!(send(app_state.clone(), "editor", "DELETE", "/team/app/tags/v1").await, StatusCode::NOT_FOUND);
+    }
+
+    // Registry falso que anota la ruta de cada petición que le llega
+    async fn spawn_recording_registry() -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = requests.clone();
+        let fake = Router::new().fallback(move |uri: axum::http::Uri| async move {
+            recorder.lock().unwrap().push(uri.path().to_string());
+            StatusCode::NOT_FOUND
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, fake).await.unwrap() });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_tag_cannot_escape_the_repository() {
+        use crate::models::{RegistryClient, Registries};
+
+        let (url, requests) = spawn_recording_registry().await;
+        let mut app_state = AppState::for_tests();
+        app_state.registries = Registries::new("default", RegistryClient::new(url, String::new())).unwrap();
+        let app_state = Arc::new(app_state);
+
+        let uri = "/app/tags/..%2F..%2Fother%2Fmanifests%2Flatest";
+        assert_eq!(send(app_state.clone(), "editor", "DELETE", uri).await, StatusCode::BAD_REQUEST);
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_reference_cannot_escape_the_repository() {
+        use crate::models::{RegistryClient, Registries};
+
+        let (url, requests) = spawn_recording_registry().await;
+        let mut app_state = AppState::for_tests();
+        app_state.registries = Registries::new("default", RegistryClient::new(url, String::new())).unwrap();
+        let app_state = Arc::new(app_state);
+
+        let uri = "/app/manifests/..%2F..%2Fother%2Fmanifests%2Flatest";
+        assert_eq!(send(app_state.clone(), "viewer", "GET", uri).await, StatusCode::BAD_REQUEST);
+        assert!(requests.lock().unwrap().is_empty());
+        // Una referencia válida sí llega al Registry
+        send(app_state.clone(), "viewer", "GET", "/app/manifests/latest").await;
+        assert_eq!(*requests.lock().unwrap(), vec!["/v2/app/manifests/latest".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_acl_hides_and_protects_repositories() {
+        let app_state = Arc::new(AppState::for_tests());
+        app_state
+            .acl
+            .replace(vec![AclRule {
+                registry: None,
+                repository: "private*".into(),
+                users: vec!["editor".into()],
+                groups: vec![],
+                permissions: vec![Permission::Read],
+            }])
+            .unwrap();
+
+        // Sin permiso de lectura el repositorio no existe para el usuario
+        assert_eq!(send(app_state.clone(), "viewer", "GET", "/private/tags").await, StatusCode::NOT_FOUND);
+        assert_eq!(
+            send(app_state.clone(), "viewer", "GET", "/private/manifests/latest").await,
+            StatusCode::NOT_FOUND
+        );
+        // Puede verlo, pero no borrar
+        assert_eq!(
+            send(app_state.clone(), "editor", "DELETE", "/private/tags/v1").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send(app_state.clone(), "viewer", "POST", "/cache/refresh?repository=private").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send(app_state.clone(), "editor", "POST", "/cache/refresh?repository=private").await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dot_segments_do_not_escape_acl() {
+        let app_state = Arc::new(AppState::for_tests());
+        app_state
+            .acl
+            .replace(vec![AclRule {
+                registry: None,
+                repository: "public/*".into(),
+                users: vec!["*".into()],
+                groups: vec![],
+                permissions: vec![Permission::Read],
+            }])
+            .unwrap();
+        // `%2F` llega decodificado al handler: `public/../secret`
+        let uri = "/public%2F..%2Fsecret/tags";
+        assert_eq!(send(app_state.clone(), "viewer", "GET", uri).await, StatusCode::BAD_REQUEST);
+        let uri = "/?repository=public/../secret";
+        assert_eq!(send(app_state.clone(), "viewer", "GET", uri).await, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            send(app_state.clone(), "editor", "POST", "/cache/refresh?repository=Public").await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_valid_reference() {
+        let digest = format!("sha256:{}", "a1".repeat(32));
+        assert!(valid_reference("v1.2.3") && valid_reference(&digest));
+        assert!(!valid_reference("sha256:abc"));
+        assert!(!valid_reference(&digest.to_uppercase()));
+        assert!(!valid_reference("../latest"));
+    }
+
+    #[test]
+    fn test_valid_repository() {
+        for name in ["app", "team/app", "a/b/c", "my-app", "my__app", "my.app", "a--b"] {
+            assert!(valid_repository(name), "{}", name);
+        }
+        for name in ["", "App", "a/../b", "..", "a/./b", "a//b", "/a", "a/", "-a", "a_", "a___b", "a b"] {
+            assert!(!valid_repository(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing, Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, info};
+
+use super::registry::check_access;
+use crate::http::jwt_auth::{require_admin, require_editor};
+use crate::models::{
+    Account, ApiResponse, AppState, AuditAction, AuditEntry, Permission, RetentionPlan, RetentionPolicy,
+    apply_retention, plan_hash, plan_retention,
+};
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/policies",
+            routing::get(get_policies).merge(routing::put(put_policies).layer(middleware::from_fn(require_admin))),
+        )
+        .route("/dry-run", routing::post(dry_run))
+        .route("/apply", routing::post(apply).layer(middleware::from_fn(require_editor)))
+}
+
+#[derive(Deserialize)]
+struct Params {
+    repository: Option<String>,
+}
+
+// `plan_hash` de la simulación que se ha revisado
+#[derive(Deserialize)]
+struct ApplyRequest {
+    plan_hash: String,
+}
+
+async fn get_policies(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    ApiResponse::success("Políticas de retención", Some(app_state.retention.policies()))
+}
+
+async fn put_policies(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Json(policies): Json<Vec<RetentionPolicy>>,
+) -> impl IntoResponse {
+    let count = policies.len();
+    let result = app_state.retention.replace(policies);
+    let entry = AuditEntry::new(&account.username, AuditAction::RetentionPoliciesUpdate);
+    app_state.audit.record(match &result {
+        Ok(()) => entry.detail(format!("{} políticas", count)),
+        Err(message) => entry.failed(message.as_str()),
+    });
+    match result {
+        Ok(()) => ApiResponse::success("Políticas de retención guardadas", Some(app_state.retention.policies()))
+            .into_response(),
+        Err(message) => ApiResponse::error(StatusCode::BAD_REQUEST, &message).into_response(),
+    }
+}
+
+// Planes de los repositorios sobre los que el usuario tiene `permission`.
+// Con `?repository=` se comprueba ese repositorio como en el resto de la API.
+async fn visible_plans(
+    app_state: &AppState,
+    account: &Account,
+    params: &Params,
+    permission: Permission,
+) -> Result<Vec<RetentionPlan>, (StatusCode, String)> {
+    let registry = app_state.registries.default_name();
+    let only = params.repository.as_deref().filter(|r| !r.is_empty());
+    if let Some(repo) = only {
+        check_access(app_state, account, registry, repo, permission)?;
+    }
+    let plans = plan_retention(app_state.registries.default_client(), &app_state.retention, only).await?;
+    Ok(plans
+        .into_iter()
+        .filter(|plan| app_state.acl.allows(account, registry, &plan.repository, permission))
+        .collect())
+}
+
+// Devuelve exactamente los tags y digests que `apply` borraría, sin tocar el Registry
+async fn dry_run(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Query(params): Query<Params>,
+) -> impl IntoResponse {
+    debug!("Retention dry-run: {:?}", params.repository);
+    match visible_plans(&app_state, &account, &params, Permission::Read).await {
+        Ok(plans) => ApiResponse::success(
+            "Simulación de retención",
+            Some(serde_json::json!({"plan_hash": plan_hash(&plans), "plans": plans})),
+        )
+        .into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+async fn apply(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Query(params): Query<Params>,
+    Json(request): Json<ApplyRequest>,
+) -> impl IntoResponse {
+    // La huella se calcula sobre lo mismo que mostró la simulación
+    let plans = match visible_plans(&app_state, &account, &params, Permission::Read).await {
+        Ok(plans) => plans,
+        Err((status, message)) => return ApiResponse::<Value>::error(status, &message).into_response(),
+    };
+    // Entre la simulación y ahora pueden haber cambiado los tags o las políticas
+    if plan_hash(&plans) != request.plan_hash {
+        return ApiResponse::<Value>::error(
+            StatusCode::CONFLICT,
+            "El plan de retención ha cambiado desde la simulación; revísalo de nuevo",
+        )
+        .into_response();
+    }
+    // De lo revisado, solo se borra donde el usuario puede borrar
+    let registry = app_state.registries.default_name();
+    if let Some(repo) = params.repository.as_deref().filter(|r| !r.is_empty())
+        && let Err((status, message)) = check_access(&app_state, &account, registry, repo, Permission::Delete)
+    {
+        return ApiResponse::<Value>::error(status, &message).into_response();
+    }
+    let plans: Vec<_> = plans
+        .into_iter()
+        .filter(|plan| app_state.acl.allows(&account, registry, &plan.repository, Permission::Delete))
+        .collect();
+    info!("Applying retention to {} repositories", plans.len());
+    let results = apply_retention(app_state.registries.default_client(), plans).await;
+    app_state.audit.record_retention(&account.username, &results);
+    ApiResponse::success("Retención aplicada", Some(results)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AclRule;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn post(app_state: Arc<AppState>, uri: &str, body: Value) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        request.extensions_mut().insert(app_state.users.get("editor").unwrap());
+        let response = router().with_state(app_state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_apply_requires_reviewed_plan() {
+        let app_state = Arc::new(AppState::for_tests());
+        let (status, body) = post(app_state.clone(), "/dry-run?repository=app", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let hash = body["data"]["plan_hash"].as_str().unwrap().to_string();
+
+        let stale = serde_json::json!({"plan_hash": "0000"});
+        assert_eq!(post(app_state.clone(), "/apply?repository=app", stale).await.0, StatusCode::CONFLICT);
+        let reviewed = serde_json::json!({"plan_hash": hash});
+        assert_eq!(post(app_state.clone(), "/apply?repository=app", reviewed).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_repository_parameter_is_checked() {
+        let app_state = Arc::new(AppState::for_tests());
+        app_state
+            .acl
+            .replace(vec![AclRule {
+                registry: None,
+                repository: "private".into(),
+                users: vec!["editor".into()],
+                groups: vec![],
+                permissions: vec![Permission::Read],
+            }])
+            .unwrap();
+        let (_, body) = post(app_state.clone(), "/dry-run?repository=private", Value::Null).await;
+        let request = serde_json::json!({"plan_hash": body["data"]["plan_hash"]});
+        assert_eq!(post(app_state.clone(), "/apply?repository=private", request).await.0, StatusCode::FORBIDDEN);
+        let (status, _) = post(app_state.clone(), "/dry-run?repository=a/../private", Value::Null).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
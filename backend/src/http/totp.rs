@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing, Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::http::jwt_auth::require_session;
+use crate::models::{Account, ApiResponse, AppState, AuditAction, AuditEntry, OIDC_PROVIDER, TokenClaims, TotpSettings};
+
+// Alta y baja del segundo factor (TOTP) de la propia cuenta
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_status))
+        .route("/enroll", routing::post(enroll))
+        .route("/enable", routing::post(enable))
+        .route("/disable", routing::post(disable))
+        .route("/recovery-codes", routing::post(reset_recovery_codes))
+        .layer(middleware::from_fn(require_session))
+}
+
+#[derive(Deserialize)]
+struct CodeRequest {
+    code: String,
+}
+
+fn invalid_code() -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, "Código no válido".to_string())
+}
+
+fn audit(app_state: &AppState, account: &Account, action: AuditAction, result: &Result<Value, (StatusCode, String)>) {
+    app_state.audit.record(AuditEntry::new(&account.username, action).result(result));
+}
+
+fn respond(message: &str, result: Result<Value, (StatusCode, String)>) -> Response {
+    match result {
+        Ok(value) => ApiResponse::success(message, Some(value)).into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+async fn get_status(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> Response {
+    // La cuenta de la extensión puede ser anterior a un cambio en esta misma sesión
+    let totp = app_state.users.get(&account.username).and_then(|a| a.totp);
+    let value = serde_json::json!({
+        "enabled": totp.as_ref().is_some_and(|t| t.enabled),
+        "pending": totp.as_ref().is_some_and(|t| !t.enabled),
+        "recovery_codes_left": totp.as_ref().map_or(0, |t| t.recovery_codes_left()),
+    });
+    ApiResponse::success("Segundo factor", Some(value)).into_response()
+}
+
+// Genera un secreto nuevo; no se exige hasta confirmarlo con `/enable`
+async fn enroll(State(app_state): State<Arc<AppState>>, Extension(account): Extension<Account>) -> Response {
+    let issuer = app_state.totp.issuer.clone();
+    let result = app_state.users.update_account(&account.username, |account| {
+        if account.provider.as_deref() == Some(OIDC_PROVIDER) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "El segundo factor de las cuentas OIDC lo gestiona el proveedor de identidad".to_string(),
+            ));
+        }
+        if account.totp_enabled() {
+            return Err((StatusCode::CONFLICT, "El segundo factor ya está activado".to_string()));
+        }
+        let totp = TotpSettings::generate();
+        let uri = totp
+            .otpauth_uri(&issuer, &account.username)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        let value = serde_json::json!({"secret": totp.secret(), "otpauth_uri": uri});
+        account.totp = Some(totp);
+        Ok(value)
+    })
+    .await;
+    respond("Escanea el código en tu app de autenticación", result)
+}
+
+// Confirma el alta con un primer código y devuelve los códigos de recuperación.
+// Las demás sesiones, abiertas sin segundo factor, se cierran.
+async fn enable(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Extension(claims): Extension<TokenClaims>,
+    Json(request): Json<CodeRequest>,
+) -> Response {
+    let result = app_state.users.update_account(&account.username, |account| {
+        let totp = account
+            .totp
+            .as_mut()
+            .filter(|t| !t.enabled)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, "No hay un alta pendiente".to_string()))?;
+        if !totp.verify(&request.code) {
+            return Err(invalid_code());
+        }
+        totp.enabled = true;
+        Ok(serde_json::json!({"recovery_codes": totp.reset_recovery_codes()}))
+    })
+    .await;
+    if result.is_ok() {
+        info!("User {} enabled two-factor authentication", account.username);
+        app_state.sessions.revoke_others(&account.username, claims.sid.as_deref());
+    }
+    audit(&app_state, &account, AuditAction::TotpEnable, &result);
+    respond("Segundo factor activado", result)
+}
+
+async fn disable(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Json(request): Json<CodeRequest>,
+) -> Response {
+    let result = app_state.users.update_account(&account.username, |account| {
+        let totp = account
+            .totp
+            .as_mut()
+            .filter(|t| t.enabled)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, "El segundo factor no está activado".to_string()))?;
+        if !totp.verify(&request.code) {
+            return Err(invalid_code());
+        }
+        account.totp = None;
+        Ok(Value::Null)
+    })
+    .await;
+    if result.is_ok() {
+        info!("User {} disabled two-factor authentication", account.username);
+    }
+    audit(&app_state, &account, AuditAction::TotpDisable, &result);
+    respond("Segundo factor desactivado", result)
+}
+
+async fn reset_recovery_codes(
+    State(app_state): State<Arc<AppState>>,
+    Extension(account): Extension<Account>,
+    Json(request): Json<CodeRequest>,
+) -> Response {
+    let result = app_state.users.update_account(&account.username, |account| {
+        let totp = account
+            .totp
+            .as_mut()
+            .filter(|t| t.enabled)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, "El segundo factor no está activado".to_string()))?;
+        if !totp.verify(&request.code) {
+            return Err(invalid_code());
+        }
+        Ok(serde_json::json!({"recovery_codes": totp.reset_recovery_codes()}))
+    })
+    .await;
+    audit(&app_state, &account, AuditAction::TotpRecoveryCodes, &result);
+    respond("Códigos de recuperación nuevos", result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::totp_code;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn send(app_state: Arc<AppState>, uri: &str, body: Value) -> (StatusCode, Value) {
+        send_in_session(app_state, None, uri, body).await
+    }
+
+    async fn send_in_session(app_state: Arc<AppState>, sid: Option<String>, uri: &str, body: Value) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let claims = TokenClaims { sub: "viewer".to_string(), exp: 0, iat: 0, sid };
+        request.extensions_mut().insert(claims);
+        request.extensions_mut().insert(app_state.users.get("viewer").unwrap());
+        let response = router().with_state(app_state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_enrollment() {
+        let app_state = Arc::new(AppState::for_tests());
+        let (status, body) = send(app_state.clone(), "/enroll", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["otpauth_uri"].as_str().unwrap().starts_with("otpauth://totp/"));
+        // Pendiente hasta confirmarlo: el login todavía no lo exige
+        assert!(!app_state.users.get("viewer").unwrap().totp_enabled());
+
+        let status = send(app_state.clone(), "/enable", serde_json::json!({"code": "000000x"})).await.0;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let settings = app_state.users.get("viewer").unwrap().totp.unwrap();
+        let (current, _) = app_state.sessions.create("viewer");
+        let (other, _) = app_state.sessions.create("viewer");
+        let code = serde_json::json!({"code": totp_code(&settings)});
+        let (status, body) = send_in_session(app_state.clone(), Some(current.clone()), "/enable", code).await;
+        assert_eq!(status, StatusCode::OK);
+        // Las demás sesiones se abrieron sin segundo factor
+        assert!(app_state.sessions.is_active(&current));
+        assert!(!app_state.sessions.is_active(&other));
+        let recovery_codes = body["data"]["recovery_codes"].as_array().unwrap().clone();
+        assert_eq!(recovery_codes.len(), 10);
+        assert!(app_state.users.get("viewer").unwrap().totp_enabled());
+        assert_eq!(send(app_state.clone(), "/enroll", Value::Null).await.0, StatusCode::CONFLICT);
+
+        let code = recovery_codes[0].as_str().unwrap();
+        assert_eq!(send(app_state.clone(), "/disable", serde_json::json!({"code": code})).await.0, StatusCode::OK);
+        assert!(app_state.users.get("viewer").unwrap().totp.is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing, Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::http::jwt_auth::require_admin;
+use crate::models::{Account, AccountView, ApiResponse, AppState, AuditAction, AuditEntry, Role, TokenClaims};
+
+// Administración de cuentas: todas las rutas exigen el rol admin
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", routing::get(get_users).post(create_user))
+        .route("/{username}/disable", routing::post(disable_user))
+        .route("/{username}/enable", routing::post(enable_user))
+        .route("/{username}/password", routing::put(reset_password))
+        .route("/{username}/role", routing::put(set_role))
+        .route("/{username}/groups", routing::put(set_groups))
+        .route("/{username}/totp", routing::delete(reset_totp))
+        .layer(middleware::from_fn(require_admin))
+}
+
+#[derive(Deserialize)]
+struct NewUser {
+    username: String,
+    password: String,
+    role: Role,
+}
+
+#[derive(Deserialize)]
+struct PasswordChange {
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct RoleChange {
+    role: Role,
+}
+
+#[derive(Deserialize)]
+struct GroupsChange {
+    groups: Vec<String>,
+}
+
+fn respond(message: &str, result: Result<AccountView, (StatusCode, String)>) -> axum::response::Response {
+    match result {
+        Ok(account) => ApiResponse::success(message, Some(account)).into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+fn audit(
+    app_state: &AppState,
+    current: &Account,
+    action: AuditAction,
+    username: &str,
+    detail: Option<String>,
+    result: &Result<AccountView, (StatusCode, String)>,
+) {
+    let entry = AuditEntry::new(&current.username, action).target(username);
+    let entry = match detail {
+        Some(detail) => entry.detail(detail),
+        None => entry,
+    };
+    app_state.audit.record(entry.result(result));
+}
+
+// Tras cambiar la contraseña, el rol o el segundo factor, las sesiones abiertas
+// con lo anterior se cierran. Si la cuenta es la del administrador, conserva la suya.
+fn revoke_sessions(app_state: &AppState, current: &Account, claims: &TokenClaims, username: &str) {
+    let keep = claims.sid.as_deref().filter(|_| current.username == username);
+    app_state.sessions.revoke_others(username, keep);
+}
+
+// Un administrador no puede quitarse a sí mismo el acceso ni los permisos
+fn check_not_self(current: &Account, username: &str) -> Result<(), (StatusCode, String)> {
+    if current.username == username {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "No puedes deshabilitar ni cambiar el rol de tu propia cuenta".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn get_users(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    ApiResponse::success("Usuarios", Some(app_state.users.list()))
+}
+
+async fn create_user(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Json(new_user): Json<NewUser>,
+) -> impl IntoResponse {
+    info!("User {} creates user {} ({:?})", current.username, new_user.username, new_user.role);
+    let result = app_state
+        .users
+        .create(&new_user.username, &new_user.password, new_user.role)
+        .await;
+    app_state.audit.record(
+        AuditEntry::new(&current.username, AuditAction::UserCreate)
+            .target(&new_user.username)
+            .detail(format!("{:?}", new_user.role).to_lowercase())
+            .result(&result),
+    );
+    match result {
+        Ok(account) => (StatusCode::CREATED, ApiResponse::success("Usuario creado", Some(account))).into_response(),
+        Err((status, message)) => ApiResponse::<Value>::error(status, &message).into_response(),
+    }
+}
+
+async fn disable_user(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    info!("User {} disables user {}", current.username, username);
+    let result = match check_not_self(&current, &username) {
+        Ok(()) => app_state.users.set_disabled(&username, true).await,
+        Err(e) => Err(e),
+    };
+    if result.is_ok() {
+        app_state.sessions.revoke_user(&username);
+    }
+    audit(&app_state, &current, AuditAction::UserDisable, &username, None, &result);
+    respond("Usuario deshabilitado", result)
+}
+
+async fn enable_user(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    info!("User {} enables user {}", current.username, username);
+    let result = app_state.users.set_disabled(&username, false).await;
+    audit(&app_state, &current, AuditAction::UserEnable, &username, None, &result);
+    respond("Usuario habilitado", result)
+}
+
+async fn reset_password(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Extension(claims): Extension<TokenClaims>,
+    Path(username): Path<String>,
+    Json(change): Json<PasswordChange>,
+) -> impl IntoResponse {
+    info!("User {} resets the password of {}", current.username, username);
+    let result = app_state.users.reset_password(&username, &change.password).await;
+    if result.is_ok() {
+        revoke_sessions(&app_state, &current, &claims, &username);
+    }
+    audit(&app_state, &current, AuditAction::UserPasswordReset, &username, None, &result);
+    respond("Contraseña cambiada", result)
+}
+
+async fn set_role(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Extension(claims): Extension<TokenClaims>,
+    Path(username): Path<String>,
+    Json(change): Json<RoleChange>,
+) -> impl IntoResponse {
+    info!("User {} sets role {:?} for {}", current.username, change.role, username);
+    let result = match check_not_self(&current, &username) {
+        Ok(()) => app_state.users.set_role(&username, change.role).await,
+        Err(e) => Err(e),
+    };
+    if result.is_ok() {
+        revoke_sessions(&app_state, &current, &claims, &username);
+    }
+    let role = format!("{:?}", change.role).to_lowercase();
+    audit(&app_state, &current, AuditAction::UserRoleChange, &username, Some(role), &result);
+    respond("Rol cambiado", result)
+}
+
+async fn set_groups(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Path(username): Path<String>,
+    Json(change): Json<GroupsChange>,
+) -> impl IntoResponse {
+    info!("User {} sets groups {:?} for {}", current.username, change.groups, username);
+    let groups = change.groups.join(",");
+    let result = app_state.users.set_groups(&username, change.groups).await;
+    audit(&app_state, &current, AuditAction::UserGroupsChange, &username, Some(groups), &result);
+    respond("Grupos cambiados", result)
+}
+
+// Quita el segundo factor de una cuenta que ha perdido su app y sus códigos de recuperación
+async fn reset_totp(
+    State(app_state): State<Arc<AppState>>,
+    Extension(current): Extension<Account>,
+    Extension(claims): Extension<TokenClaims>,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    info!("User {} resets two-factor authentication of {}", current.username, username);
+    let result = app_state.users.update_account(&username, |account| {
+        account.totp = None;
+        Ok(AccountView::from(&*account))
+    })
+    .await;
+    if result.is_ok() {
+        revoke_sessions(&app_state, &current, &claims, &username);
+    }
+    audit(&app_state, &current, AuditAction::UserTotpReset, &username, None, &result);
+    respond("Segundo factor desactivado", result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn send(app_state: Arc<AppState>, as_user: &str, method: &str, uri: &str, body: Value) -> StatusCode {
+        send_in_session(app_state, as_user, None, method, uri, body).await
+    }
+
+    async fn send_in_session(
+        app_state: Arc<AppState>,
+        as_user: &str,
+        sid: Option<String>,
+        method: &str,
+        uri: &str,
+        body: Value,
+    ) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let claims = TokenClaims { sub: as_user.to_string(), exp: 0, iat: 0, sid };
+        request.extensions_mut().insert(claims);
+        request.extensions_mut().insert(app_state.users.get(as_user).unwrap());
+        router().with_state(app_state).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_manages_users() {
+        let app_state = Arc::new(AppState::for_tests());
+        let body = serde_json::json!({"username": "new", "password": "secret", "role": "viewer"});
+        assert_eq!(send(app_state.clone(), "editor", "POST", "/", body.clone()).await, StatusCode::FORBIDDEN);
+        assert_eq!(send(app_state.clone(), "admin", "POST", "/", body.clone()).await, StatusCode::CREATED);
+        assert_eq!(send(app_state.clone(), "admin", "POST", "/", body).await, StatusCode::CONFLICT);
+
+        let status = send(app_state.clone(), "admin", "POST", "/new/disable", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(app_state.users.active("new").is_none());
+
+        let status = send(app_state.clone(), "admin", "PUT", "/new/role", serde_json::json!({"role": "editor"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(app_state.users.get("new").unwrap().role, Role::Editor);
+    }
+
+    #[tokio::test]
+    async fn test_admin_cannot_lock_themselves_out() {
+        let app_state = Arc::new(AppState::for_tests());
+        let status = send(app_state.clone(), "admin", "POST", "/admin/disable", Value::Null).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let status = send(app_state.clone(), "admin", "PUT", "/ghost/password", serde_json::json!({"password": "x"})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_credential_changes_close_sessions() {
+        let app_state = Arc::new(AppState::for_tests());
+        let (viewer_session, _) = app_state.sessions.create("viewer");
+        let (admin_session, _) = app_state.sessions.create("admin");
+        let (other_admin_session, _) = app_state.sessions.create("admin");
+
+        let password = serde_json::json!({"password": "new-secret"});
+        assert_eq!(send(app_state.clone(), "admin", "PUT", "/viewer/password", password.clone()).await, StatusCode::OK);
+        assert!(!app_state.sessions.is_active(&viewer_session));
+
+        let (viewer_session, _) = app_state.sessions.create("viewer");
+        let role = serde_json::json!({"role": "editor"});
+        assert_eq!(send(app_state.clone(), "admin", "PUT", "/viewer/role", role).await, StatusCode::OK);
+        assert!(!app_state.sessions.is_active(&viewer_session));
+
+        let (viewer_session, _) = app_state.sessions.create("viewer");
+        assert_eq!(send(app_state.clone(), "admin", "DELETE", "/viewer/totp", Value::Null).await, StatusCode::OK);
+        assert!(!app_state.sessions.is_active(&viewer_session));
+
+        // Al cambiar su propia contraseña, el administrador conserva la sesión desde la que lo hace
+        let sid = Some(admin_session.clone());
+        let status = send_in_session(app_state.clone(), "admin", sid, "PUT", "/admin/password", password).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(app_state.sessions.is_active(&admin_session));
+        assert!(!app_state.sessions.is_active(&other_admin_session));
+    }
+}
@@ -4,6 +4,7 @@ mod constants;
 
 use axum::{
     Router,
+    middleware,
     http::{
         header::{
             ACCEPT,
@@ -26,28 +27,71 @@ use tracing_subscriber::{
     util::SubscriberInitExt
 };
 use tower_http::services::{ServeDir, ServeFile};
-use tracing::{
-    info,
-    debug,
-};
+use tracing::info;
 use std::{
+    net::SocketAddr,
     str::FromStr,
     env::var,
+    time::Duration,
 };
-use models::{
-    User,
-    RegistryClient,
-};
+use models::RegistryClient;
 use http::{
+    acl,
+    api_tokens,
+    audit,
+    copy,
     health,
     auth,
+    events,
     fallback_404,
+    jobs,
+    jwt_auth,
+    users,
     registry,
+    registries,
+    retention,
+    totp,
 };
 use dotenv::dotenv;
 use models::{
+    AclStore,
+    ActivityFeed,
+    ApiTokenStore,
     AppState,
+    AuditLog,
+    CopyTracker,
     Error,
+    JobScheduler,
+    Ldap3Directory,
+    LoginGuard,
+    LdapConfig,
+    LdapProvider,
+    OidcConfig,
+    OidcProvider,
+    Registries,
+    RegistryConfig,
+    RetentionStore,
+    SessionStore,
+    TotpProvider,
+    UserStore,
+    parse_role,
+    parse_role_mapping,
+    run_scheduler,
+};
+
+use constants::{
+    ACTIVITY_FEED_SIZE,
+    COPY_HISTORY_SIZE,
+    DEFAULT_ACCESS_TOKEN_MINUTES,
+    DEFAULT_AUDIT_MEMORY_ENTRIES,
+    DEFAULT_CACHE_TTL,
+    DEFAULT_LOGIN_LOCKOUT_SECONDS,
+    DEFAULT_LOGIN_MAX_FAILURES,
+    DEFAULT_LOGIN_MAX_FAILURES_PER_IP,
+    DEFAULT_REGISTRY_MAX_PAGES,
+    DEFAULT_REFRESH_TOKEN_DAYS,
+    DEFAULT_REGISTRY_PAGE_SIZE,
+    JOB_HISTORY_SIZE,
 };
 
 const STATIC_DIR: &str = "static";
@@ -62,14 +106,143 @@ async fn main() -> Result<(), Error> {
         .init();
     info!("Log level: {log_level}");
 
-    let username = var("USERNAME").expect("USERNAME environment mandatory");
-    let hashed_password = var("HASHED_PASSWORD").expect("HASHED_PASSWORD environment mandatory");
+    let users_file = var("USERS_FILE").unwrap_or("users.json".to_string());
+    let users = UserStore::load(Some(users_file.into()))?;
+    // USERNAME/HASHED_PASSWORD solo se usan para crear el primer administrador
+    if let (Ok(username), Ok(hashed_password)) = (var("USERNAME"), var("HASHED_PASSWORD")) {
+        users.bootstrap_admin(&username, &hashed_password).await?;
+    }
+    if users.list().is_empty() {
+        panic!("No users defined: set USERNAME and HASHED_PASSWORD to create the first admin");
+    }
     let registry_url = var("REGISTRY_URL").expect("REGISTRY_URL environment mandatory");
     let basic_auth = var("BASIC_AUTH").expect("BASIC_AUTH environment mandatory");
+    let registry_page_size = var("REGISTRY_PAGE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_REGISTRY_PAGE_SIZE);
+    let registry_max_pages = var("REGISTRY_MAX_PAGES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_REGISTRY_MAX_PAGES);
+    let cache_ttl = var("CACHE_TTL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CACHE_TTL);
+    let cache_revalidate = var("CACHE_REVALIDATE")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    // REGISTRY_URL es el Registry principal; REGISTRIES_FILE añade otros con nombre
+    let registry_client = |url: String, basic_auth: String| {
+        RegistryClient::new(url, basic_auth)
+            .with_pagination(registry_page_size, registry_max_pages)
+            .with_cache(Duration::from_secs(cache_ttl), cache_revalidate)
+    };
+    let registry_name = var("REGISTRY_NAME").unwrap_or("default".to_string());
+    let mut registries = Registries::new(&registry_name, registry_client(registry_url, basic_auth))?;
+    if let Some(registries_file) = var("REGISTRIES_FILE").ok().filter(|s| !s.is_empty()) {
+        for config in RegistryConfig::load_file(registries_file.as_ref())? {
+            info!("Registry {}: {}", config.name, config.url);
+            registries.add(&config.name, registry_client(config.url, config.basic_auth))?;
+        }
+    }
+    let events_secret = var("EVENTS_SECRET").ok().filter(|s| !s.is_empty());
+    let activity_pulls = var("ACTIVITY_PULLS")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    let retention_file = var("RETENTION_FILE").unwrap_or("retention.json".to_string());
+    let acl_file = var("ACL_FILE").unwrap_or("acl.json".to_string());
+    let api_tokens_file = var("API_TOKENS_FILE").unwrap_or("api_tokens.json".to_string());
+    let totp_issuer = var("TOTP_ISSUER").unwrap_or("registryui".to_string());
+    let audit_file = var("AUDIT_FILE").unwrap_or("audit.jsonl".to_string());
+    let audit_memory_entries = var("AUDIT_MEMORY_ENTRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_AUDIT_MEMORY_ENTRIES);
+    let retention_schedule = var("RETENTION_SCHEDULE").ok().filter(|s| !s.is_empty());
+    let jobs_paused = var("JOBS_PAUSED")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    // El login OIDC se activa al definir OIDC_ISSUER
+    let oidc = match var("OIDC_ISSUER").ok().filter(|s| !s.is_empty()) {
+        Some(issuer) => Some(OidcProvider::new(OidcConfig {
+            issuer,
+            client_id: var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID environment mandatory with OIDC_ISSUER"),
+            client_secret: var("OIDC_CLIENT_SECRET").ok().filter(|s| !s.is_empty()),
+            redirect_url: var("OIDC_REDIRECT_URL").expect("OIDC_REDIRECT_URL environment mandatory with OIDC_ISSUER"),
+            scopes: var("OIDC_SCOPES").unwrap_or("openid profile email groups".to_string()),
+            username_claim: var("OIDC_USERNAME_CLAIM").unwrap_or("preferred_username".to_string()),
+            groups_claim: var("OIDC_GROUPS_CLAIM").unwrap_or("groups".to_string()),
+            role_mapping: parse_role_mapping(&var("OIDC_ROLE_MAPPING").unwrap_or_default())?,
+            default_role: match var("OIDC_DEFAULT_ROLE").ok().filter(|s| !s.is_empty()) {
+                Some(role) => Some(parse_role(&role)?),
+                None => None,
+            },
+        })),
+        None => None,
+    };
+    // AUTH_PROVIDER=ldap añade LDAP a las cuentas locales
+    let ldap = match var("AUTH_PROVIDER").unwrap_or("local".to_string()).as_str() {
+        "ldap" => {
+            let url = var("LDAP_URL").expect("LDAP_URL environment mandatory with AUTH_PROVIDER=ldap");
+            let config = LdapConfig {
+                bind_dn: var("LDAP_BIND_DN").ok().filter(|s| !s.is_empty()),
+                bind_password: var("LDAP_BIND_PASSWORD").ok(),
+                base_dn: var("LDAP_BASE_DN").expect("LDAP_BASE_DN environment mandatory with AUTH_PROVIDER=ldap"),
+                user_filter: var("LDAP_USER_FILTER").unwrap_or("(uid={username})".to_string()),
+                username_attribute: var("LDAP_USERNAME_ATTRIBUTE").unwrap_or("uid".to_string()),
+                group_attribute: var("LDAP_GROUP_ATTRIBUTE").unwrap_or("memberOf".to_string()),
+                role_mapping: parse_role_mapping(&var("LDAP_ROLE_MAPPING").unwrap_or_default())?,
+                default_role: match var("LDAP_DEFAULT_ROLE").ok().filter(|s| !s.is_empty()) {
+                    Some(role) => Some(parse_role(&role)?),
+                    None => None,
+                },
+            };
+            let starttls = var("LDAP_STARTTLS").map(|v| v == "true").unwrap_or(false);
+            let allow_plaintext = var("LDAP_ALLOW_PLAINTEXT").map(|v| v == "true").unwrap_or(false);
+            let directory = Ldap3Directory::new(&url, starttls, allow_plaintext)?;
+            info!("LDAP authentication: {}", url);
+            Some(LdapProvider::new(config, Box::new(directory)))
+        }
+        "local" => None,
+        other => panic!("Unknown AUTH_PROVIDER: {}", other),
+    };
+    let access_token_minutes = var("ACCESS_TOKEN_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_ACCESS_TOKEN_MINUTES);
+    let refresh_token_days = var("REFRESH_TOKEN_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_REFRESH_TOKEN_DAYS);
+    // Solo para desarrollo local sin https
+    let cookie_secure = var("COOKIE_SECURE")
+        .map(|v| v != "false")
+        .unwrap_or(true);
+    let login_max_failures = var("LOGIN_MAX_FAILURES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LOGIN_MAX_FAILURES);
+    let login_max_failures_per_ip = var("LOGIN_MAX_FAILURES_PER_IP")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LOGIN_MAX_FAILURES_PER_IP);
+    let login_lockout_seconds = var("LOGIN_LOCKOUT_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LOGIN_LOCKOUT_SECONDS);
+    // Solo detrás de un proxy inverso que sobrescriba la cabecera
+    let trust_forwarded_for = var("TRUST_FORWARDED_FOR")
+        .map(|v| v == "true")
+        .unwrap_or(false);
     let port = var("PORT").unwrap_or("3000".to_string());
     info!("Port: {}", port);
-    let secret = var("SECRET").unwrap_or("esto-es-un-secreto".to_string());
-    debug!("Secret: {}", secret);
+    // Sin valor por defecto: uno conocido permitiría firmar tokens válidos a cualquiera
+    let secret = var("SECRET")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .expect("SECRET environment mandatory");
 
 
     let cors = CorsLayer::new()
@@ -80,22 +253,53 @@ async fn main() -> Result<(), Error> {
         //.allow_credentials(true)
         .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
 
+    let app_state = Arc::new(AppState {
+        secret,
+        static_dir: STATIC_DIR.to_string(),
+        users,
+        registries,
+        events_secret,
+        activity: ActivityFeed::new(ACTIVITY_FEED_SIZE).with_pulls(activity_pulls),
+        retention: RetentionStore::load(Some(retention_file.into()))?,
+        acl: AclStore::load(Some(acl_file.into()))?,
+        api_tokens: ApiTokenStore::load(Some(api_tokens_file.into()))?,
+        totp: TotpProvider::new(&totp_issuer),
+        audit: AuditLog::load(Some(audit_file.into()), audit_memory_entries),
+        oidc,
+        ldap,
+        sessions: SessionStore::new(
+            chrono::Duration::minutes(access_token_minutes),
+            chrono::Duration::days(refresh_token_days),
+            cookie_secure),
+        login_guard: LoginGuard::new(
+            login_max_failures,
+            login_max_failures_per_ip,
+            Duration::from_secs(login_lockout_seconds))
+            .with_forwarded_for(trust_forwarded_for),
+        jobs: JobScheduler::new(retention_schedule, jobs_paused, JOB_HISTORY_SIZE)?,
+        copies: CopyTracker::new(COPY_HISTORY_SIZE),
+    });
+    tokio::spawn(run_scheduler(app_state.clone()));
+    let audit_state = app_state.clone();
+
+    // Las rutas anidadas antes del `route_layer` exigen un JWT válido
     let api_routes = Router::new()
+        .nest("/registry", registry::router())
+        .nest("/registries", registries::router())
+        .nest("/copy", copy::router())
+        .nest("/retention", retention::router())
+        .nest("/jobs", jobs::router())
+        .nest("/users", users::router())
+        .nest("/acl", acl::router())
+        .nest("/audit", audit::router())
+        .nest("/auth/tokens", api_tokens::router())
+        .nest("/auth/totp", totp::router())
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), jwt_auth::auth))
         .nest("/health", health::router())
         .nest("/auth", auth::router())
-        .nest("/registry", registry::router())
+        .nest("/events", events::router())
         .fallback(fallback_404)
-        .with_state(Arc::new(AppState {
-            secret,
-            static_dir: STATIC_DIR.to_string(),
-            user: User {
-                username,
-                hashed_password,
-            },
-            registry_client: RegistryClient::new(
-                registry_url,
-                basic_auth),
-    }));
+        .with_state(app_state);
 
     let app = Router::new()
         .nest("/api/v1", api_routes)
@@ -106,8 +310,35 @@ async fn main() -> Result<(), Error> {
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     tracing::info!("🚀 Server started successfully 🚀");
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+    // Lo anotado en la auditoría se escribe antes de salir
+    audit_state.audit.flush();
 
     Ok(())
 }
 
+// Ctrl+C o SIGTERM (p.ej. `docker stop`)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutting down");
+}
+
@@ -0,0 +1,232 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use super::glob::glob_to_regex;
+use super::user_store::{Account, Role};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    // Borrar implica poder ver el repositorio
+    Delete,
+}
+
+// Regla de acceso para los repositorios que casan con `repository` (glob).
+// Sin `registry` vale para ese nombre en todos los Registries.
+// `users` admite `*` para cualquier usuario autenticado.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AclRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    pub repository: String,
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+impl AclRule {
+    pub fn validate(&self) -> Result<(), String> {
+        glob_to_regex(&self.repository)
+            .map_err(|e| format!("Patrón de repositorio no válido '{}': {}", self.repository, e))?;
+        if self.users.is_empty() && self.groups.is_empty() {
+            return Err(format!(
+                "La regla de '{}' debe indicar al menos un usuario o un grupo",
+                self.repository
+            ));
+        }
+        if self.permissions.is_empty() {
+            return Err(format!("La regla de '{}' no concede ningún permiso", self.repository));
+        }
+        Ok(())
+    }
+
+    fn covers_registry(&self, registry: &str) -> bool {
+        self.registry.as_deref().is_none_or(|r| r == registry)
+    }
+
+    fn applies_to(&self, account: &Account) -> bool {
+        self.users.iter().any(|u| u == "*" || *u == account.username)
+            || self.groups.iter().any(|g| account.groups.contains(g))
+    }
+
+    fn grants(&self, permission: Permission) -> bool {
+        self.permissions
+            .iter()
+            .any(|p| *p == permission || (*p == Permission::Delete && permission == Permission::Read))
+    }
+}
+
+// Regla con el patrón ya compilado: `allows` se consulta en cada petición
+// y para cada repositorio del catálogo
+struct CompiledRule {
+    rule: AclRule,
+    repository: Regex,
+}
+
+fn compile(rules: Vec<AclRule>) -> Result<Vec<CompiledRule>, String> {
+    rules
+        .into_iter()
+        .map(|rule| {
+            rule.validate()?;
+            let repository = glob_to_regex(&rule.repository).map_err(|e| e.to_string())?;
+            Ok(CompiledRule { rule, repository })
+        })
+        .collect()
+}
+
+// Reglas de acceso persistidas en un fichero JSON.
+// Un repositorio sin ninguna regla que lo cubra queda abierto a todos los usuarios
+// (según su rol); en cuanto alguna regla lo cubre, solo acceden aquellos a los que
+// una de esas reglas les concede el permiso. Los administradores ven todo.
+pub struct AclStore {
+    path: Option<PathBuf>,
+    rules: RwLock<Vec<CompiledRule>>,
+}
+
+impl AclStore {
+    // Un fichero que no se puede leer es un error: sin reglas, todo quedaría abierto
+    pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+        let rules = match path.as_ref().filter(|p| p.exists()) {
+            Some(p) => {
+                let content = std::fs::read_to_string(p)
+                    .map_err(|e| format!("No se pudo leer {}: {}", p.display(), e))?;
+                let rules = serde_json::from_str(&content)
+                    .map_err(|e| format!("Reglas de acceso no válidas en {}: {}", p.display(), e))?;
+                compile(rules).map_err(|e| format!("Reglas de acceso no válidas en {}: {}", p.display(), e))?
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path,
+            rules: RwLock::new(rules),
+        })
+    }
+
+    pub fn rules(&self) -> Vec<AclRule> {
+        self.rules.read().unwrap().iter().map(|r| r.rule.clone()).collect()
+    }
+
+    pub fn replace(&self, rules: Vec<AclRule>) -> Result<(), String> {
+        let compiled = compile(rules)?;
+        if let Some(path) = &self.path {
+            let rules: Vec<&AclRule> = compiled.iter().map(|r| &r.rule).collect();
+            let content = serde_json::to_string_pretty(&rules).map_err(|e| e.to_string())?;
+            std::fs::write(path, content).map_err(|e| format!("No se pudo guardar {}: {}", path.display(), e))?;
+        }
+        *self.rules.write().unwrap() = compiled;
+        Ok(())
+    }
+
+    pub fn allows(&self, account: &Account, registry: &str, repo: &str, permission: Permission) -> bool {
+        if account.role == Role::Admin {
+            return true;
+        }
+        let rules = self.rules.read().unwrap();
+        let mut covering = rules
+            .iter()
+            .filter(|r| r.rule.covers_registry(registry) && r.repository.is_match(repo))
+            .peekable();
+        if covering.peek().is_none() {
+            return true;
+        }
+        covering.any(|r| r.rule.applies_to(account) && r.rule.grants(permission))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(username: &str, role: Role, groups: &[&str]) -> Account {
+        let mut account = Account::new(username, "", role);
+        account.groups = groups.iter().map(|g| g.to_string()).collect();
+        account
+    }
+
+    fn store() -> AclStore {
+        let store = AclStore::load(None).unwrap();
+        store
+            .replace(vec![
+                AclRule {
+                    registry: None,
+                    repository: "team-a/*".into(),
+                    users: vec![],
+                    groups: vec!["team-a".into()],
+                    permissions: vec![Permission::Delete],
+                },
+                AclRule {
+                    registry: None,
+                    repository: "team-a/*".into(),
+                    users: vec!["auditor".into()],
+                    groups: vec![],
+                    permissions: vec![Permission::Read],
+                },
+            ])
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn test_rules_restrict_covered_repositories() {
+        let store = store();
+        let member = account("alice", Role::Editor, &["team-a"]);
+        let auditor = account("auditor", Role::Editor, &[]);
+        let outsider = account("bob", Role::Editor, &["team-b"]);
+
+        assert!(store.allows(&member, "prod", "team-a/api", Permission::Delete));
+        assert!(store.allows(&member, "prod", "team-a/api", Permission::Read));
+        assert!(store.allows(&auditor, "prod", "team-a/api", Permission::Read));
+        assert!(!store.allows(&auditor, "prod", "team-a/api", Permission::Delete));
+        assert!(!store.allows(&outsider, "prod", "team-a/api", Permission::Read));
+        // Sin reglas que lo cubran, el repositorio queda abierto
+        assert!(store.allows(&outsider, "prod", "team-b/api", Permission::Delete));
+        assert!(store.allows(&account("root", Role::Admin, &[]), "prod", "team-a/api", Permission::Delete));
+    }
+
+    #[test]
+    fn test_rules_scoped_to_a_registry() {
+        let store = AclStore::load(None).unwrap();
+        store
+            .replace(vec![AclRule {
+                registry: Some("prod".into()),
+                repository: "app".into(),
+                users: vec!["alice".into()],
+                groups: vec![],
+                permissions: vec![Permission::Delete],
+            }])
+            .unwrap();
+        let alice = account("alice", Role::Editor, &[]);
+        let bob = account("bob", Role::Editor, &[]);
+        assert!(store.allows(&alice, "prod", "app", Permission::Delete));
+        assert!(!store.allows(&bob, "prod", "app", Permission::Read));
+        // El mismo repositorio en otro Registry no lo cubre la regla
+        assert!(store.allows(&bob, "staging", "app", Permission::Delete));
+    }
+
+    #[test]
+    fn test_rule_validation() {
+        let rule = AclRule {
+            registry: None,
+            repository: "app".into(),
+            users: vec![],
+            groups: vec![],
+            permissions: vec![Permission::Read],
+        };
+        assert!(rule.validate().is_err());
+        assert!(AclStore::load(None).unwrap().replace(vec![rule]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_file_fails_to_load() {
+        let path = std::env::temp_dir().join(format!("acl-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "{not json").unwrap();
+        // Sin reglas todo quedaría abierto: mejor no arrancar
+        assert!(AclStore::load(Some(path.clone())).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{not json");
+        std::fs::remove_file(path).unwrap();
+    }
+}
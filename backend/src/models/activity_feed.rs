@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use super::registry_event::Activity;
+
+// Últimas operaciones notificadas por el Registry, de la más reciente a la más antigua
+pub struct ActivityFeed {
+    entries: Mutex<VecDeque<Activity>>,
+    capacity: usize,
+    // Cada pull también genera una notificación; por defecto no se guardan para
+    // que no desplacen los push y delete
+    pulls: bool,
+}
+
+impl ActivityFeed {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            pulls: false,
+        }
+    }
+
+    pub fn with_pulls(mut self, pulls: bool) -> Self {
+        self.pulls = pulls;
+        self
+    }
+
+    // El Registry reintenta los envíos, así que descartamos ids repetidos
+    pub fn push(&self, activity: Activity) -> bool {
+        if !self.pulls && activity.action == "pull" {
+            return false;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.iter().any(|a| a.id == activity.id) {
+            return false;
+        }
+        if entries.len() == self.capacity {
+            entries.pop_back();
+        }
+        entries.push_front(activity);
+        true
+    }
+
+    // Últimas `limit` entradas de los repositorios para los que `visible` es cierto
+    pub fn recent(&self, limit: usize, visible: impl Fn(&str) -> bool) -> Vec<Activity> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|a| visible(&a.repository))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(id: &str) -> Activity {
+        event(id, "push")
+    }
+
+    fn event(id: &str, action: &str) -> Activity {
+        Activity {
+            id: id.to_string(),
+            timestamp: "2024-01-01T00:00:00Z".to_string(),
+            action: action.to_string(),
+            repository: "app".to_string(),
+            tag: None,
+            digest: None,
+            actor: None,
+        }
+    }
+
+    #[test]
+    fn test_feed_is_bounded_and_deduplicated() {
+        let feed = ActivityFeed::new(2);
+        assert!(feed.push(activity("1")));
+        assert!(!feed.push(activity("1")));
+        feed.push(activity("2"));
+        feed.push(activity("3"));
+        let ids: Vec<_> = feed.recent(10, |_| true).into_iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec!["3", "2"]);
+        assert_eq!(feed.recent(1, |_| true).len(), 1);
+    }
+
+    #[test]
+    fn test_pulls_are_optional() {
+        let feed = ActivityFeed::new(10);
+        assert!(!feed.push(event("1", "pull")));
+        assert!(feed.push(event("2", "delete")));
+        assert_eq!(feed.recent(10, |_| true).len(), 1);
+
+        let feed = ActivityFeed::new(10).with_pulls(true);
+        assert!(feed.push(event("1", "pull")));
+    }
+}
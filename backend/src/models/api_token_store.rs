@@ -0,0 +1,260 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tracing::debug;
+use super::acl::Permission;
+use super::user_store::{Account, Role};
+
+// Prefijo que distingue los tokens de API de los JWT de sesión
+pub const API_TOKEN_PREFIX: &str = "rui_";
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// Token de API tal y como se guarda: solo el hash del secreto
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    hash: String,
+    pub scopes: Vec<Permission>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    // Rol máximo que concede el token: `read` como viewer, `delete` como editor.
+    // Un token nunca da permisos de administración.
+    pub fn max_role(&self) -> Role {
+        if self.scopes.contains(&Permission::Delete) {
+            Role::Editor
+        } else {
+            Role::Viewer
+        }
+    }
+
+    // Cuenta con la que se atiende la petición: la del dueño, limitada por los scopes
+    pub fn restrict(&self, mut account: Account) -> Account {
+        account.role = account.role.min(self.max_role());
+        account
+    }
+}
+
+// Token sin el hash, para las respuestas de la API
+#[derive(Serialize, Debug, Clone)]
+pub struct ApiTokenView {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub scopes: Vec<Permission>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<&ApiToken> for ApiTokenView {
+    fn from(token: &ApiToken) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            owner: token.owner.clone(),
+            scopes: token.scopes.clone(),
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+        }
+    }
+}
+
+pub struct ApiTokenStore {
+    path: Option<PathBuf>,
+    tokens: RwLock<BTreeMap<String, ApiToken>>,
+}
+
+impl ApiTokenStore {
+    // Un fichero que no se puede leer es un error: el siguiente guardado lo sobrescribiría
+    pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+        let tokens: Vec<ApiToken> = match path.as_ref().filter(|p| p.exists()) {
+            Some(p) => {
+                let content = std::fs::read_to_string(p)
+                    .map_err(|e| format!("No se pudo leer {}: {}", p.display(), e))?;
+                serde_json::from_str(&content)
+                    .map_err(|e| format!("Tokens de API no válidos en {}: {}", p.display(), e))?
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path,
+            tokens: RwLock::new(tokens.into_iter().map(|t| (t.id.clone(), t)).collect()),
+        })
+    }
+
+    // Tokens de `owner`, o todos si es `None`
+    pub fn list(&self, owner: Option<&str>) -> Vec<ApiTokenView> {
+        self.tokens
+            .read()
+            .unwrap()
+            .values()
+            .filter(|t| owner.is_none_or(|owner| t.owner == owner))
+            .map(ApiTokenView::from)
+            .collect()
+    }
+
+    // Crea un token y devuelve su valor en claro (`rui_<id>.<secreto>`), que no se vuelve a mostrar
+    pub fn create(
+        &self,
+        owner: &str,
+        name: &str,
+        scopes: Vec<Permission>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(ApiTokenView, String), (StatusCode, String)> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "El nombre del token no puede estar vacío".to_string()));
+        }
+        if scopes.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "El token debe tener al menos un scope".to_string()));
+        }
+        if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err((StatusCode::BAD_REQUEST, "La fecha de caducidad ya ha pasado".to_string()));
+        }
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let token = ApiToken {
+            id: id.clone(),
+            name: name.to_string(),
+            owner: owner.to_string(),
+            hash: hash_secret(&secret),
+            scopes,
+            created_at: Utc::now(),
+            expires_at,
+        };
+        let view = ApiTokenView::from(&token);
+        self.update(|tokens| {
+            if tokens.values().any(|t| t.owner == owner && t.name == name) {
+                return Err((StatusCode::CONFLICT, format!("Ya tienes un token llamado '{}'", name)));
+            }
+            tokens.insert(id.clone(), token);
+            Ok(())
+        })?;
+        Ok((view, format!("{}{}.{}", API_TOKEN_PREFIX, id, secret)))
+    }
+
+    // Token vigente que corresponde al valor presentado
+    pub fn verify(&self, value: &str) -> Option<ApiToken> {
+        let (id, secret) = value.strip_prefix(API_TOKEN_PREFIX)?.split_once('.')?;
+        let token = self.tokens.read().unwrap().get(id).cloned()?;
+        if token.hash != hash_secret(secret) {
+            debug!("API token {} with wrong secret", id);
+            return None;
+        }
+        if token.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            debug!("API token {} of {} has expired", token.name, token.owner);
+            return None;
+        }
+        Some(token)
+    }
+
+    pub fn get(&self, id: &str) -> Option<ApiTokenView> {
+        self.tokens.read().unwrap().get(id).map(ApiTokenView::from)
+    }
+
+    pub fn revoke(&self, id: &str) -> Result<(), (StatusCode, String)> {
+        self.update(|tokens| {
+            tokens
+                .remove(id)
+                .map(|_| ())
+                .ok_or_else(|| (StatusCode::NOT_FOUND, format!("El token '{}' no existe", id)))
+        })
+    }
+
+    // Aplica el cambio sobre una copia y solo lo da por bueno si se ha podido guardar
+    fn update(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<String, ApiToken>) -> Result<(), (StatusCode, String)>,
+    ) -> Result<(), (StatusCode, String)> {
+        let mut tokens = self.tokens.write().unwrap();
+        let mut updated = tokens.clone();
+        change(&mut updated)?;
+        if let Some(path) = &self.path {
+            let list: Vec<&ApiToken> = updated.values().collect();
+            let content = serde_json::to_string_pretty(&list)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            std::fs::write(path, content).map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("No se pudo guardar {}: {}", path.display(), e),
+                )
+            })?;
+        }
+        *tokens = updated;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_verify_and_revoke() {
+        let store = ApiTokenStore::load(None).unwrap();
+        let (view, value) = store.create("editor", "ci", vec![Permission::Read], None).unwrap();
+        assert!(value.starts_with(API_TOKEN_PREFIX));
+        assert_eq!(store.verify(&value).unwrap().owner, "editor");
+        assert!(store.verify(&format!("{}x", value)).is_none());
+        assert!(store.verify("rui_unknown.secret").is_none());
+
+        let duplicate = store.create("editor", "ci", vec![Permission::Read], None).unwrap_err();
+        assert_eq!(duplicate.0, StatusCode::CONFLICT);
+        assert_eq!(store.list(Some("editor")).len(), 1);
+        assert!(store.list(Some("viewer")).is_empty());
+
+        store.revoke(&view.id).unwrap();
+        assert!(store.verify(&value).is_none());
+        assert_eq!(store.revoke(&view.id).unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_expiry_and_validation() {
+        let store = ApiTokenStore::load(None).unwrap();
+        let past = Utc::now() - chrono::Duration::days(1);
+        assert_eq!(store.create("admin", "old", vec![Permission::Read], Some(past)).unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert_eq!(store.create("admin", " ", vec![Permission::Read], None).unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert_eq!(store.create("admin", "none", vec![], None).unwrap_err().0, StatusCode::BAD_REQUEST);
+
+        let (view, value) = store.create("admin", "soon", vec![Permission::Read], Some(Utc::now() + chrono::Duration::days(1))).unwrap();
+        // Se fuerza la caducidad
+        store.tokens.write().unwrap().get_mut(&view.id).unwrap().expires_at = Some(past);
+        assert!(store.verify(&value).is_none());
+    }
+
+    #[test]
+    fn test_scopes_cap_the_owner_role() {
+        let store = ApiTokenStore::load(None).unwrap();
+        let (_, read) = store.create("admin", "read", vec![Permission::Read], None).unwrap();
+        let (_, delete) = store.create("admin", "delete", vec![Permission::Read, Permission::Delete], None).unwrap();
+        let admin = Account::new("admin", "", Role::Admin);
+        let viewer = Account::new("viewer", "", Role::Viewer);
+
+        assert_eq!(store.verify(&read).unwrap().restrict(admin.clone()).role, Role::Viewer);
+        assert_eq!(store.verify(&delete).unwrap().restrict(admin).role, Role::Editor);
+        // Los scopes no amplían los permisos del dueño
+        assert_eq!(store.verify(&delete).unwrap().restrict(viewer).role, Role::Viewer);
+    }
+
+    #[test]
+    fn test_invalid_file_fails_to_load() {
+        let path = std::env::temp_dir().join(format!("api-tokens-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(ApiTokenStore::load(Some(path.clone())).is_err());
+        // El fichero sigue intacto
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{not json");
+        std::fs::remove_file(path).unwrap();
+    }
+}
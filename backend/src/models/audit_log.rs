@@ -0,0 +1,502 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock, mpsc};
+use std::time::{Duration, Instant};
+use tracing::error;
+use super::Paginable;
+use super::paginable::QueryParams;
+use super::retention::RetentionResult;
+
+// Usuario de las acciones que no lanza nadie (p.ej. la retención programada)
+pub const SYSTEM_USER: &str = "system";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    Logout,
+    TagCreate,
+    TagDelete,
+    ImageCopy,
+    RetentionDelete,
+    RetentionRun,
+    RetentionPoliciesUpdate,
+    AclUpdate,
+    JobsPause,
+    JobsResume,
+    UserCreate,
+    UserDisable,
+    UserEnable,
+    UserPasswordReset,
+    UserRoleChange,
+    UserGroupsChange,
+    UserTotpReset,
+    ApiTokenCreate,
+    ApiTokenRevoke,
+    TotpEnable,
+    TotpDisable,
+    TotpRecoveryCodes,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+// Una línea del fichero de auditoría. `user` es el `sub` del token de la petición
+// (en los logins, el nombre con el que se intentó entrar).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    // Usuario o token afectado por los cambios de configuración
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(user: &str, action: AuditAction) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            user: user.to_string(),
+            action,
+            registry: None,
+            repository: None,
+            tag: None,
+            digest: None,
+            target: None,
+            outcome: AuditOutcome::Success,
+            detail: None,
+            ip: None,
+        }
+    }
+
+    pub fn registry(mut self, registry: &str) -> Self {
+        self.registry = Some(registry.to_string());
+        self
+    }
+
+    pub fn repository(mut self, repository: &str) -> Self {
+        self.repository = Some(repository.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn digest(mut self, digest: &str) -> Self {
+        self.digest = Some(digest.to_string());
+        self
+    }
+
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn ip(mut self, ip: &str) -> Self {
+        self.ip = Some(ip.to_string());
+        self
+    }
+
+    pub fn failed(mut self, detail: impl Into<String>) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self.detail = Some(detail.into());
+        self
+    }
+
+    // Resultado de una operación con el error habitual de los handlers
+    pub fn result<T>(self, result: &Result<T, (StatusCode, String)>) -> Self {
+        match result {
+            Ok(_) => self,
+            Err((_, message)) => self.failed(message.as_str()),
+        }
+    }
+}
+
+// Parámetros de `GET /api/v1/audit?page=&limit=&user=&action=&repository=&tag=&outcome=&since=&until=`
+#[derive(Deserialize, Debug, Default)]
+pub struct AuditQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub user: Option<String>,
+    pub action: Option<AuditAction>,
+    pub repository: Option<String>,
+    pub tag: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Paginable for AuditQuery {
+    fn page(&self) -> Option<u32> {
+        self.page
+    }
+
+    fn limit(&self) -> Option<u32> {
+        self.limit
+    }
+}
+
+fn as_str<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.user.as_ref().is_none_or(|user| entry.user == *user)
+            && self.action.is_none_or(|action| entry.action == action)
+            && self.repository.as_ref().is_none_or(|r| entry.repository.as_ref() == Some(r))
+            && self.tag.as_ref().is_none_or(|t| entry.tag.as_ref() == Some(t))
+            && self.outcome.is_none_or(|outcome| entry.outcome == outcome)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp < until)
+    }
+
+    pub fn base_path(&self, path: &str) -> String {
+        QueryParams::new()
+            .add_opt("user", self.user.as_deref())
+            .add_opt("action", self.action.map(|a| as_str(&a)).as_deref())
+            .add_opt("repository", self.repository.as_deref())
+            .add_opt("tag", self.tag.as_deref())
+            .add_opt("outcome", self.outcome.map(|o| as_str(&o)).as_deref())
+            .add_opt("since", self.since.map(|s| s.to_rfc3339()).as_deref())
+            .add_opt("until", self.until.map(|u| u.to_rfc3339()).as_deref())
+            .path(path)
+    }
+}
+
+// Los intentos repetidos contra una cuenta bloqueada se anotan una vez por intervalo
+const THROTTLE_INTERVAL: Duration = Duration::from_secs(60);
+// Claves de `record_throttled` que se recuerdan como mucho; por encima se anota todo
+const MAX_THROTTLED_KEYS: usize = 10_000;
+
+enum WriterMessage {
+    Line(String),
+    // Responde cuando todo lo anterior está en el fichero
+    Flush(mpsc::Sender<()>),
+}
+
+// Escribe las líneas en su propio hilo, con el fichero abierto una sola vez, para
+// que `record` no haga E/S bloqueante desde los handlers
+fn spawn_writer(path: &Path) -> Option<mpsc::Sender<WriterMessage>> {
+    let file = match std::fs::OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("No se pudo abrir {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let mut writer = BufWriter::new(file);
+        while let Ok(message) = receiver.recv() {
+            // Se vacía el buffer cuando no quedan más líneas pendientes
+            let mut pending = Some(message);
+            while let Some(message) = pending.take() {
+                match message {
+                    WriterMessage::Line(line) => {
+                        if let Err(e) = writeln!(writer, "{}", line) {
+                            error!("No se pudo escribir en {}: {} ({})", path.display(), e, line);
+                        }
+                    }
+                    WriterMessage::Flush(done) => {
+                        let _ = writer.flush();
+                        let _ = done.send(());
+                    }
+                }
+                pending = receiver.try_recv().ok();
+            }
+            if let Err(e) = writer.flush() {
+                error!("No se pudo escribir en {}: {}", path.display(), e);
+            }
+        }
+    });
+    Some(sender)
+}
+
+// Registro de auditoría: un fichero JSON lines al que solo se añaden líneas.
+// En memoria se guardan solo las `capacity` entradas más recientes, que son las
+// que se pueden consultar; el fichero conserva todo el histórico.
+pub struct AuditLog {
+    capacity: usize,
+    entries: RwLock<VecDeque<AuditEntry>>,
+    writer: Option<mpsc::Sender<WriterMessage>>,
+    // Última vez que se anotó cada clave de `record_throttled` y los intentos omitidos desde entonces
+    throttled: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl AuditLog {
+    pub fn load(path: Option<PathBuf>, capacity: usize) -> Self {
+        let mut entries = VecDeque::new();
+        if let Some(p) = path.as_ref().filter(|p| p.exists()) {
+            match std::fs::File::open(p) {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines() {
+                        let line = match line {
+                            Ok(line) => line,
+                            Err(e) => {
+                                error!("No se pudo leer {}: {}", p.display(), e);
+                                break;
+                            }
+                        };
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        match serde_json::from_str(&line) {
+                            Ok(entry) => {
+                                if entries.len() == capacity {
+                                    entries.pop_front();
+                                }
+                                entries.push_back(entry);
+                            }
+                            Err(e) => error!("Entrada de auditoría no válida en {}: {}", p.display(), e),
+                        }
+                    }
+                }
+                Err(e) => error!("No se pudo leer {}: {}", p.display(), e),
+            }
+        }
+        Self {
+            capacity,
+            entries: RwLock::new(entries),
+            writer: path.as_deref().and_then(spawn_writer),
+            throttled: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Si no se puede escribir la línea se registra el error, pero la acción ya está hecha
+    pub fn record(&self, entry: AuditEntry) {
+        let mut entries = self.entries.write().unwrap();
+        // Se envía con el lock tomado para que el fichero tenga el mismo orden que la memoria
+        if let Some(writer) = &self.writer {
+            match serde_json::to_string(&entry) {
+                Ok(line) => {
+                    if writer.send(WriterMessage::Line(line)).is_err() {
+                        error!("El escritor de auditoría se ha detenido ({:?})", entry);
+                    }
+                }
+                Err(e) => error!("No se pudo serializar la entrada de auditoría: {} ({:?})", e, entry),
+            }
+        }
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    // Como `record`, pero las entradas iguales (usuario, acción, IP y detalle) se anotan
+    // como mucho una vez por intervalo: un cliente insistiendo contra un bloqueo no llena
+    // el fichero. La siguiente anotación indica cuántas se omitieron.
+    pub fn record_throttled(&self, mut entry: AuditEntry) {
+        let key = format!(
+            "{}|{}|{}|{}",
+            entry.user,
+            as_str(&entry.action),
+            entry.ip.as_deref().unwrap_or_default(),
+            entry.detail.as_deref().unwrap_or_default()
+        );
+        let now = Instant::now();
+        {
+            let mut throttled = self.throttled.lock().unwrap();
+            if let Some((last, skipped)) = throttled.get_mut(&key) {
+                if now.duration_since(*last) < THROTTLE_INTERVAL {
+                    *skipped += 1;
+                    return;
+                }
+                if *skipped > 0 {
+                    let detail = entry.detail.take().unwrap_or_default();
+                    entry.detail = Some(format!("{} ({} intentos más sin anotar)", detail, skipped));
+                }
+            }
+            if throttled.len() >= MAX_THROTTLED_KEYS {
+                throttled.retain(|_, (last, _)| now.duration_since(*last) < THROTTLE_INTERVAL);
+            }
+            if throttled.len() < MAX_THROTTLED_KEYS || throttled.contains_key(&key) {
+                throttled.insert(key, (now, 0));
+            }
+        }
+        self.record(entry);
+    }
+
+    // Espera a que lo anotado hasta ahora esté escrito en el fichero
+    pub fn flush(&self) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let (done, wait) = mpsc::channel();
+        if writer.send(WriterMessage::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+
+    // Un registro por tag borrado por la retención, y otro con el resumen de la ejecución
+    pub fn record_retention(&self, user: &str, results: &[RetentionResult]) {
+        let mut tags_deleted = 0;
+        let mut errors = Vec::new();
+        for result in results {
+            for deletion in &result.deleted {
+                let entry = AuditEntry::new(user, AuditAction::RetentionDelete)
+                    .repository(&result.repository)
+                    .digest(&deletion.digest);
+                if deletion.tags.is_empty() {
+                    self.record(entry.clone());
+                }
+                for tag in &deletion.tags {
+                    self.record(entry.clone().tag(tag));
+                }
+                tags_deleted += deletion.tags.len();
+            }
+            errors.extend(result.errors.iter().map(|e| format!("{}: {}", result.repository, e)));
+        }
+        let summary = AuditEntry::new(user, AuditAction::RetentionRun)
+            .detail(format!("{} repositorios, {} tags borrados", results.len(), tags_deleted));
+        self.record(match errors.is_empty() {
+            true => summary,
+            false => summary.failed(errors.join("; ")),
+        });
+    }
+
+    // Página de entradas que cumplen el filtro, de la más reciente a la más antigua, y el total
+    pub fn query(&self, query: &AuditQuery) -> (usize, Vec<AuditEntry>) {
+        let entries = self.entries.read().unwrap();
+        let offset = query.offset().max(0) as usize;
+        let limit = query.limit_or_default().max(0) as usize;
+        let mut total = 0;
+        let mut page = Vec::new();
+        for entry in entries.iter().rev().filter(|e| query.matches(e)) {
+            if total >= offset && page.len() < limit {
+                page.push(entry.clone());
+            }
+            total += 1;
+        }
+        (total, page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::retention::PlannedDeletion;
+
+    #[test]
+    fn test_append_and_reload() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4().simple()));
+        let log = AuditLog::load(Some(path.clone()), 10);
+        log.record(AuditEntry::new("admin", AuditAction::TagDelete).repository("app").tag("v1").digest("sha256:a"));
+        log.record(AuditEntry::new("eve", AuditAction::Login).ip("10.0.0.1").failed("Invalid name or password"));
+        log.flush();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        let reloaded = AuditLog::load(Some(path.clone()), 10);
+        let (total, entries) = reloaded.query(&AuditQuery::default());
+        assert_eq!(total, 2);
+        // Lo más reciente primero
+        assert_eq!(entries[0].user, "eve");
+        assert_eq!(entries[1].digest.as_deref(), Some("sha256:a"));
+
+        // En memoria solo quedan las más recientes; el fichero lo guarda todo
+        let small = AuditLog::load(Some(path.clone()), 1);
+        small.record(AuditEntry::new("admin", AuditAction::AclUpdate));
+        small.flush();
+        let (total, entries) = small.query(&AuditQuery::default());
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].action, AuditAction::AclUpdate);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_repeated_entries_are_throttled() {
+        let log = AuditLog::load(None, 100);
+        let locked = || AuditEntry::new("eve", AuditAction::Login).ip("10.0.0.1").failed("Locked out");
+        for _ in 0..5 {
+            log.record_throttled(locked());
+        }
+        log.record_throttled(AuditEntry::new("eve", AuditAction::Login).ip("10.0.0.2").failed("Locked out"));
+        assert_eq!(log.query(&AuditQuery::default()).0, 2);
+
+        // Pasado el intervalo se anota de nuevo, con los intentos omitidos
+        let key = "eve|login|10.0.0.1|Locked out";
+        log.throttled.lock().unwrap().get_mut(key).unwrap().0 -= THROTTLE_INTERVAL;
+        log.record_throttled(locked());
+        let (total, entries) = log.query(&AuditQuery::default());
+        assert_eq!(total, 3);
+        assert_eq!(entries[0].detail.as_deref(), Some("Locked out (4 intentos más sin anotar)"));
+    }
+
+    #[test]
+    fn test_filters_and_paging() {
+        let log = AuditLog::load(None, 100);
+        for tag in ["v1", "v2", "v3"] {
+            log.record(AuditEntry::new("admin", AuditAction::TagDelete).repository("app").tag(tag));
+        }
+        log.record(AuditEntry::new("admin", AuditAction::AclUpdate));
+        log.record(AuditEntry::new("bob", AuditAction::TagDelete).repository("web").tag("v1").failed("denied"));
+
+        let query = AuditQuery { action: Some(AuditAction::TagDelete), user: Some("admin".into()), limit: Some(2), page: Some(2), ..Default::default() };
+        let (total, page) = log.query(&query);
+        assert_eq!(total, 3);
+        assert_eq!(page.iter().map(|e| e.tag.clone().unwrap()).collect::<Vec<_>>(), vec!["v1"]);
+
+        let query = AuditQuery { outcome: Some(AuditOutcome::Failure), ..Default::default() };
+        assert_eq!(log.query(&query).1[0].user, "bob");
+        let query = AuditQuery { since: Some(Utc::now() + chrono::Duration::hours(1)), ..Default::default() };
+        assert_eq!(log.query(&query).0, 0);
+    }
+
+    #[test]
+    fn test_retention_entries() {
+        let log = AuditLog::load(None, 100);
+        let results = vec![RetentionResult {
+            repository: "app".into(),
+            deleted: vec![PlannedDeletion { digest: "sha256:a".into(), tags: vec!["v1".into(), "v1.0".into()], size_bytes: 1 }],
+            errors: vec!["sha256:b: 500".into()],
+        }];
+        log.record_retention(SYSTEM_USER, &results);
+        let (total, entries) = log.query(&AuditQuery::default());
+        assert_eq!(total, 3);
+        assert_eq!(entries[0].action, AuditAction::RetentionRun);
+        assert_eq!(entries[0].outcome, AuditOutcome::Failure);
+        assert_eq!(entries[1].tag.as_deref(), Some("v1.0"));
+    }
+
+    #[test]
+    fn test_base_path_keeps_filters() {
+        let query = AuditQuery { action: Some(AuditAction::TagDelete), repository: Some("library/app".into()), ..Default::default() };
+        assert_eq!(query.base_path("/api/v1/audit"), "/api/v1/audit?action=tag_delete&repository=library%2Fapp");
+        assert_eq!(AuditQuery::default().base_path("/api/v1/audit"), "/api/v1/audit");
+    }
+}
@@ -0,0 +1,135 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+use super::Paginable;
+use super::paginable::QueryParams;
+use super::repository_info::RepositoryInfo;
+use super::sort_order::SortOrder;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogSort {
+    #[default]
+    Name,
+    LastPush,
+    TagCount,
+}
+
+impl CatalogSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatalogSort::Name => "name",
+            CatalogSort::LastPush => "last_push",
+            CatalogSort::TagCount => "tag_count",
+        }
+    }
+}
+
+// Parámetros de `GET /api/v1/registry?page=&limit=&sort=&order=&q=`
+#[derive(Deserialize, Debug, Default)]
+pub struct CatalogQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub sort: CatalogSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub q: Option<String>,
+}
+
+impl Paginable for CatalogQuery {
+    fn page(&self) -> Option<u32> {
+        self.page
+    }
+
+    fn limit(&self) -> Option<u32> {
+        self.limit
+    }
+}
+
+impl CatalogQuery {
+    // Filtro por subcadena sin distinguir mayúsculas
+    pub fn matches(&self, name: &str) -> bool {
+        match self.q.as_deref().map(str::trim) {
+            Some(q) if !q.is_empty() => name.to_lowercase().contains(&q.to_lowercase()),
+            _ => true,
+        }
+    }
+
+    // Ordenar por nombre no necesita consultar el Registry, así que se puede
+    // paginar antes de enriquecer los repositorios.
+    pub fn sorts_by_name(&self) -> bool {
+        self.sort == CatalogSort::Name
+    }
+
+    pub fn sort(&self, repositories: &mut [RepositoryInfo]) {
+        repositories.sort_by(|a, b| self.compare(a, b));
+    }
+
+    pub fn compare(&self, a: &RepositoryInfo, b: &RepositoryInfo) -> Ordering {
+        let ordering = match self.sort {
+            CatalogSort::Name => a.name.cmp(&b.name),
+            CatalogSort::LastPush => a.last_push_time().cmp(&b.last_push_time()),
+            CatalogSort::TagCount => a.tag_count.cmp(&b.tag_count),
+        };
+        // El nombre desempata para que el orden entre páginas sea estable
+        self.order.apply(ordering.then_with(|| a.name.cmp(&b.name)))
+    }
+
+    // Ruta base para los enlaces prev/next conservando orden y filtro
+    pub fn base_path(&self, path: &str) -> String {
+        QueryParams::new()
+            .add("sort", self.sort.as_str())
+            .add("order", self.order.as_str())
+            .add_opt("q", self.q.as_deref())
+            .path(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str, last_push: Option<&str>, tag_count: usize) -> RepositoryInfo {
+        RepositoryInfo {
+            name: name.to_string(),
+            last_push: last_push.map(|s| s.to_string()),
+            tag_count,
+        }
+    }
+
+    #[test]
+    fn test_matches_is_case_insensitive() {
+        let query = CatalogQuery { q: Some("App".into()), ..Default::default() };
+        assert!(query.matches("team/my-app"));
+        assert!(!query.matches("team/web"));
+        assert!(CatalogQuery::default().matches("anything"));
+    }
+
+    #[test]
+    fn test_sort_by_tag_count_desc() {
+        let query = CatalogQuery { sort: CatalogSort::TagCount, order: SortOrder::Desc, ..Default::default() };
+        let mut repos = vec![repo("a", None, 1), repo("b", None, 5), repo("c", None, 3)];
+        query.sort(&mut repos);
+        let names: Vec<_> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_sort_by_last_push() {
+        let query = CatalogQuery { sort: CatalogSort::LastPush, ..Default::default() };
+        let mut repos = vec![
+            repo("a", Some("2024-03-01T00:00:00Z"), 1),
+            repo("b", None, 1),
+            repo("c", Some("2024-01-01T10:00:00+02:00"), 1),
+        ];
+        query.sort(&mut repos);
+        let names: Vec<_> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_base_path_keeps_filters() {
+        let query = CatalogQuery { q: Some("my app".into()), order: SortOrder::Desc, ..Default::default() };
+        assert_eq!(query.base_path("/api/v1/registry"), "/api/v1/registry?sort=name&order=desc&q=my%20app");
+    }
+}
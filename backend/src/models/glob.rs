@@ -0,0 +1,37 @@
+use regex::Regex;
+
+// Convierte un patrón glob (`*` cualquier cadena, `?` un carácter) en una
+// expresión regular anclada. El resto de caracteres se tratan literalmente.
+pub fn glob_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut expression = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => expression.push_str(".*"),
+            '?' => expression.push('.'),
+            other => expression.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    expression.push('$');
+    Regex::new(&expression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_wildcards() {
+        let re = glob_to_regex("v1.*-rc?").unwrap();
+        assert!(re.is_match("v1.2-rc1"));
+        assert!(re.is_match("v1.-rc9"));
+        assert!(!re.is_match("v1.2-rc10"));
+        assert!(!re.is_match("xv1.2-rc1"));
+    }
+
+    #[test]
+    fn test_glob_escapes_regex_characters() {
+        let re = glob_to_regex("team/app+(1)").unwrap();
+        assert!(re.is_match("team/app+(1)"));
+        assert!(!re.is_match("team/appp(1)"));
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Blob de configuración de la imagen (application/vnd.docker.container.image.v1+json
+// o application/vnd.oci.image.config.v1+json). Se deserializa con los nombres del
+// Registry y se serializa en snake_case para la API.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ImageConfig {
+    pub created: Option<String>,
+    pub architecture: Option<String>,
+    pub os: Option<String>,
+    pub variant: Option<String>,
+    #[serde(default)]
+    pub config: Option<ContainerConfig>,
+    #[serde(default, skip_serializing)]
+    pub history: Vec<HistoryEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ContainerConfig {
+    #[serde(rename(deserialize = "Env"), default)]
+    pub env: Option<Vec<String>>,
+    #[serde(rename(deserialize = "Entrypoint"), default)]
+    pub entrypoint: Option<Vec<String>>,
+    #[serde(rename(deserialize = "Cmd"), default)]
+    pub cmd: Option<Vec<String>>,
+    #[serde(rename(deserialize = "Labels"), default)]
+    pub labels: Option<BTreeMap<String, String>>,
+    #[serde(
+        rename(deserialize = "ExposedPorts"),
+        default,
+        deserialize_with = "deserialize_exposed_ports"
+    )]
+    pub exposed_ports: Vec<String>,
+    #[serde(rename(deserialize = "WorkingDir"), default)]
+    pub working_dir: Option<String>,
+    #[serde(rename(deserialize = "User"), default)]
+    pub user: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HistoryEntry {
+    pub created: Option<String>,
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub empty_layer: bool,
+}
+
+// ExposedPorts llega como {"80/tcp": {}}; solo nos interesan las claves
+fn deserialize_exposed_ports<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ports: Option<BTreeMap<String, Value>> = Option::deserialize(deserializer)?;
+    Ok(ports.map(|p| p.into_keys().collect()).unwrap_or_default())
+}
+
+impl ImageConfig {
+    // Comandos que generaron cada capa real (las entradas `empty_layer` no tienen capa)
+    pub fn layer_commands(&self) -> Vec<Option<String>> {
+        self.history
+            .iter()
+            .filter(|h| !h.empty_layer)
+            .map(|h| h.created_by.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_deserialize_docker_config_blob() {
+        let blob = json!({
+            "created": "2024-01-01T00:00:00Z",
+            "architecture": "amd64",
+            "os": "linux",
+            "config": {
+                "Env": ["PATH=/usr/bin"],
+                "Entrypoint": ["/entrypoint.sh"],
+                "Cmd": null,
+                "Labels": {"maintainer": "atareao"},
+                "ExposedPorts": {"80/tcp": {}, "443/tcp": {}},
+                "WorkingDir": "/app",
+                "User": "app"
+            },
+            "history": [
+                {"created_by": "ADD rootfs"},
+                {"created_by": "ENV PATH=/usr/bin", "empty_layer": true},
+                {"created_by": "COPY . /app"}
+            ]
+        });
+        let config: ImageConfig = serde_json::from_value(blob).unwrap();
+        let container = config.config.clone().unwrap();
+        assert_eq!(config.architecture.as_deref(), Some("amd64"));
+        assert_eq!(container.exposed_ports, vec!["443/tcp", "80/tcp"]);
+        assert_eq!(container.cmd, None);
+        assert_eq!(container.working_dir.as_deref(), Some("/app"));
+        assert_eq!(
+            config.layer_commands(),
+            vec![Some("ADD rootfs".to_string()), Some("COPY . /app".to_string())]
+        );
+
+        let serialized = serde_json::to_value(&config).unwrap();
+        assert_eq!(serialized["config"]["entrypoint"], json!(["/entrypoint.sh"]));
+    }
+
+    #[test]
+    fn test_deserialize_minimal_config_blob() {
+        let config: ImageConfig = serde_json::from_str("{}").unwrap();
+        assert!(config.config.is_none());
+        assert!(config.layer_commands().is_empty());
+    }
+}
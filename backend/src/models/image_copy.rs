@@ -0,0 +1,435 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use reqwest::Body;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::debug;
+
+use super::manifest::Manifest;
+use super::manifest_v2::ManifestV2;
+use super::registry_client::{BlobUpload, RawManifest, RegistryClient};
+
+// Imagen de origen o destino de una copia. Sin `registry` se usa el principal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageRef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    pub repository: String,
+    pub reference: String,
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(registry) = &self.registry {
+            write!(f, "{}/", registry)?;
+        }
+        let separator = if self.reference.contains(':') { '@' } else { ':' };
+        write!(f, "{}{}{}", self.repository, separator, self.reference)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CopyRequest {
+    pub source: ImageRef,
+    // `reference` tiene que ser un tag
+    pub target: ImageRef,
+    // Sobrescribe el tag de destino aunque ya apunte a otra imagen
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CopyProgress {
+    pub id: u64,
+    pub user: String,
+    pub source: ImageRef,
+    pub target: ImageRef,
+    pub status: CopyStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    // Manifiestos a subir: la imagen o el índice más sus plataformas
+    pub manifests: usize,
+    pub blobs_total: usize,
+    pub blobs_done: usize,
+    // De los hechos, los montados desde otro repositorio y los que ya estaban en el destino
+    pub blobs_mounted: usize,
+    pub blobs_existing: usize,
+    pub bytes_total: u64,
+    pub bytes_copied: u64,
+    pub digest: Option<String>,
+    pub error: Option<String>,
+}
+
+// Copias en curso y recientes, de la más reciente a la más antigua
+pub struct CopyTracker {
+    next_id: AtomicU64,
+    copies: Mutex<VecDeque<CopyProgress>>,
+    history_size: usize,
+}
+
+impl CopyTracker {
+    pub fn new(history_size: usize) -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            copies: Mutex::new(VecDeque::with_capacity(history_size)),
+            history_size,
+        }
+    }
+
+    pub fn start(&self, user: &str, source: ImageRef, target: ImageRef) -> CopyProgress {
+        let progress = CopyProgress {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            user: user.to_string(),
+            source,
+            target,
+            status: CopyStatus::Running,
+            started_at: Utc::now(),
+            finished_at: None,
+            manifests: 0,
+            blobs_total: 0,
+            blobs_done: 0,
+            blobs_mounted: 0,
+            blobs_existing: 0,
+            bytes_total: 0,
+            bytes_copied: 0,
+            digest: None,
+            error: None,
+        };
+        let mut copies = self.copies.lock().unwrap();
+        if copies.len() == self.history_size {
+            copies.pop_back();
+        }
+        copies.push_front(progress.clone());
+        progress
+    }
+
+    pub fn update(&self, id: u64, change: impl FnOnce(&mut CopyProgress)) {
+        if let Some(progress) = self.copies.lock().unwrap().iter_mut().find(|p| p.id == id) {
+            change(progress);
+        }
+    }
+
+    pub fn finish(&self, id: u64, result: &Result<String, (StatusCode, String)>) {
+        self.update(id, |progress| {
+            progress.finished_at = Some(Utc::now());
+            match result {
+                Ok(digest) => {
+                    progress.status = CopyStatus::Succeeded;
+                    progress.digest = Some(digest.clone());
+                }
+                Err((_, message)) => {
+                    progress.status = CopyStatus::Failed;
+                    progress.error = Some(message.clone());
+                }
+            }
+        });
+    }
+
+    pub fn get(&self, id: u64) -> Option<CopyProgress> {
+        self.copies.lock().unwrap().iter().find(|p| p.id == id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<CopyProgress> {
+        self.copies.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+enum BlobCopy {
+    Copied,
+    Mounted,
+    Existing,
+}
+
+fn parse(raw: &RawManifest) -> Result<Manifest, (StatusCode, String)> {
+    raw.parse()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Manifiesto {} no válido: {}", raw.digest, e)))
+}
+
+fn add_blobs(blobs: &mut Vec<(String, u64)>, image: &ManifestV2) {
+    let descriptors = std::iter::once((&image.config.digest, image.config.size))
+        .chain(image.layers.iter().map(|l| (&l.digest, l.size)));
+    for (digest, size) in descriptors {
+        if !blobs.iter().any(|(d, _)| d == digest) {
+            blobs.push((digest.clone(), size));
+        }
+    }
+}
+
+// Antes de empezar la copia: sin `force`, un tag de destino que ya apunta a otra
+// imagen es un conflicto, como en `retag`
+pub async fn check_copy_target(
+    source_client: &RegistryClient,
+    source: &ImageRef,
+    target_client: &RegistryClient,
+    target: &ImageRef,
+    force: bool,
+) -> Result<(), (StatusCode, String)> {
+    if force {
+        return Ok(());
+    }
+    let root = source_client.fetch_raw_manifest(&source.repository, &source.reference).await?;
+    target_client
+        .check_tag_overwrite(&target.repository, &target.reference, &root.digest, false)
+        .await
+}
+
+// Copia la imagen `source` (o todas las plataformas de un índice) a `target`, con los
+// manifiestos tal cual para que conserven su digest. Devuelve el digest subido.
+pub async fn copy_image(
+    source_client: &RegistryClient,
+    source: &ImageRef,
+    target_client: &RegistryClient,
+    target: &ImageRef,
+    force: bool,
+    tracker: &CopyTracker,
+    id: u64,
+) -> Result<String, (StatusCode, String)> {
+    let root = source_client.fetch_raw_manifest(&source.repository, &source.reference).await?;
+    let mut children = Vec::new();
+    let mut blobs = Vec::new();
+    match parse(&root)? {
+        Manifest::DockerV2(image) | Manifest::OciImage(image) => add_blobs(&mut blobs, &image),
+        Manifest::DockerList(list) | Manifest::OciIndex(list) => {
+            // Todas las entradas, también las atestaciones: el índice las referencia
+            for child in &list.manifests {
+                let raw = source_client.fetch_raw_manifest(&source.repository, &child.digest).await?;
+                match parse(&raw)? {
+                    Manifest::DockerV2(image) | Manifest::OciImage(image) => add_blobs(&mut blobs, &image),
+                    _ => {
+                        return Err((
+                            StatusCode::BAD_REQUEST,
+                            format!("El índice {} contiene otro índice", root.digest),
+                        ))
+                    }
+                }
+                children.push((child.digest.clone(), raw));
+            }
+        }
+    }
+    tracker.update(id, |progress| {
+        progress.manifests = children.len() + 1;
+        progress.blobs_total = blobs.len();
+        progress.bytes_total = blobs.iter().map(|(_, size)| size).sum();
+    });
+
+    for (digest, size) in blobs {
+        let copied = copy_blob(source_client, &source.repository, target_client, &target.repository, &digest, size).await?;
+        tracker.update(id, |progress| {
+            progress.blobs_done += 1;
+            match copied {
+                BlobCopy::Copied => progress.bytes_copied += size,
+                BlobCopy::Mounted => progress.blobs_mounted += 1,
+                BlobCopy::Existing => progress.blobs_existing += 1,
+            }
+        });
+    }
+
+    // Las plataformas antes que el índice que las referencia
+    for (digest, raw) in &children {
+        target_client.put_manifest(&target.repository, digest, raw).await?;
+    }
+    // Se vuelve a mirar el tag tras copiar los blobs, que puede llevar un rato. Como en
+    // `retag`, un push del mismo tag entre esta comprobación y el PUT queda sobrescrito.
+    target_client
+        .check_tag_overwrite(&target.repository, &target.reference, &root.digest, force)
+        .await?;
+    target_client.put_manifest(&target.repository, &target.reference, &root).await
+}
+
+async fn copy_blob(
+    source_client: &RegistryClient,
+    source_repo: &str,
+    target_client: &RegistryClient,
+    target_repo: &str,
+    digest: &str,
+    size: u64,
+) -> Result<BlobCopy, (StatusCode, String)> {
+    let same_registry = source_client.base_url() == target_client.base_url();
+    if (same_registry && source_repo == target_repo) || target_client.blob_exists(target_repo, digest).await? {
+        return Ok(BlobCopy::Existing);
+    }
+    let location = if same_registry {
+        match target_client.mount_blob(target_repo, digest, source_repo).await? {
+            BlobUpload::Mounted => return Ok(BlobCopy::Mounted),
+            BlobUpload::Started(location) => location,
+        }
+    } else {
+        target_client.start_upload(target_repo).await?
+    };
+    debug!("Copiando blob {} ({} bytes) a {}", digest, size, target_repo);
+    let blob = source_client.fetch_blob(source_repo, digest).await?;
+    target_client
+        .upload_blob(&location, digest, size, Body::wrap_stream(blob.bytes_stream()))
+        .await?;
+    Ok(BlobCopy::Copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Bytes,
+        extract::{Path, RawQuery, State},
+        http::HeaderMap,
+        routing,
+    };
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const IMAGE: &str = r#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","config":{"mediaType":"application/vnd.oci.image.config.v1+json","size":2,"digest":"sha256:cfg"},"layers":[{"mediaType":"application/vnd.oci.image.layer.v1.tar+gzip","size":5,"digest":"sha256:layer"}]}"#;
+
+    // Registry en memoria: blobs y manifiestos por `repo/referencia`
+    #[derive(Default)]
+    struct FakeRegistry {
+        blobs: Mutex<HashMap<String, Bytes>>,
+        manifests: Mutex<HashMap<String, Bytes>>,
+        mounts: Mutex<Vec<String>>,
+    }
+
+    async fn spawn_registry(registry: Arc<FakeRegistry>) -> String {
+        let router = Router::new()
+            .route(
+                "/v2/{repo}/manifests/{reference}",
+                routing::get(|State(r): State<Arc<FakeRegistry>>, Path((repo, reference)): Path<(String, String)>| async move {
+                    let Some(body) = r.manifests.lock().unwrap().get(&format!("{}/{}", repo, reference)).cloned() else {
+                        return (StatusCode::NOT_FOUND, HeaderMap::new(), Bytes::new());
+                    };
+                    let mut headers = HeaderMap::new();
+                    headers.insert("Content-Type", "application/vnd.oci.image.manifest.v1+json".parse().unwrap());
+                    let digest = format!("sha256:{}", hex::encode(Sha256::digest(&body)));
+                    headers.insert("Docker-Content-Digest", digest.parse().unwrap());
+                    (StatusCode::OK, headers, body)
+                })
+                .put(|State(r): State<Arc<FakeRegistry>>, Path((repo, reference)): Path<(String, String)>, body: Bytes| async move {
+                    r.manifests.lock().unwrap().insert(format!("{}/{}", repo, reference), body);
+                    StatusCode::CREATED
+                }),
+            )
+            .route(
+                "/v2/{repo}/blobs/{digest}",
+                routing::get(|State(r): State<Arc<FakeRegistry>>, Path((repo, digest)): Path<(String, String)>| async move {
+                    let blob = r.blobs.lock().unwrap().get(&format!("{}/{}", repo, digest)).cloned();
+                    blob.map(|b| (StatusCode::OK, b)).unwrap_or((StatusCode::NOT_FOUND, Bytes::new()))
+                }),
+            )
+            .route(
+                "/v2/{repo}/blobs/uploads/",
+                routing::post(|State(r): State<Arc<FakeRegistry>>, Path(repo): Path<String>, RawQuery(query): RawQuery| async move {
+                    let mut headers = HeaderMap::new();
+                    if let Some(query) = query.filter(|q| q.starts_with("mount=")) {
+                        r.mounts.lock().unwrap().push(query);
+                        return (StatusCode::CREATED, headers);
+                    }
+                    headers.insert("Location", format!("/v2/{}/blobs/uploads/u1?_state=x", repo).parse().unwrap());
+                    (StatusCode::ACCEPTED, headers)
+                }),
+            )
+            .route(
+                "/v2/{repo}/blobs/uploads/{id}",
+                routing::put(
+                    |State(r): State<Arc<FakeRegistry>>, Path((repo, _)): Path<(String, String)>, RawQuery(query): RawQuery, body: Bytes| async move {
+                        let digest = query.unwrap().split("digest=").nth(1).unwrap().replace("%3A", ":");
+                        r.blobs.lock().unwrap().insert(format!("{}/{}", repo, digest), body);
+                        StatusCode::CREATED
+                    },
+                ),
+            )
+            .with_state(registry);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn image_ref(repository: &str, reference: &str) -> ImageRef {
+        ImageRef { registry: None, repository: repository.into(), reference: reference.into() }
+    }
+
+    fn source_registry() -> Arc<FakeRegistry> {
+        let registry = FakeRegistry::default();
+        registry.manifests.lock().unwrap().insert("app/rc-42".into(), Bytes::from(IMAGE));
+        registry.blobs.lock().unwrap().insert("app/sha256:cfg".into(), Bytes::from("{}"));
+        registry.blobs.lock().unwrap().insert("app/sha256:layer".into(), Bytes::from("layer"));
+        Arc::new(registry)
+    }
+
+    #[tokio::test]
+    async fn test_copy_between_registries_streams_blobs() {
+        let source = source_registry();
+        let target = Arc::new(FakeRegistry::default());
+        let source_client = RegistryClient::new(spawn_registry(source).await, String::new());
+        let target_client = RegistryClient::new(spawn_registry(target.clone()).await, String::new());
+        let tracker = CopyTracker::new(10);
+        let progress = tracker.start("editor", image_ref("app", "rc-42"), image_ref("app", "1.4.0"));
+
+        let result = copy_image(
+            &source_client,
+            &progress.source,
+            &target_client,
+            &progress.target,
+            false,
+            &tracker,
+            progress.id,
+        )
+        .await;
+        tracker.finish(progress.id, &result);
+
+        // El manifiesto llega byte a byte, así que conserva el digest
+        assert_eq!(target.manifests.lock().unwrap()["app/1.4.0"], Bytes::from(IMAGE));
+        assert_eq!(target.blobs.lock().unwrap()["app/sha256:layer"], Bytes::from("layer"));
+        let progress = tracker.get(progress.id).unwrap();
+        assert_eq!(progress.status, CopyStatus::Succeeded);
+        assert_eq!((progress.blobs_total, progress.blobs_done, progress.bytes_copied), (2, 2, 7));
+    }
+
+    #[tokio::test]
+    async fn test_copy_within_registry_mounts_blobs() {
+        let registry = source_registry();
+        let client = RegistryClient::new(spawn_registry(registry.clone()).await, String::new());
+        let tracker = CopyTracker::new(10);
+        let progress = tracker.start("editor", image_ref("app", "rc-42"), image_ref("app-prod", "1.4.0"));
+
+        copy_image(&client, &progress.source, &client, &progress.target, false, &tracker, progress.id)
+            .await
+            .unwrap();
+        assert_eq!(registry.mounts.lock().unwrap().len(), 2);
+        assert!(registry.manifests.lock().unwrap().contains_key("app-prod/1.4.0"));
+        assert_eq!(tracker.get(progress.id).unwrap().blobs_mounted, 2);
+    }
+
+    #[tokio::test]
+    async fn test_existing_target_tag_needs_force() {
+        let registry = source_registry();
+        let other = Bytes::from(IMAGE.replace("sha256:layer", "sha256:other"));
+        registry.manifests.lock().unwrap().insert("app/1.4.0".into(), other.clone());
+        let client = RegistryClient::new(spawn_registry(registry.clone()).await, String::new());
+        let tracker = CopyTracker::new(10);
+        let (source, target) = (image_ref("app", "rc-42"), image_ref("app", "1.4.0"));
+
+        let error = check_copy_target(&client, &source, &client, &target, false).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::CONFLICT);
+        // Aunque se salte la comprobación previa, el tag se vuelve a mirar antes del PUT
+        let error = copy_image(&client, &source, &client, &target, false, &tracker, 0).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::CONFLICT);
+        assert_eq!(registry.manifests.lock().unwrap()["app/1.4.0"], other);
+
+        // Si ya apunta a la misma imagen no hay conflicto
+        check_copy_target(&client, &source, &client, &image_ref("app", "rc-42"), false).await.unwrap();
+        check_copy_target(&client, &source, &client, &target, true).await.unwrap();
+        copy_image(&client, &source, &client, &target, true, &tracker, 0).await.unwrap();
+        assert_eq!(registry.manifests.lock().unwrap()["app/1.4.0"], Bytes::from(IMAGE));
+    }
+}
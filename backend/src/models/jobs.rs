@@ -0,0 +1,271 @@
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+use super::AppState;
+use super::audit_log::{SYSTEM_USER, AuditAction, AuditEntry};
+use super::retention::{apply_retention, plan_retention};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobTrigger {
+    Schedule,
+    Manual,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    // Terminó, pero algún borrado falló
+    Failed,
+    // Tocaba ejecutarse pero los trabajos estaban en pausa
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct JobRun {
+    pub id: u64,
+    pub job: String,
+    pub trigger: JobTrigger,
+    pub status: JobStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub repositories: usize,
+    pub tags_deleted: usize,
+    pub digests_deleted: usize,
+    pub errors: Vec<String>,
+}
+
+// Programación e historial de los trabajos en segundo plano (por ahora, la retención)
+pub struct JobScheduler {
+    schedule: Option<Schedule>,
+    expression: Option<String>,
+    paused: AtomicBool,
+    running: AtomicBool,
+    next_id: AtomicU64,
+    runs: Mutex<VecDeque<JobRun>>,
+    history_size: usize,
+}
+
+pub const RETENTION_JOB: &str = "retention";
+
+// Acepta expresiones cron de 5 campos (min hora día mes día-semana) además de las
+// de 6/7 campos con segundos que entiende el crate `cron`.
+pub fn parse_schedule(expression: &str) -> Result<Schedule, String> {
+    let fields = expression.split_whitespace().count();
+    let normalized = if fields == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    Schedule::from_str(&normalized).map_err(|e| format!("Expresión cron no válida '{}': {}", expression, e))
+}
+
+impl JobScheduler {
+    pub fn new(expression: Option<String>, paused: bool, history_size: usize) -> Result<Self, String> {
+        let schedule = expression.as_deref().map(parse_schedule).transpose()?;
+        Ok(Self {
+            schedule,
+            expression,
+            paused: AtomicBool::new(paused),
+            running: AtomicBool::new(false),
+            next_id: AtomicU64::new(1),
+            runs: Mutex::new(VecDeque::with_capacity(history_size)),
+            history_size,
+        })
+    }
+
+    pub fn expression(&self) -> Option<&str> {
+        self.expression.as_deref()
+    }
+
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        self.schedule.as_ref()?.upcoming(Utc).next()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    // Historial de ejecuciones, de la más reciente a la más antigua
+    pub fn runs(&self) -> Vec<JobRun> {
+        self.runs.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn start(&self, trigger: JobTrigger, status: JobStatus) -> JobRun {
+        let run = JobRun {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            job: RETENTION_JOB.to_string(),
+            trigger,
+            status,
+            started_at: Utc::now(),
+            finished_at: None,
+            repositories: 0,
+            tags_deleted: 0,
+            digests_deleted: 0,
+            errors: Vec::new(),
+        };
+        self.record(run.clone());
+        run
+    }
+
+    fn record(&self, run: JobRun) {
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(existing) = runs.iter_mut().find(|r| r.id == run.id) {
+            *existing = run;
+            return;
+        }
+        if runs.len() == self.history_size {
+            runs.pop_back();
+        }
+        runs.push_front(run);
+    }
+}
+
+// Marca la ejecución en curso. Al soltarse, aunque la ejecución se cancele o
+// haga panic a medias, libera el turno y cierra la ejecución si quedó abierta.
+struct RunningGuard<'a> {
+    jobs: &'a JobScheduler,
+    id: u64,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut runs) = self.jobs.runs.lock()
+            && let Some(run) = runs.iter_mut().find(|r| r.id == self.id && r.status == JobStatus::Running)
+        {
+            run.status = JobStatus::Failed;
+            run.finished_at = Some(Utc::now());
+            run.errors.push("La ejecución se interrumpió".to_string());
+        }
+        self.jobs.running.store(false, Ordering::SeqCst);
+    }
+}
+
+// Ejecuta la retención de todos los repositorios con política y registra la ejecución
+// (también en la auditoría, a nombre de `user`).
+// Devuelve `Err` si los trabajos están en pausa o ya hay una ejecución en curso.
+pub async fn run_retention_job(app_state: &AppState, trigger: JobTrigger, user: &str) -> Result<JobRun, String> {
+    let jobs = &app_state.jobs;
+    if jobs.is_paused() {
+        let mut run = jobs.start(trigger, JobStatus::Skipped);
+        run.finished_at = Some(Utc::now());
+        jobs.record(run);
+        return Err("Los trabajos están en pausa".to_string());
+    }
+    if jobs.running.swap(true, Ordering::SeqCst) {
+        return Err("Ya hay una ejecución de retención en curso".to_string());
+    }
+
+    let mut run = jobs.start(trigger, JobStatus::Running);
+    let _guard = RunningGuard { jobs, id: run.id };
+    info!("Job {} #{} iniciado ({:?})", RETENTION_JOB, run.id, trigger);
+    match plan_retention(app_state.registries.default_client(), &app_state.retention, None).await {
+        Ok(plans) => {
+            run.repositories = plans.len();
+            let results = apply_retention(app_state.registries.default_client(), plans).await;
+            app_state.audit.record_retention(user, &results);
+            for result in results {
+                run.digests_deleted += result.deleted.len();
+                run.tags_deleted += result.deleted.iter().map(|d| d.tags.len()).sum::<usize>();
+                run.errors.extend(result.errors.into_iter().map(|e| format!("{}: {}", result.repository, e)));
+            }
+        }
+        Err((_, message)) => {
+            app_state
+                .audit
+                .record(AuditEntry::new(user, AuditAction::RetentionRun).failed(message.as_str()));
+            run.errors.push(message);
+        }
+    }
+    run.status = if run.errors.is_empty() { JobStatus::Succeeded } else { JobStatus::Failed };
+    run.finished_at = Some(Utc::now());
+    jobs.record(run.clone());
+    info!(
+        "Job {} #{} terminado: {} tags borrados, {} errores",
+        RETENTION_JOB,
+        run.id,
+        run.tags_deleted,
+        run.errors.len()
+    );
+    Ok(run)
+}
+
+// Bucle del planificador: duerme hasta la siguiente hora programada y lanza la retención
+pub async fn run_scheduler(app_state: Arc<AppState>) {
+    let Some(schedule) = app_state.jobs.schedule.clone() else {
+        info!("Sin RETENTION_SCHEDULE: la retención programada está desactivada");
+        return;
+    };
+    info!("Retención programada: {}", app_state.jobs.expression().unwrap_or_default());
+
+    while let Some(next) = schedule.upcoming(Utc).next() {
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        if let Err(message) = run_retention_job(&app_state, JobTrigger::Schedule, SYSTEM_USER).await {
+            error!("Job {} no ejecutado: {}", RETENTION_JOB, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuditQuery;
+
+    #[test]
+    fn test_parse_five_field_schedule() {
+        let schedule = parse_schedule("30 3 * * *").unwrap();
+        let next = schedule.upcoming(Utc).next().unwrap();
+        assert_eq!(next.format("%H:%M:%S").to_string(), "03:30:00");
+        assert!(parse_schedule("not a cron").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_paused_jobs_are_skipped_and_recorded() {
+        let mut app_state = AppState::for_tests();
+        app_state.jobs = JobScheduler::new(None, true, 10).unwrap();
+        assert!(run_retention_job(&app_state, JobTrigger::Manual, "admin").await.is_err());
+        let runs = app_state.jobs.runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, JobStatus::Skipped);
+        assert!(runs[0].finished_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_registry_errors_are_recorded() {
+        // El Registry de pruebas no está levantado, así que el catálogo falla
+        let app_state = AppState::for_tests();
+        let run = run_retention_job(&app_state, JobTrigger::Manual, "editor").await.unwrap();
+        assert_eq!(run.status, JobStatus::Failed);
+        assert_eq!(run.tags_deleted, 0);
+        assert!(!run.errors.is_empty());
+        assert_eq!(app_state.jobs.runs()[0].status, JobStatus::Failed);
+        assert!(!app_state.jobs.running.load(Ordering::SeqCst));
+        let (_, entries) = app_state.audit.query(&AuditQuery::default());
+        assert_eq!(entries[0].user, "editor");
+        assert_eq!(entries[0].action, AuditAction::RetentionRun);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_run_releases_the_flag() {
+        let app_state = AppState::for_tests();
+        // Se abandona el futuro en mitad de la ejecución, como cuando el cliente se desconecta
+        let job = run_retention_job(&app_state, JobTrigger::Manual, "editor");
+        let _ = tokio::time::timeout(std::time::Duration::ZERO, job).await;
+        assert!(!app_state.jobs.running.load(Ordering::SeqCst));
+        let run = &app_state.jobs.runs()[0];
+        assert_eq!(run.status, JobStatus::Failed);
+        assert!(run.finished_at.is_some());
+    }
+}
@@ -0,0 +1,427 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use std::collections::HashMap;
+
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use tracing::{debug, error};
+use super::user_store::{Role, mapped_role};
+
+pub const LDAP_PROVIDER: &str = "ldap";
+
+// Código LDAP de credenciales incorrectas (RFC 4511)
+const INVALID_CREDENTIALS: u32 = 49;
+
+pub struct LdapConfig {
+    // Cuenta de servicio para buscar al usuario; sin ella la búsqueda es anónima
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    pub base_dn: String,
+    // Filtro de búsqueda; `{username}` se sustituye por el nombre escapado
+    pub user_filter: String,
+    // Atributo con el nombre canónico del usuario, que es el que se guarda como cuenta
+    pub username_attribute: String,
+    pub group_attribute: String,
+    // Grupo (DN completo o su CN) → rol
+    pub role_mapping: Vec<(String, Role)>,
+    pub default_role: Option<Role>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryEntry {
+    pub dn: String,
+    pub username: Option<String>,
+    pub groups: Vec<String>,
+}
+
+// Operaciones que necesitamos del directorio. La implementación real usa `ldap3`;
+// los tests usan un directorio en memoria.
+#[async_trait]
+pub trait Directory: Send + Sync {
+    // Bind simple; `Ok(false)` si las credenciales no son válidas
+    async fn bind(&self, dn: &str, password: &str) -> Result<bool, String>;
+
+    async fn search(
+        &self,
+        credentials: Option<(&str, &str)>,
+        base_dn: &str,
+        filter: &str,
+        username_attribute: &str,
+        group_attribute: &str,
+    ) -> Result<Vec<DirectoryEntry>, String>;
+}
+
+pub struct Ldap3Directory {
+    url: String,
+    starttls: bool,
+}
+
+impl Ldap3Directory {
+    // Las contraseñas viajan en el bind: sin `ldaps://` ni StartTLS solo se acepta
+    // la conexión en claro si se pide expresamente
+    pub fn new(url: &str, starttls: bool, allow_plaintext: bool) -> Result<Self, String> {
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+        match scheme.as_deref() {
+            Some("ldaps") if starttls => Err("StartTLS no se usa con ldaps://".to_string()),
+            Some("ldaps") | Some("ldapi") => Ok(()),
+            Some("ldap") if starttls || allow_plaintext => Ok(()),
+            Some("ldap") => Err(format!(
+                "{} enviaría las contraseñas en claro; usa ldaps:// o LDAP_STARTTLS=true",
+                url
+            )),
+            _ => Err(format!("URL LDAP no válida: {}", url)),
+        }?;
+        Ok(Self { url: url.to_string(), starttls })
+    }
+
+    async fn connect(&self) -> Result<ldap3::Ldap, String> {
+        let settings = LdapConnSettings::new().set_starttls(self.starttls);
+        let (conn, ldap) = LdapConnAsync::with_settings(settings, &self.url)
+            .await
+            .map_err(|e| format!("No se pudo conectar con {}: {}", self.url, e))?;
+        ldap3::drive!(conn);
+        Ok(ldap)
+    }
+}
+
+#[async_trait]
+impl Directory for Ldap3Directory {
+    async fn bind(&self, dn: &str, password: &str) -> Result<bool, String> {
+        let mut ldap = self.connect().await?;
+        let result = ldap.simple_bind(dn, password).await.map_err(|e| e.to_string())?;
+        let _ = ldap.unbind().await;
+        match result.rc {
+            0 => Ok(true),
+            INVALID_CREDENTIALS => Ok(false),
+            _ => Err(result.to_string()),
+        }
+    }
+
+    async fn search(
+        &self,
+        credentials: Option<(&str, &str)>,
+        base_dn: &str,
+        filter: &str,
+        username_attribute: &str,
+        group_attribute: &str,
+    ) -> Result<Vec<DirectoryEntry>, String> {
+        let mut ldap = self.connect().await?;
+        if let Some((dn, password)) = credentials {
+            ldap.simple_bind(dn, password)
+                .await
+                .and_then(|r| r.success())
+                .map_err(|e| format!("Bind de la cuenta de servicio fallido: {}", e))?;
+        }
+        let (entries, _) = ldap
+            .search(base_dn, Scope::Subtree, filter, vec![username_attribute, group_attribute])
+            .await
+            .and_then(|r| r.success())
+            .map_err(|e| e.to_string())?;
+        let _ = ldap.unbind().await;
+        Ok(entries
+            .into_iter()
+            .map(SearchEntry::construct)
+            .map(|mut entry| DirectoryEntry {
+                username: take_attribute(&mut entry.attrs, username_attribute).into_iter().next(),
+                groups: take_attribute(&mut entry.attrs, group_attribute),
+                dn: entry.dn,
+            })
+            .collect())
+    }
+}
+
+// Los nombres de atributo no distinguen mayúsculas y el servidor puede devolverlos
+// con otra forma que la pedida
+fn take_attribute(attrs: &mut HashMap<String, Vec<String>>, name: &str) -> Vec<String> {
+    let key = attrs.keys().find(|key| key.eq_ignore_ascii_case(name)).cloned();
+    key.and_then(|key| attrs.remove(&key)).unwrap_or_default()
+}
+
+// Usuario encontrado en el directorio, antes de comprobar su contraseña
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdapUser {
+    // Nombre canónico según el directorio, no el que se escribió en el login
+    pub username: String,
+    dn: String,
+    groups: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdapIdentity {
+    pub username: String,
+    pub groups: Vec<String>,
+    pub role: Role,
+}
+
+// CN de un DN de grupo: `cn=admins,ou=groups,dc=example,dc=com` → `admins`
+fn group_name(group: &str) -> &str {
+    group
+        .split(',')
+        .next()
+        .and_then(|rdn| rdn.split_once('='))
+        .filter(|(attribute, _)| attribute.trim().eq_ignore_ascii_case("cn"))
+        .map(|(_, value)| value.trim())
+        .unwrap_or(group)
+}
+
+// Autenticación search + bind: se busca el DN del usuario y se hace bind con su contraseña
+pub struct LdapProvider {
+    config: LdapConfig,
+    directory: Box<dyn Directory>,
+}
+
+impl LdapProvider {
+    pub fn new(config: LdapConfig, directory: Box<dyn Directory>) -> Self {
+        Self { config, directory }
+    }
+
+    // Busca al usuario. `Ok(None)` si no existe; el nombre del resultado es el del
+    // directorio, así que `Dave` o un alias del filtro llevan a la misma cuenta
+    pub async fn find(&self, username: &str) -> Result<Option<LdapUser>, (StatusCode, String)> {
+        if username.is_empty() {
+            return Ok(None);
+        }
+        let filter = self.config.user_filter.replace("{username}", &ldap_escape(username));
+        let credentials = self
+            .config
+            .bind_dn
+            .as_deref()
+            .map(|dn| (dn, self.config.bind_password.as_deref().unwrap_or_default()));
+        let entries = self
+            .directory
+            .search(
+                credentials,
+                &self.config.base_dn,
+                &filter,
+                &self.config.username_attribute,
+                &self.config.group_attribute,
+            )
+            .await
+            .map_err(unavailable)?;
+        let entry = match entries.as_slice() {
+            [entry] => entry,
+            [] => {
+                debug!("LDAP user not found: {}", username);
+                return Ok(None);
+            }
+            _ => {
+                error!("LDAP filter {} matches {} entries", filter, entries.len());
+                return Ok(None);
+            }
+        };
+        let Some(canonical) = entry.username.as_deref().filter(|u| !u.is_empty()) else {
+            error!("LDAP entry {} has no {} attribute", entry.dn, self.config.username_attribute);
+            return Ok(None);
+        };
+        Ok(Some(LdapUser {
+            username: canonical.to_string(),
+            dn: entry.dn.clone(),
+            groups: entry.groups.clone(),
+        }))
+    }
+
+    // Bind con la contraseña del usuario encontrado. `Ok(None)` si no es válida
+    pub async fn authenticate(
+        &self,
+        user: &LdapUser,
+        password: &str,
+    ) -> Result<Option<LdapIdentity>, (StatusCode, String)> {
+        // Un bind con contraseña vacía es un bind anónimo y el servidor lo aceptaría
+        if password.is_empty() {
+            return Ok(None);
+        }
+        if !self.directory.bind(&user.dn, password).await.map_err(unavailable)? {
+            debug!("LDAP bind failed for {}", user.dn);
+            return Ok(None);
+        }
+
+        let role = mapped_role(&self.config.role_mapping, self.config.default_role, |mapped| {
+            user.groups
+                .iter()
+                .any(|g| g.eq_ignore_ascii_case(mapped) || group_name(g).eq_ignore_ascii_case(mapped))
+        })
+        .ok_or_else(|| {
+            debug!("LDAP user {} has no mapped group: {:?}", user.username, user.groups);
+            (StatusCode::FORBIDDEN, "Tu cuenta no tiene acceso a esta aplicación".to_string())
+        })?;
+        Ok(Some(LdapIdentity {
+            username: user.username.clone(),
+            groups: user.groups.iter().map(|g| group_name(g).to_string()).collect(),
+            role,
+        }))
+    }
+}
+
+fn unavailable(e: String) -> (StatusCode, String) {
+    error!("LDAP error: {}", e);
+    (StatusCode::SERVICE_UNAVAILABLE, "El directorio LDAP no está disponible".to_string())
+}
+
+// Directorio en memoria para los tests: interpreta solo filtros `(atributo=valor)`
+#[cfg(test)]
+pub mod test_directory {
+    use super::*;
+
+    pub struct TestUser {
+        pub dn: String,
+        pub uid: String,
+        pub password: String,
+        pub groups: Vec<String>,
+    }
+
+    pub struct TestDirectory {
+        pub service: (String, String),
+        pub users: Vec<TestUser>,
+        pub available: bool,
+    }
+
+    impl TestDirectory {
+        pub fn example() -> Self {
+            Self {
+                service: ("cn=svc,dc=example,dc=com".into(), "svc-password".into()),
+                users: vec![
+                    TestUser {
+                        dn: "uid=dave,ou=people,dc=example,dc=com".into(),
+                        uid: "dave".into(),
+                        password: "dave-password".into(),
+                        groups: vec!["cn=registry-editors,ou=groups,dc=example,dc=com".into()],
+                    },
+                    TestUser {
+                        dn: "uid=erin,ou=people,dc=example,dc=com".into(),
+                        uid: "erin".into(),
+                        password: "erin-password".into(),
+                        groups: vec!["cn=staff,ou=groups,dc=example,dc=com".into()],
+                    },
+                ],
+                available: true,
+            }
+        }
+
+        pub fn config() -> LdapConfig {
+            LdapConfig {
+                bind_dn: Some("cn=svc,dc=example,dc=com".into()),
+                bind_password: Some("svc-password".into()),
+                base_dn: "dc=example,dc=com".into(),
+                user_filter: "(uid={username})".into(),
+                username_attribute: "uid".into(),
+                group_attribute: "memberOf".into(),
+                role_mapping: vec![("registry-editors".into(), Role::Editor)],
+                default_role: None,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Directory for TestDirectory {
+        async fn bind(&self, dn: &str, password: &str) -> Result<bool, String> {
+            if !self.available {
+                return Err("connection refused".into());
+            }
+            Ok(self.users.iter().any(|u| u.dn == dn && u.password == password)
+                || (self.service.0 == dn && self.service.1 == password))
+        }
+
+        async fn search(
+            &self,
+            credentials: Option<(&str, &str)>,
+            _base_dn: &str,
+            filter: &str,
+            _username_attribute: &str,
+            _group_attribute: &str,
+        ) -> Result<Vec<DirectoryEntry>, String> {
+            let (dn, password) = credentials.ok_or("anonymous search not allowed")?;
+            if !self.bind(dn, password).await? {
+                return Err("invalid service credentials".into());
+            }
+            let uid = filter
+                .strip_prefix("(uid=")
+                .and_then(|f| f.strip_suffix(')'))
+                .ok_or("unsupported filter")?;
+            Ok(self
+                .users
+                .iter()
+                .filter(|u| u.uid.eq_ignore_ascii_case(uid))
+                .map(|u| DirectoryEntry {
+                    dn: u.dn.clone(),
+                    username: Some(u.uid.clone()),
+                    groups: u.groups.clone(),
+                })
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_directory::TestDirectory;
+
+    fn provider(directory: TestDirectory, default_role: Option<Role>) -> LdapProvider {
+        let mut config = TestDirectory::config();
+        config.default_role = default_role;
+        LdapProvider::new(config, Box::new(directory))
+    }
+
+    async fn login(
+        provider: &LdapProvider,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<LdapIdentity>, (StatusCode, String)> {
+        match provider.find(username).await? {
+            Some(user) => provider.authenticate(&user, password).await,
+            None => Ok(None),
+        }
+    }
+
+    #[test]
+    fn test_group_name() {
+        assert_eq!(group_name("cn=admins,ou=groups,dc=example,dc=com"), "admins");
+        assert_eq!(group_name("admins"), "admins");
+        assert_eq!(group_name("ou=groups,dc=example"), "ou=groups,dc=example");
+    }
+
+    #[tokio::test]
+    async fn test_search_and_bind() {
+        let provider = provider(TestDirectory::example(), None);
+        let identity = login(&provider, "dave", "dave-password").await.unwrap().unwrap();
+        assert_eq!(identity.role, Role::Editor);
+        assert_eq!(identity.groups, vec!["registry-editors"]);
+        // El nombre de la cuenta es el del directorio, no el escrito en el login
+        let identity = login(&provider, "DAVE", "dave-password").await.unwrap().unwrap();
+        assert_eq!(identity.username, "dave");
+
+        assert_eq!(login(&provider, "dave", "wrong").await.unwrap(), None);
+        assert_eq!(login(&provider, "dave", "").await.unwrap(), None);
+        assert_eq!(login(&provider, "nobody", "x").await.unwrap(), None);
+        // Los caracteres especiales se escapan y no alteran el filtro
+        assert_eq!(login(&provider, "*", "x").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_group_mapping_and_default_role() {
+        let without_default = provider(TestDirectory::example(), None);
+        let error = login(&without_default, "erin", "erin-password").await.unwrap_err();
+        assert_eq!(error.0, StatusCode::FORBIDDEN);
+
+        let with_default = provider(TestDirectory::example(), Some(Role::Viewer));
+        let identity = login(&with_default, "erin", "erin-password").await.unwrap().unwrap();
+        assert_eq!(identity.role, Role::Viewer);
+    }
+
+    #[test]
+    fn test_plaintext_urls_need_opt_in() {
+        assert!(Ldap3Directory::new("ldaps://ldap.example.com", false, false).is_ok());
+        assert!(Ldap3Directory::new("ldap://ldap.example.com", true, false).is_ok());
+        assert!(Ldap3Directory::new("ldap://ldap.example.com", false, true).is_ok());
+        assert!(Ldap3Directory::new("ldap://ldap.example.com", false, false).is_err());
+        assert!(Ldap3Directory::new("ldaps://ldap.example.com", true, false).is_err());
+        assert!(Ldap3Directory::new("ldap.example.com", false, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_directory() {
+        let mut directory = TestDirectory::example();
+        directory.available = false;
+        let error = login(&provider(directory, None), "dave", "dave-password").await.unwrap_err();
+        assert_eq!(error.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,188 @@
+use axum::http::HeaderMap;
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// Bloqueo máximo, y tiempo sin fallos tras el que se olvidan los anteriores
+const MAX_LOCKOUT: Duration = Duration::from_secs(3600);
+// Por encima de estas entradas se purgan las que ya no bloquean
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+// Contadores de fallos de login por IP y por usuario con bloqueo exponencial:
+// al llegar al límite se bloquea `base_lockout`, y cada fallo posterior dobla el bloqueo.
+pub struct LoginGuard {
+    by_ip: DashMap<String, Attempts>,
+    by_user: DashMap<String, Attempts>,
+    max_failures_per_user: u32,
+    max_failures_per_ip: u32,
+    base_lockout: Duration,
+    // Tomar la IP de `X-Forwarded-For` (solo detrás de un proxy de confianza)
+    trust_forwarded_for: bool,
+}
+
+fn remaining(attempts: &Attempts, now: Instant) -> Option<Duration> {
+    attempts
+        .locked_until
+        .filter(|until| *until > now)
+        .map(|until| until - now)
+}
+
+impl LoginGuard {
+    pub fn new(max_failures_per_user: u32, max_failures_per_ip: u32, base_lockout: Duration) -> Self {
+        Self {
+            by_ip: DashMap::new(),
+            by_user: DashMap::new(),
+            max_failures_per_user,
+            max_failures_per_ip,
+            base_lockout,
+            trust_forwarded_for: false,
+        }
+    }
+
+    pub fn with_forwarded_for(mut self, trust_forwarded_for: bool) -> Self {
+        self.trust_forwarded_for = trust_forwarded_for;
+        self
+    }
+
+    // IP del cliente: la primera de `X-Forwarded-For` si se confía en el proxy,
+    // si no la de la conexión
+    pub fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+        let forwarded = self
+            .trust_forwarded_for
+            .then(|| headers.get("x-forwarded-for")?.to_str().ok()?.split(',').next())
+            .flatten()
+            .map(str::trim)
+            .filter(|ip| !ip.is_empty());
+        match (forwarded, peer) {
+            (Some(ip), _) => ip.to_string(),
+            (None, Some(peer)) => peer.ip().to_string(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+
+    // `Err` con el tiempo que falta si la IP o el usuario están bloqueados
+    pub fn check(&self, ip: &str, username: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let locked = [self.by_ip.get(ip), self.by_user.get(username)]
+            .into_iter()
+            .flatten()
+            .filter_map(|attempts| remaining(&attempts, now))
+            .max();
+        match locked {
+            Some(duration) => Err(duration),
+            None => Ok(()),
+        }
+    }
+
+    // Registra un fallo y devuelve el bloqueo aplicado, si lo hay
+    pub fn record_failure(&self, ip: &str, username: &str) -> Option<Duration> {
+        let now = Instant::now();
+        if self.by_ip.len() + self.by_user.len() > MAX_TRACKED {
+            self.purge(now);
+        }
+        let by_ip = Self::fail(&self.by_ip, ip, self.max_failures_per_ip, self.base_lockout, now);
+        let by_user = Self::fail(&self.by_user, username, self.max_failures_per_user, self.base_lockout, now);
+        by_ip.max(by_user)
+    }
+
+    pub fn record_success(&self, ip: &str, username: &str) {
+        self.by_ip.remove(ip);
+        self.by_user.remove(username);
+    }
+
+    fn fail(
+        map: &DashMap<String, Attempts>,
+        key: &str,
+        max_failures: u32,
+        base_lockout: Duration,
+        now: Instant,
+    ) -> Option<Duration> {
+        let mut attempts = map.entry(key.to_string()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        if now.duration_since(attempts.last_failure) > MAX_LOCKOUT {
+            attempts.failures = 0;
+        }
+        attempts.failures += 1;
+        attempts.last_failure = now;
+        if attempts.failures < max_failures {
+            return None;
+        }
+        let exponent = (attempts.failures - max_failures).min(16);
+        let lockout = base_lockout.saturating_mul(1 << exponent).min(MAX_LOCKOUT);
+        attempts.locked_until = Some(now + lockout);
+        Some(lockout)
+    }
+
+    fn purge(&self, now: Instant) {
+        let stale = |_: &String, attempts: &mut Attempts| {
+            remaining(attempts, now).is_some() || now.duration_since(attempts.last_failure) < MAX_LOCKOUT
+        };
+        self.by_ip.retain(stale);
+        self.by_user.retain(stale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_lockout_per_user() {
+        let guard = LoginGuard::new(3, 100, Duration::from_secs(30));
+        assert_eq!(guard.record_failure("1.1.1.1", "admin"), None);
+        assert_eq!(guard.record_failure("2.2.2.2", "admin"), None);
+        assert!(guard.check("3.3.3.3", "admin").is_ok());
+
+        assert_eq!(guard.record_failure("3.3.3.3", "admin"), Some(Duration::from_secs(30)));
+        assert!(guard.check("4.4.4.4", "admin").is_err());
+        assert_eq!(guard.record_failure("3.3.3.3", "admin"), Some(Duration::from_secs(60)));
+        assert_eq!(guard.record_failure("3.3.3.3", "admin"), Some(Duration::from_secs(120)));
+        // Otros usuarios desde otra IP no se ven afectados
+        assert!(guard.check("4.4.4.4", "viewer").is_ok());
+
+        guard.record_success("3.3.3.3", "admin");
+        assert!(guard.check("3.3.3.3", "admin").is_ok());
+    }
+
+    #[test]
+    fn test_lockout_per_ip_across_usernames() {
+        let guard = LoginGuard::new(100, 2, Duration::from_secs(10));
+        guard.record_failure("1.1.1.1", "a");
+        guard.record_failure("1.1.1.1", "b");
+        let remaining = guard.check("1.1.1.1", "c").unwrap_err();
+        assert!(remaining <= Duration::from_secs(10));
+        assert!(guard.check("2.2.2.2", "c").is_ok());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 192.168.1.1".parse().unwrap());
+        let peer = Some("127.0.0.1:4000".parse().unwrap());
+
+        let direct = LoginGuard::new(5, 20, Duration::from_secs(30));
+        assert_eq!(direct.client_ip(&headers, peer), "127.0.0.1");
+        let proxied = LoginGuard::new(5, 20, Duration::from_secs(30)).with_forwarded_for(true);
+        assert_eq!(proxied.client_ip(&headers, peer), "10.0.0.1");
+        assert_eq!(proxied.client_ip(&HeaderMap::new(), None), "unknown");
+    }
+
+    #[test]
+    fn test_lockout_is_capped() {
+        let guard = LoginGuard::new(1, 100, Duration::from_secs(600));
+        for _ in 0..10 {
+            guard.record_failure("1.1.1.1", "admin");
+        }
+        assert_eq!(guard.record_failure("1.1.1.1", "admin"), Some(MAX_LOCKOUT));
+    }
+}
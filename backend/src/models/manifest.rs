@@ -0,0 +1,116 @@
+use serde::Serialize;
+use serde_json::Value;
+use super::manifest_list::ManifestList;
+use super::manifest_v2::ManifestV2;
+
+pub const DOCKER_MANIFEST_V2: &str = "application/vnd.docker.distribution.manifest.v2+json";
+pub const DOCKER_MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+pub const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+pub const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+
+// Valor de la cabecera Accept con todos los formatos que sabemos interpretar
+pub const ACCEPT_MANIFESTS: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.oci.image.index.v1+json";
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum Manifest {
+    DockerV2(ManifestV2),
+    OciImage(ManifestV2),
+    DockerList(ManifestList),
+    OciIndex(ManifestList),
+}
+
+impl Manifest {
+    // El tipo se decide por la cabecera Content-Type; si falta o es genérica
+    // se usa el campo `mediaType` del cuerpo y, en último caso, su forma.
+    pub fn parse(content_type: Option<&str>, body: &[u8]) -> Result<Self, String> {
+        let value: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+        let header_type = content_type
+            .map(|c| c.split(';').next().unwrap_or_default().trim())
+            .filter(|c| Self::is_manifest_type(c));
+        let media_type = header_type
+            .or_else(|| value.get("mediaType").and_then(|m| m.as_str()))
+            .unwrap_or(if value.get("manifests").is_some() { OCI_INDEX } else { OCI_MANIFEST })
+            .to_string();
+
+        let parsed = match media_type.as_str() {
+            DOCKER_MANIFEST_V2 => serde_json::from_value(value).map(Manifest::DockerV2),
+            OCI_MANIFEST => serde_json::from_value(value).map(Manifest::OciImage),
+            DOCKER_MANIFEST_LIST => serde_json::from_value(value).map(Manifest::DockerList),
+            OCI_INDEX => serde_json::from_value(value).map(Manifest::OciIndex),
+            other => return Err(format!("Tipo de manifiesto no soportado: {}", other)),
+        };
+        parsed.map_err(|e| e.to_string())
+    }
+
+    pub fn is_manifest_type(media_type: &str) -> bool {
+        matches!(
+            media_type,
+            DOCKER_MANIFEST_V2 | DOCKER_MANIFEST_LIST | OCI_MANIFEST | OCI_INDEX
+        )
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Manifest::DockerV2(_) => DOCKER_MANIFEST_V2,
+            Manifest::OciImage(_) => OCI_MANIFEST,
+            Manifest::DockerList(_) => DOCKER_MANIFEST_LIST,
+            Manifest::OciIndex(_) => OCI_INDEX,
+        }
+    }
+
+    pub fn as_image(&self) -> Option<&ManifestV2> {
+        match self {
+            Manifest::DockerV2(m) | Manifest::OciImage(m) => Some(m),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OCI_IMAGE_BODY: &str = r#"{"schemaVersion":2,"config":{"mediaType":"application/vnd.oci.image.config.v1+json","size":10,"digest":"sha256:cfg"},"layers":[{"mediaType":"application/vnd.oci.image.layer.v1.tar+gzip","size":90,"digest":"sha256:l1"}]}"#;
+    const INDEX_BODY: &str = r#"{"schemaVersion":2,"manifests":[{"mediaType":"application/vnd.oci.image.manifest.v1+json","size":500,"digest":"sha256:arm","platform":{"architecture":"arm64","os":"linux","variant":"v8"}},{"mediaType":"application/vnd.oci.image.manifest.v1+json","size":400,"digest":"sha256:amd","platform":{"architecture":"amd64","os":"linux"}},{"mediaType":"application/vnd.oci.image.manifest.v1+json","size":100,"digest":"sha256:att","platform":{"architecture":"unknown","os":"unknown"}}]}"#;
+
+    #[test]
+    fn test_parse_oci_manifest_from_content_type() {
+        let manifest = Manifest::parse(
+            Some("application/vnd.oci.image.manifest.v1+json; charset=utf-8"),
+            OCI_IMAGE_BODY.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(manifest.media_type(), OCI_MANIFEST);
+        assert_eq!(manifest.as_image().unwrap().size_bytes(), 100);
+    }
+
+    #[test]
+    fn test_parse_docker_manifest_from_body() {
+        let body = OCI_IMAGE_BODY.replacen(
+            "{\"schemaVersion\":2,",
+            "{\"schemaVersion\":2,\"mediaType\":\"application/vnd.docker.distribution.manifest.v2+json\",",
+            1,
+        );
+        let manifest = Manifest::parse(Some("application/json"), body.as_bytes()).unwrap();
+        assert!(matches!(manifest, Manifest::DockerV2(_)));
+    }
+
+    #[test]
+    fn test_parse_oci_index_without_media_type() {
+        let manifest = Manifest::parse(None, INDEX_BODY.as_bytes()).unwrap();
+        assert_eq!(manifest.media_type(), OCI_INDEX);
+        let Manifest::OciIndex(list) = manifest else { panic!("expected an OCI index") };
+        assert_eq!(list.images().count(), 2);
+        assert_eq!(list.preferred().unwrap().digest, "sha256:amd");
+    }
+
+    #[test]
+    fn test_parse_unsupported_media_type() {
+        let body = r#"{"schemaVersion":1,"mediaType":"application/vnd.docker.distribution.manifest.v1+prettyjws"}"#;
+        assert!(Manifest::parse(None, body.as_bytes()).is_err());
+    }
+}
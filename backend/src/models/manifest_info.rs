@@ -1,8 +1,25 @@
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
+use super::image_config::ImageConfig;
+use super::manifest::Manifest;
+use super::tag_detail::PlatformDetail;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Serialize)]
 pub struct ManifestInfo {
     pub name: String,
-    pub tag: String,
+    pub reference: String,
     pub digest: String,
+    pub media_type: String,
+    pub size_bytes: u64,
+    pub manifest: Manifest,
+    pub config: Option<ImageConfig>,
+    pub layers: Vec<LayerInfo>,
+    pub platforms: Vec<PlatformDetail>,
+}
+
+#[derive(Serialize)]
+pub struct LayerInfo {
+    pub digest: String,
+    pub media_type: String,
+    pub size: u64,
+    pub created_by: Option<String>,
 }
@@ -0,0 +1,52 @@
+use serde::{Serialize, Deserialize};
+
+// Docker manifest list / OCI image index: un manifiesto por plataforma
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ManifestList {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: i32,
+    #[serde(rename = "mediaType", default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub manifests: Vec<ManifestDescriptor>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestDescriptor {
+    #[serde(rename = "mediaType")]
+    pub media_type: String,
+    pub size: u64,
+    pub digest: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+impl ManifestList {
+    // Manifiestos de imagen reales; descarta las atestaciones de buildkit
+    // (plataforma "unknown/unknown") que no son ejecutables.
+    pub fn images(&self) -> impl Iterator<Item = &ManifestDescriptor> {
+        self.manifests.iter().filter(|m| {
+            m.platform
+                .as_ref()
+                .is_none_or(|p| p.os != "unknown" && p.architecture != "unknown")
+        })
+    }
+
+    // Manifiesto que usamos como representante del índice (linux/amd64 si existe)
+    pub fn preferred(&self) -> Option<&ManifestDescriptor> {
+        self.images()
+            .find(|m| {
+                m.platform
+                    .as_ref()
+                    .is_some_and(|p| p.os == "linux" && p.architecture == "amd64")
+            })
+            .or_else(|| self.images().next())
+    }
+}
@@ -2,12 +2,20 @@ use serde::{Serialize, Deserialize};
 use super::layer_descriptor::LayerDescriptor;
 use super::config_descriptor::ConfigDescriptor;
 
+// Manifiesto de una imagen concreta. Sirve tanto para el formato Docker V2
+// como para el OCI image manifest, que en OCI puede omitir `mediaType`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ManifestV2 {
     #[serde(rename = "schemaVersion")]
     pub schema_version: i32,
-    #[serde(rename = "mediaType")]
-    pub media_type: String,
+    #[serde(rename = "mediaType", default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
     pub config: ConfigDescriptor,
     pub layers: Vec<LayerDescriptor>,
 }
+
+impl ManifestV2 {
+    pub fn size_bytes(&self) -> u64 {
+        self.layers.iter().map(|l| l.size).sum::<u64>() + self.config.size
+    }
+}
@@ -1,25 +1,76 @@
+#[allow(dead_code)]
 mod data;
 mod response;
 mod paginable;
 mod user;
+mod user_store;
+mod session_store;
+mod login_guard;
+mod api_token_store;
+mod totp;
+mod audit_log;
 mod token_claims;
 mod catalog;
 mod tag_list;
 mod manifest_info;
 mod registry_client;
+mod registries;
+mod image_copy;
+mod registry_token;
+mod repository_cache;
+mod registry_event;
+mod activity_feed;
+mod retention;
+mod acl;
+mod oidc;
+mod ldap;
+mod jobs;
 mod repository_info;
 mod manifest_v2;
+mod manifest_list;
+mod manifest;
 mod config_descriptor;
 mod layer_descriptor;
+mod image_config;
 mod tag_detail;
+mod sort_order;
+mod catalog_query;
+mod tag_query;
+mod glob;
 
 pub type Error = Box<dyn std::error::Error>;
 pub use paginable::Paginable;
 pub use registry_client::RegistryClient;
+pub use registries::{Registries, RegistryConfig};
+pub use image_copy::{CopyProgress, CopyRequest, CopyTracker, check_copy_target, copy_image};
 pub use token_claims::TokenClaims;
+pub use catalog_query::CatalogQuery;
+pub use tag_query::TagQuery;
+pub use registry_event::EventEnvelope;
+pub use activity_feed::ActivityFeed;
+pub use jobs::{JobScheduler, JobTrigger, run_retention_job, run_scheduler};
+pub use oidc::{OIDC_PROVIDER, OidcConfig, OidcProvider, PENDING_LOGIN_TTL};
+pub use ldap::{LDAP_PROVIDER, Ldap3Directory, LdapConfig, LdapProvider, LdapUser};
+#[cfg(test)]
+pub use oidc::mock as oidc_mock;
+#[cfg(test)]
+pub use ldap::test_directory;
+pub use acl::{AclRule, AclStore, Permission};
+pub use retention::{RetentionPlan, RetentionPolicy, RetentionStore, apply_retention, plan_hash, plan_retention};
 
 pub use user::User;
+pub use session_store::SessionStore;
+pub use login_guard::LoginGuard;
+pub use totp::{TotpProvider, TotpSettings};
+pub use audit_log::{AuditAction, AuditEntry, AuditLog, AuditQuery};
+#[cfg(test)]
+pub use audit_log::AuditOutcome;
+#[cfg(test)]
+pub use totp::current_code as totp_code;
+pub use api_token_store::{API_TOKEN_PREFIX, ApiTokenStore, ApiTokenView};
+pub use user_store::{Account, AccountView, Role, UserStore, parse_role, parse_role_mapping, verify_password};
 
+#[allow(unused_imports)]
 pub use response::{
     ApiResponse,
     CustomResponse,
@@ -30,7 +81,67 @@ pub use response::{
 
 pub struct AppState {
     pub secret: String,
+    #[allow(dead_code)]
     pub static_dir: String,
-    pub user: User,
-    pub registry_client: RegistryClient,
+    pub users: UserStore,
+    pub registries: Registries,
+    pub events_secret: Option<String>,
+    pub activity: ActivityFeed,
+    pub retention: RetentionStore,
+    pub jobs: JobScheduler,
+    pub copies: CopyTracker,
+    pub acl: AclStore,
+    pub oidc: Option<OidcProvider>,
+    pub ldap: Option<LdapProvider>,
+    pub sessions: SessionStore,
+    pub login_guard: LoginGuard,
+    pub api_tokens: ApiTokenStore,
+    pub totp: TotpProvider,
+    pub audit: AuditLog,
+}
+
+#[cfg(test)]
+impl AppState {
+    // Estado mínimo para los tests de los handlers
+    pub fn for_tests() -> Self {
+        Self {
+            secret: "test-secret".to_string(),
+            static_dir: "static".to_string(),
+            users: test_users(),
+            registries: Registries::new(
+                "default",
+                // Un puerto sin nadie escuchando: ningún test depende de un Registry local
+                RegistryClient::new("http://127.0.0.1:1".to_string(), String::new()),
+            )
+            .unwrap(),
+            events_secret: Some("events-secret".to_string()),
+            activity: ActivityFeed::new(10),
+            retention: RetentionStore::load(None).unwrap(),
+            jobs: JobScheduler::new(None, false, 10).unwrap(),
+            copies: CopyTracker::new(10),
+            acl: AclStore::load(None).unwrap(),
+            oidc: None,
+            ldap: None,
+            sessions: SessionStore::new(chrono::Duration::minutes(15), chrono::Duration::days(7), true),
+            login_guard: LoginGuard::new(3, 10, std::time::Duration::from_secs(30)),
+            api_tokens: ApiTokenStore::load(None).unwrap(),
+            totp: TotpProvider::new("registryui"),
+            audit: AuditLog::load(None, 1000),
+        }
+    }
+}
+
+// admin, editor y viewer con sus roles, y una cuenta deshabilitada.
+// La contraseña de todos es `password` (hash con coste mínimo para no ralentizar los tests).
+#[cfg(test)]
+fn test_users() -> UserStore {
+    let hashed = bcrypt::hash("password", 4).unwrap();
+    let users = UserStore::load(None).unwrap();
+    users.insert(Account::new("admin", &hashed, Role::Admin));
+    users.insert(Account::new("editor", &hashed, Role::Editor));
+    users.insert(Account::new("viewer", &hashed, Role::Viewer));
+    let mut disabled = Account::new("disabled", &hashed, Role::Admin);
+    disabled.disabled = true;
+    users.insert(disabled);
+    users
 }
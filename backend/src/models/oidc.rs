@@ -0,0 +1,537 @@
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dashmap::DashMap;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+use tracing::{debug, error};
+use super::user_store::{Role, mapped_role};
+
+// Tiempo que tiene el usuario para volver del proveedor con el código
+pub const PENDING_LOGIN_TTL: Duration = Duration::from_secs(600);
+// Logins a medias que se guardan como mucho; al llenarse se descartan los más antiguos
+const MAX_PENDING_LOGINS: usize = 1000;
+// Una clave desconocida vuelve a pedir el JWKS (rotación), pero no más de una vez por minuto
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const OIDC_PROVIDER: &str = "oidc";
+
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    pub username_claim: String,
+    pub groups_claim: String,
+    // Grupo del proveedor → rol; gana el rol más alto de los grupos del usuario
+    pub role_mapping: Vec<(String, Role)>,
+    // Rol si ningún grupo casa; sin él, esos usuarios no pueden entrar
+    pub default_role: Option<Role>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    created_at: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OidcIdentity {
+    // `iss` y `sub`: lo único estable del usuario en el proveedor
+    pub subject: String,
+    pub username: String,
+    pub groups: Vec<String>,
+    pub role: Role,
+}
+
+// Cliente del flujo authorization code + PKCE (S256)
+pub struct OidcProvider {
+    config: OidcConfig,
+    client: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+    pending: DashMap<String, PendingLogin>,
+}
+
+fn random_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+fn bad_gateway(message: String) -> (StatusCode, String) {
+    error!("{}", message);
+    (StatusCode::BAD_GATEWAY, message)
+}
+
+// Algoritmo con el que se valida una firma: el de la clave, nunca el que diga
+// la cabecera del token, y solo asimétricos (con HS* la clave pública haría de secreto)
+fn key_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    let algorithm = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+        (Some(algorithm), _) => Algorithm::from_str(&algorithm.to_string()).ok()?,
+        (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+        (None, AlgorithmParameters::EllipticCurve(params)) => match params.curve {
+            EllipticCurve::P256 => Algorithm::ES256,
+            EllipticCurve::P384 => Algorithm::ES384,
+            _ => return None,
+        },
+        (None, AlgorithmParameters::OctetKeyPair(_)) => Algorithm::EdDSA,
+        (None, AlgorithmParameters::OctetKey(_)) => return None,
+    };
+    matches!(
+        algorithm,
+        Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512
+            | Algorithm::ES256
+            | Algorithm::ES384
+            | Algorithm::EdDSA
+    )
+    .then_some(algorithm)
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(None),
+            pending: DashMap::new(),
+        }
+    }
+
+    // El documento de descubrimiento se pide en el primer login, no al arrancar
+    async fn metadata(&self) -> Result<&ProviderMetadata, (StatusCode, String)> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                debug!("OIDC discovery: {}", url);
+                self.client
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .map_err(|e| bad_gateway(format!("No se pudo consultar {}: {}", url, e)))?
+                    .json::<ProviderMetadata>()
+                    .await
+                    .map_err(|e| bad_gateway(format!("Descubrimiento OIDC no válido: {}", e)))
+            })
+            .await
+    }
+
+    // URL del proveedor a la que redirigir al usuario para iniciar sesión y el
+    // `state`, que el handler liga además al navegador con una cookie
+    pub async fn authorization_url(&self) -> Result<(String, String), (StatusCode, String)> {
+        let metadata = self.metadata().await?;
+        self.pending
+            .retain(|_, pending| pending.created_at.elapsed() < PENDING_LOGIN_TTL);
+        // Quien pida logins sin terminarlos no puede hacer crecer el mapa sin límite
+        while self.pending.len() >= MAX_PENDING_LOGINS {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|entry| entry.created_at)
+                .map(|entry| entry.key().clone());
+            match oldest {
+                Some(state) => self.pending.remove(&state),
+                None => break,
+            };
+        }
+
+        let state = random_token();
+        let pending = PendingLogin {
+            code_verifier: random_token(),
+            nonce: random_token(),
+            created_at: Instant::now(),
+        };
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| bad_gateway(format!("authorization_endpoint no válido: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &pending.nonce)
+            .append_pair("code_challenge", &code_challenge(&pending.code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        self.pending.insert(state.clone(), pending);
+        Ok((url.to_string(), state))
+    }
+
+    // Canjea el código del callback y valida el ID token
+    pub async fn exchange(&self, code: &str, state: &str) -> Result<OidcIdentity, (StatusCode, String)> {
+        let (_, pending) = self
+            .pending
+            .remove(state)
+            .filter(|(_, p)| p.created_at.elapsed() < PENDING_LOGIN_TTL)
+            .ok_or((StatusCode::BAD_REQUEST, "Estado OIDC desconocido o caducado".to_string()))?;
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", &pending.code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let response = self
+            .client
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| bad_gateway(format!("No se pudo contactar con el token endpoint: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            error!("OIDC token endpoint returned {}: {}", status, body);
+            return Err((StatusCode::UNAUTHORIZED, "El proveedor OIDC rechazó el código".to_string()));
+        }
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| bad_gateway(format!("Respuesta del token endpoint no válida: {}", e)))?;
+
+        let claims = self.validate_id_token(metadata, &tokens.id_token).await?;
+        if claims.get("nonce").and_then(Value::as_str) != Some(pending.nonce.as_str()) {
+            return Err((StatusCode::UNAUTHORIZED, "El nonce del ID token no coincide".to_string()));
+        }
+        self.identity(&claims)
+    }
+
+    async fn validate_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<Map<String, Value>, (StatusCode, String)> {
+        let invalid = |e: jsonwebtoken::errors::Error| {
+            error!("Invalid ID token: {}", e);
+            (StatusCode::UNAUTHORIZED, "ID token no válido".to_string())
+        };
+        let header = decode_header(id_token).map_err(invalid)?;
+        let jwk = self.jwk(metadata, header.kid.as_deref()).await?;
+        let algorithm = key_algorithm(&jwk)
+            .ok_or((StatusCode::UNAUTHORIZED, "Algoritmo de la clave del ID token no admitido".to_string()))?;
+
+        // Un token firmado con otro algoritmo que el de la clave no pasa la validación
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        let key = DecodingKey::from_jwk(&jwk).map_err(invalid)?;
+        Ok(decode::<Map<String, Value>>(id_token, &key, &validation)
+            .map_err(invalid)?
+            .claims)
+    }
+
+    // Clave del JWKS, que se guarda como el descubrimiento. Solo se vuelve a pedir
+    // si la clave no está (el proveedor ha rotado las suyas).
+    async fn jwk(&self, metadata: &ProviderMetadata, kid: Option<&str>) -> Result<Jwk, (StatusCode, String)> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+        let stale = {
+            let cached = self.jwks.read().unwrap();
+            if let Some(jwk) = cached.as_ref().and_then(|(jwks, _)| find(jwks)) {
+                return Ok(jwk);
+            }
+            cached.as_ref().is_none_or(|(_, fetched_at)| fetched_at.elapsed() >= JWKS_REFRESH_INTERVAL)
+        };
+        if !stale {
+            return Err((StatusCode::UNAUTHORIZED, "Clave del ID token desconocida".to_string()));
+        }
+
+        debug!("OIDC JWKS: {}", metadata.jwks_uri);
+        let jwks: JwkSet = self
+            .client
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| bad_gateway(format!("No se pudo obtener el JWKS: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| bad_gateway(format!("JWKS no válido: {}", e)))?;
+        let jwk = find(&jwks);
+        *self.jwks.write().unwrap() = Some((jwks, Instant::now()));
+        jwk.ok_or((StatusCode::UNAUTHORIZED, "Clave del ID token desconocida".to_string()))
+    }
+
+    fn identity(&self, claims: &Map<String, Value>) -> Result<OidcIdentity, (StatusCode, String)> {
+        let claim = |name: &str| claims.get(name).and_then(Value::as_str).filter(|v| !v.is_empty());
+        let (Some(issuer), Some(sub)) = (claim("iss"), claim("sub")) else {
+            return Err((StatusCode::UNAUTHORIZED, "El ID token no tiene iss o sub".to_string()));
+        };
+        let subject = format!("{} {}", issuer, sub);
+        let username = claims
+            .get(&self.config.username_claim)
+            .or_else(|| claims.get("sub"))
+            .and_then(Value::as_str)
+            .filter(|u| !u.is_empty())
+            .ok_or((StatusCode::UNAUTHORIZED, "El ID token no identifica al usuario".to_string()))?
+            .to_string();
+        let groups: Vec<String> = match claims.get(&self.config.groups_claim) {
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).map(String::from).collect(),
+            Some(Value::String(group)) => vec![group.clone()],
+            _ => Vec::new(),
+        };
+        let role = mapped_role(&self.config.role_mapping, self.config.default_role, |group| {
+            groups.iter().any(|g| g == group)
+        })
+        .ok_or_else(|| {
+                debug!("OIDC user {} has no mapped group: {:?}", username, groups);
+                (StatusCode::FORBIDDEN, "Tu cuenta no tiene acceso a esta aplicación".to_string())
+            })?;
+        Ok(OidcIdentity { subject, username, groups, role })
+    }
+}
+
+// Proveedor de identidad de pruebas: descubrimiento, JWKS y token endpoint
+// firmando ID tokens RS256 con una clave generada al vuelo.
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use axum::{extract::State, routing, Form, Json, Router};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use openssl::rsa::Rsa;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use crate::models::user_store::parse_role_mapping;
+
+    pub struct MockIdp {
+        pub issuer: String,
+        // `nonce` y `code_challenge` que el test pasa al IdP como si vinieran del navegador
+        pub authorization: Arc<Mutex<Option<(String, String)>>>,
+        pub algorithm: Arc<Mutex<jsonwebtoken::Algorithm>>,
+        pub jwks_requests: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[derive(Clone)]
+    struct IdpState {
+        issuer: String,
+        key: EncodingKey,
+        jwk: Value,
+        // Claims de los ID tokens (se completan iss, aud, exp y nonce)
+        claims: Value,
+        authorization: Arc<Mutex<Option<(String, String)>>>,
+        // Algoritmo con el que se firman los ID tokens
+        algorithm: Arc<Mutex<jsonwebtoken::Algorithm>>,
+        jwks_requests: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    pub async fn spawn(claims: Value) -> MockIdp {
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+        let jwk = json!({
+            "kty": "RSA",
+            "kid": "test-key",
+            "alg": "RS256",
+            "use": "sig",
+            "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+            "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let state = IdpState {
+            issuer: issuer.clone(),
+            key,
+            jwk,
+            claims,
+            authorization: Arc::new(Mutex::new(None)),
+            algorithm: Arc::new(Mutex::new(jsonwebtoken::Algorithm::RS256)),
+            jwks_requests: Default::default(),
+        };
+        let idp = MockIdp {
+            issuer,
+            authorization: state.authorization.clone(),
+            algorithm: state.algorithm.clone(),
+            jwks_requests: state.jwks_requests.clone(),
+        };
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", routing::get(discovery))
+            .route(
+                "/jwks",
+                routing::get(|State(s): State<IdpState>| async move {
+                    s.jwks_requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Json(json!({"keys": [s.jwk]}))
+                }),
+            )
+            .route("/token", routing::post(token))
+            .with_state(state);
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        idp
+    }
+
+    async fn discovery(State(state): State<IdpState>) -> Json<Value> {
+        Json(json!({
+            "issuer": state.issuer,
+            "authorization_endpoint": format!("{}/authorize", state.issuer),
+            "token_endpoint": format!("{}/token", state.issuer),
+            "jwks_uri": format!("{}/jwks", state.issuer),
+        }))
+    }
+
+    async fn token(
+        State(state): State<IdpState>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, StatusCode> {
+        let (nonce, challenge) = state.authorization.lock().unwrap().clone().ok_or(StatusCode::BAD_REQUEST)?;
+        let verifier = form.get("code_verifier").ok_or(StatusCode::BAD_REQUEST)?;
+        if form.get("code").map(String::as_str) != Some("good-code") || code_challenge(verifier) != challenge {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let mut claims = state.claims.clone();
+        let client_id = form.get("client_id").cloned().unwrap_or_default();
+        claims["iss"] = json!(state.issuer);
+        claims["aud"] = json!(client_id);
+        claims["exp"] = json!(chrono::Utc::now().timestamp() + 300);
+        claims["nonce"] = json!(nonce);
+        let algorithm = *state.algorithm.lock().unwrap();
+        let mut header = Header::new(algorithm);
+        header.kid = Some("test-key".to_string());
+        // Con HS256 el atacante firma usando la clave pública como secreto
+        let key = match algorithm {
+            jsonwebtoken::Algorithm::HS256 => EncodingKey::from_secret(state.jwk["n"].as_str().unwrap().as_bytes()),
+            _ => state.key.clone(),
+        };
+        let id_token = encode(&header, &claims, &key).unwrap();
+        Ok(Json(json!({"access_token": "access", "token_type": "Bearer", "id_token": id_token})))
+    }
+
+    impl MockIdp {
+        pub fn config(&self) -> OidcConfig {
+            OidcConfig {
+                issuer: self.issuer.clone(),
+                client_id: "registryui".to_string(),
+                client_secret: Some("client-secret".to_string()),
+                redirect_url: "http://localhost:3000/api/v1/auth/oidc/callback".to_string(),
+                scopes: "openid profile groups".to_string(),
+                username_claim: "preferred_username".to_string(),
+                groups_claim: "groups".to_string(),
+                role_mapping: parse_role_mapping("registry-admins=admin,registry-editors=editor").unwrap(),
+                default_role: None,
+            }
+        }
+
+        // Simula el paso por la página de login del IdP: devuelve el `state`
+        pub fn authorize(&self, authorization_url: &str) -> String {
+            let url = Url::parse(authorization_url).unwrap();
+            let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            *self.authorization.lock().unwrap() =
+                Some((params["nonce"].clone(), params["code_challenge"].clone()));
+            params["state"].clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_code_challenge_s256() {
+        // Ejemplo del RFC 7636, apéndice B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_authorization_code_flow_with_pkce() {
+        let idp = mock::spawn(json!({
+            "sub": "1234",
+            "preferred_username": "alice",
+            "groups": ["registry-editors", "everyone"],
+        }))
+        .await;
+        let provider = OidcProvider::new(idp.config());
+
+        let (url, _) = provider.authorization_url().await.unwrap();
+        assert!(url.starts_with(&format!("{}/authorize?", idp.issuer)));
+        assert!(url.contains("code_challenge_method=S256"));
+        let state = idp.authorize(&url);
+
+        assert_eq!(
+            provider.exchange("bad-code", "unknown-state").await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        let identity = provider.exchange("good-code", &state).await.unwrap();
+        assert_eq!(identity.username, "alice");
+        assert_eq!(identity.subject, format!("{} 1234", idp.issuer));
+        assert_eq!(identity.role, Role::Editor);
+        // El state solo se puede usar una vez
+        assert!(provider.exchange("good-code", &state).await.is_err());
+
+        // El JWKS se pidió una sola vez
+        let state = idp.authorize(&provider.authorization_url().await.unwrap().0);
+        provider.exchange("good-code", &state).await.unwrap();
+        assert_eq!(idp.jwks_requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_algorithm_comes_from_the_key() {
+        let idp = mock::spawn(json!({"sub": "1234", "groups": ["registry-admins"]})).await;
+        let provider = OidcProvider::new(idp.config());
+        *idp.algorithm.lock().unwrap() = jsonwebtoken::Algorithm::HS256;
+        let state = idp.authorize(&provider.authorization_url().await.unwrap().0);
+        assert_eq!(provider.exchange("good-code", &state).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_pending_logins_are_capped() {
+        let idp = mock::spawn(json!({"sub": "1234"})).await;
+        let provider = OidcProvider::new(idp.config());
+        let (_, first) = provider.authorization_url().await.unwrap();
+        for _ in 0..MAX_PENDING_LOGINS {
+            provider.authorization_url().await.unwrap();
+        }
+        assert_eq!(provider.pending.len(), MAX_PENDING_LOGINS);
+        assert!(!provider.pending.contains_key(&first));
+    }
+
+    #[tokio::test]
+    async fn test_unmapped_groups_are_rejected() {
+        let idp = mock::spawn(json!({"sub": "bob", "groups": ["everyone"]})).await;
+        let provider = OidcProvider::new(idp.config());
+        let state = idp.authorize(&provider.authorization_url().await.unwrap().0);
+        let error = provider.exchange("good-code", &state).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::FORBIDDEN);
+    }
+}
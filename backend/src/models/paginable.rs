@@ -17,6 +17,55 @@ pub trait Paginable {
     fn offset(&self) -> i64 {
         (self.page_or_default() - 1) * self.limit_or_default()
     }
+
+    // Página solicitada dentro de una lista ya filtrada y ordenada
+    fn page_of<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset().max(0) as usize)
+            .take(self.limit_or_default().max(0) as usize)
+            .collect()
+    }
+}
+
+// Parámetros que conservan los enlaces prev/next (orden, filtros...)
+#[derive(Default)]
+pub struct QueryParams(Vec<String>);
+
+impl QueryParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(mut self, name: &str, value: &str) -> Self {
+        self.0.push(format!("{}={}", name, url_encode(value)));
+        self
+    }
+
+    // Los filtros vacíos no se incluyen
+    pub fn add_opt(self, name: &str, value: Option<&str>) -> Self {
+        match value.filter(|v| !v.is_empty()) {
+            Some(value) => self.add(name, value),
+            None => self,
+        }
+    }
+
+    pub fn path(&self, path: &str) -> String {
+        match self.0.is_empty() {
+            true => path.to_string(),
+            false => format!("{}?{}", path, self.0.join("&")),
+        }
+    }
+}
+
+pub fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -53,5 +102,18 @@ mod tests {
         assert_eq!(params.limit_or_default(), 10);
         assert_eq!(params.offset(), 10);
     }
+
+    #[test]
+    fn test_page_of() {
+        let params = TestParams { page: Some(2), limit: Some(2) };
+        assert_eq!(params.page_of(vec![1, 2, 3, 4, 5]), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_query_params() {
+        let params = QueryParams::new().add("sort", "name").add_opt("q", Some("my app")).add_opt("regex", Some(""));
+        assert_eq!(params.path("/api/v1/registry"), "/api/v1/registry?sort=name&q=my%20app");
+        assert_eq!(QueryParams::new().add_opt("q", None).path("/api/v1/audit"), "/api/v1/audit");
+    }
 }
 
@@ -0,0 +1,234 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::warn;
+
+use crate::constants::REGISTRY_CONCURRENCY;
+use super::Paginable;
+use super::catalog_query::CatalogQuery;
+use super::registry_client::RegistryClient;
+use super::repository_info::RepositoryInfo;
+use super::response::{PagedResponse, Pagination};
+
+// Un Registry adicional de `REGISTRIES_FILE`
+#[derive(Deserialize, Debug)]
+pub struct RegistryConfig {
+    pub name: String,
+    pub url: String,
+    // Credenciales `usuario:contraseña` en base64, como BASIC_AUTH
+    #[serde(default)]
+    pub basic_auth: String,
+}
+
+impl RegistryConfig {
+    pub fn load_file(path: &Path) -> Result<Vec<RegistryConfig>, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("No se pudo leer {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Registries no válidos en {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegistryView {
+    pub name: String,
+    pub url: String,
+    pub default: bool,
+}
+
+// Repositorio pendiente de enriquecer: (Registry, cliente, nombre)
+type CatalogName = (String, RegistryClient, String);
+
+// Repositorio del catálogo combinado, con el Registry al que pertenece
+#[derive(Serialize)]
+struct CatalogEntry {
+    registry: String,
+    #[serde(flatten)]
+    repository: RepositoryInfo,
+}
+
+// Registries gestionados por la instancia. El primero es el principal: el de
+// `/api/v1/registry`, las notificaciones y la retención.
+pub struct Registries {
+    clients: Vec<(String, RegistryClient)>,
+}
+
+// Los nombres van en la ruta; los que empiezan por `_` quedan para la propia API
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+impl Registries {
+    pub fn new(name: &str, client: RegistryClient) -> Result<Self, String> {
+        if !valid_name(name) {
+            return Err(format!("Nombre de Registry no válido: '{}'", name));
+        }
+        Ok(Self {
+            clients: vec![(name.to_string(), client)],
+        })
+    }
+
+    pub fn add(&mut self, name: &str, client: RegistryClient) -> Result<(), String> {
+        if !valid_name(name) {
+            return Err(format!("Nombre de Registry no válido: '{}'", name));
+        }
+        if self.get(name).is_some() {
+            return Err(format!("El Registry '{}' está repetido", name));
+        }
+        let client = client.with_api_path(&format!("/api/v1/registries/{}", name));
+        self.clients.push((name.to_string(), client));
+        Ok(())
+    }
+
+    pub fn default_client(&self) -> &RegistryClient {
+        &self.clients[0].1
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.clients[0].0
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegistryClient> {
+        self.clients.iter().find(|(n, _)| n == name).map(|(_, c)| c)
+    }
+
+    // Registry por nombre o, sin nombre, el principal
+    pub fn resolve(&self, name: Option<&str>) -> Option<&RegistryClient> {
+        match name {
+            Some(name) => self.get(name),
+            None => Some(self.default_client()),
+        }
+    }
+
+    pub fn list(&self) -> Vec<RegistryView> {
+        self.clients
+            .iter()
+            .enumerate()
+            .map(|(i, (name, client))| RegistryView {
+                name: name.clone(),
+                url: client.base_url().to_string(),
+                default: i == 0,
+            })
+            .collect()
+    }
+
+    // Catálogo de todos los Registries. Uno que no responde no impide ver el resto:
+    // se indica en `unavailable`.
+    pub async fn get_catalog(&self, query: &CatalogQuery, visible: impl Fn(&str, &str) -> bool) -> Response {
+        let listings = futures::future::join_all(
+            self.clients
+                .iter()
+                .map(|(name, client)| async move { (name, client, client.list_repositories().await) }),
+        )
+        .await;
+
+        let mut names = Vec::new();
+        let mut unavailable = Vec::new();
+        for (registry, client, result) in listings {
+            match result {
+                Ok(repositories) => names.extend(
+                    repositories
+                        .into_iter()
+                        .filter(|name| query.matches(name) && visible(registry, name))
+                        .map(|name| (registry.clone(), client.clone(), name)),
+                ),
+                Err((_, message)) => {
+                    warn!("Registry {} sin catálogo: {}", registry, message);
+                    unavailable.push(registry.clone());
+                }
+            }
+        }
+        let total = names.len();
+
+        // Igual que en un solo Registry: ordenando por nombre solo se enriquece la página
+        let page = if query.sorts_by_name() {
+            names.sort_by(|a, b| query.order.apply(a.2.cmp(&b.2).then_with(|| a.0.cmp(&b.0))));
+            fetch_entries(query.page_of(names)).await
+        } else {
+            let mut entries = fetch_entries(names).await;
+            entries.sort_by(|a, b| {
+                query
+                    .compare(&a.repository, &b.repository)
+                    .then_with(|| a.registry.cmp(&b.registry))
+            });
+            query.page_of(entries)
+        };
+
+        let pagination = Pagination::new(query, total as i64, &query.base_path("/api/v1/registries/_catalog"));
+        PagedResponse::new(
+            StatusCode::OK,
+            "Catálogo combinado obtenido",
+            Some(serde_json::json!({"repositories": page, "unavailable": unavailable})),
+            pagination,
+        )
+        .into_response()
+    }
+}
+
+async fn fetch_entries(names: Vec<CatalogName>) -> Vec<CatalogEntry> {
+    futures::stream::iter(names)
+        .map(|(registry, client, name)| async move {
+            CatalogEntry {
+                registry,
+                repository: client.fetch_repository_info(name).await,
+            }
+        })
+        .buffered(REGISTRY_CONCURRENCY)
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing};
+
+    async fn spawn_registry(repositories: &'static str) -> String {
+        let router = Router::new().route("/v2/_catalog", routing::get(move || async move { repositories }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_names() {
+        let client = RegistryClient::new("http://localhost:5000".into(), String::new());
+        let mut registries = Registries::new("prod", client.clone()).unwrap();
+        registries.add("staging", client.clone()).unwrap();
+        assert!(registries.add("staging", client.clone()).is_err());
+        assert!(registries.add("_catalog", client.clone()).is_err());
+        assert!(registries.add("a/b", client.clone()).is_err());
+        let names: Vec<_> = registries.list().into_iter().map(|r| (r.name, r.default)).collect();
+        assert_eq!(names, vec![("prod".to_string(), true), ("staging".to_string(), false)]);
+    }
+
+    #[tokio::test]
+    async fn test_combined_catalog() {
+        let prod = spawn_registry(r#"{"repositories":["web","api"]}"#).await;
+        let mirror = spawn_registry(r#"{"repositories":["api","secret"]}"#).await;
+        let mut registries = Registries::new("prod", RegistryClient::new(prod, String::new())).unwrap();
+        registries.add("mirror", RegistryClient::new(mirror, String::new())).unwrap();
+        // Un puerto sin nadie escuchando
+        registries
+            .add("down", RegistryClient::new("http://127.0.0.1:1".into(), String::new()))
+            .unwrap();
+
+        let response = registries.get_catalog(&CatalogQuery::default(), |_, name| name != "secret").await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let entries: Vec<_> = body["data"]["repositories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| format!("{}/{}", e["registry"].as_str().unwrap(), e["name"].as_str().unwrap()))
+            .collect();
+        assert_eq!(entries, vec!["mirror/api", "prod/api", "prod/web"]);
+        assert_eq!(body["data"]["unavailable"], serde_json::json!(["down"]));
+    }
+}
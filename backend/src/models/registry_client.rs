@@ -1,24 +1,89 @@
-use super::ApiResponse;
+use super::{ApiResponse, CatalogQuery, PagedResponse, Paginable, Pagination};
 use super::catalog::Catalog;
+use super::paginable::url_encode;
+use super::image_config::ImageConfig;
+use super::manifest::{ACCEPT_MANIFESTS, Manifest};
+use super::manifest_info::{LayerInfo, ManifestInfo};
+use super::manifest_list::ManifestList;
 use super::manifest_v2::ManifestV2;
+use super::registry_token::{BearerChallenge, TokenCache, TokenResponse, scope_for};
+use super::repository_cache::{CacheLookup, RepositoryCache};
 use super::repository_info::RepositoryInfo;
-use super::tag_detail::TagDetail;
+use super::tag_detail::{PlatformDetail, TagDetail};
 use super::tag_list::TagList;
+use super::tag_query::TagQuery;
 use axum::{http::StatusCode, response::IntoResponse};
-use dashmap::DashMap;
+use futures::StreamExt;
 use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue};
-use reqwest::{Client, header};
+use reqwest::{Body, Client, Method, Response, Url, header};
+use bytes::Bytes;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
-use tracing::debug;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::constants::{DEFAULT_CACHE_TTL, DEFAULT_REGISTRY_MAX_PAGES, DEFAULT_REGISTRY_PAGE_SIZE, REGISTRY_CONCURRENCY};
+
+fn manifest_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static(ACCEPT_MANIFESTS));
+    headers
+}
+
+// Extrae la URL con rel="next" de una cabecera Link, p.ej.
+// `</v2/_catalog?last=b&n=100>; rel="next"`
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let target = parts.next()?.trim();
+        let is_next = parts.any(|param| {
+            let param = param.trim().replace(' ', "");
+            param == "rel=\"next\"" || param == "rel=next"
+        });
+        is_next.then(|| target.trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+// Manifiesto sin interpretar, con el tipo que anunció el Registry
+pub struct RawManifest {
+    pub body: Bytes,
+    pub content_type: Option<String>,
+    pub digest: String,
+}
+
+impl RawManifest {
+    pub fn parse(&self) -> Result<Manifest, String> {
+        Manifest::parse(self.content_type.as_deref(), &self.body)
+    }
+
+    // Tipo con el que volver a subirlo: el del Registry o, si es genérico, el del cuerpo
+    pub fn media_type(&self) -> Result<String, String> {
+        match self.content_type.as_deref().map(|c| c.split(';').next().unwrap_or_default().trim()) {
+            Some(c) if Manifest::is_manifest_type(c) => Ok(c.to_string()),
+            _ => Ok(self.parse()?.media_type().to_string()),
+        }
+    }
+}
+
+pub enum BlobUpload {
+    Mounted,
+    Started(String),
+}
 
 #[derive(Clone)]
 pub struct RegistryClient {
     base_url: String,
     basic_auth: String,
     client: Client,
-    cache: Arc<DashMap<String, RepositoryInfo>>,
+    cache: Arc<RepositoryCache>,
+    revalidate: bool,
+    page_size: u32,
+    max_pages: u32,
+    tokens: Arc<TokenCache>,
+    // Prefijo de los enlaces de paginación de este Registry en la API
+    api_path: String,
 }
 
 impl RegistryClient {
@@ -27,18 +92,82 @@ impl RegistryClient {
             base_url,
             basic_auth: format!("Basic {}", encoded),
             client: Client::new(),
-            cache: Arc::new(DashMap::new()),
+            cache: Arc::new(RepositoryCache::new(Duration::from_secs(DEFAULT_CACHE_TTL))),
+            revalidate: false,
+            page_size: DEFAULT_REGISTRY_PAGE_SIZE,
+            max_pages: DEFAULT_REGISTRY_MAX_PAGES,
+            tokens: Arc::new(TokenCache::default()),
+            api_path: "/api/v1/registry".to_string(),
         }
     }
 
+    pub fn with_api_path(mut self, api_path: &str) -> Self {
+        self.api_path = api_path.to_string();
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Tamaño de página (`n`) pedido al Registry y número máximo de páginas a seguir
+    pub fn with_pagination(mut self, page_size: u32, max_pages: u32) -> Self {
+        self.page_size = page_size.max(1);
+        self.max_pages = max_pages.max(1);
+        self
+    }
+
+    // Vida de las entradas de la caché y si, al caducar, se revalidan comparando la lista de tags
+    pub fn with_cache(mut self, ttl: Duration, revalidate: bool) -> Self {
+        self.cache = Arc::new(RepositoryCache::new(ttl));
+        self.revalidate = revalidate;
+        self
+    }
+
     async fn fetch_catalog_names(&self) -> Result<Catalog, (StatusCode, String)> {
         let url = format!("{}/v2/_catalog", self.base_url);
-        self.fetch_from_registry(&url, None).await
+        let repositories = self
+            .fetch_all_pages(&url, |c: Catalog| c.repositories)
+            .await?;
+        Ok(Catalog { repositories })
     }
 
     async fn fetch_tags(&self, repo: &str) -> Result<TagList, (StatusCode, String)> {
         let url = format!("{}/v2/{}/tags/list", self.base_url, repo);
-        self.fetch_from_registry(&url, None).await
+        let tags = self.fetch_all_pages(&url, |t: TagList| t.tags).await?;
+        Ok(TagList { tags })
+    }
+
+    // Sigue la cabecera `Link: <...>; rel="next"` (RFC 5988) hasta agotar las páginas
+    // o alcanzar `max_pages`, acumulando los nombres de cada página.
+    async fn fetch_all_pages<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        items: impl Fn(T) -> Vec<String>,
+    ) -> Result<Vec<String>, (StatusCode, String)> {
+        let mut next_url = Some(format!("{}?n={}", url, self.page_size));
+        let mut all = Vec::new();
+        let mut pages = 0;
+
+        while let Some(current) = next_url.take() {
+            if pages == self.max_pages {
+                warn!("Límite de {} páginas alcanzado en {}; la lista puede estar incompleta", self.max_pages, url);
+                break;
+            }
+            pages += 1;
+
+            let resp = self.send_request(Method::GET, &current, None).await?;
+            next_url = resp
+                .headers()
+                .get(header::LINK)
+                .and_then(|h| h.to_str().ok())
+                .and_then(next_link)
+                .and_then(|link| Url::parse(&current).and_then(|u| u.join(&link)).ok())
+                .map(|u| u.to_string());
+            all.extend(items(Self::parse_response(resp, &current).await?));
+        }
+
+        Ok(all)
     }
 
     pub async fn fetch_creation_date(
@@ -47,97 +176,426 @@ impl RegistryClient {
         tag: &str,
     ) -> Result<String, (StatusCode, String)> {
         // 1. Obtener el manifiesto para el tag dado
-        let manifest: ManifestV2 = self
-            .fetch_from_registry(
-                &format!("{}/v2/{}/manifests/{}", self.base_url, repo, tag),
-                Some({
-                    let mut headers = HeaderMap::new();
-                    headers.insert(
-                        ACCEPT,
-                        HeaderValue::from_static(
-                            "application/vnd.docker.distribution.manifest.v2+json",
-                        ),
-                    );
-                    headers
-                }),
-            )
-            .await?;
+        let (manifest, _) = self.fetch_image_manifest(repo, tag).await?;
 
         // 2. Obtener el blob de configuración usando el digest del manifiesto
-        let config_blob: Value = self
+        let config_blob = self
             .fetch_config_blob(repo, &manifest.config.digest)
             .await?;
 
-        // 3. Extraer la fecha de creación del JSON del blob de configuración
-        if let Some(created) = config_blob.get("created")
-            && let Some(created_str) = created.as_str()
-        {
-            return Ok(created_str.to_string());
-        }
-
-        Err((
+        // 3. Extraer la fecha de creación del blob de configuración
+        config_blob.created.ok_or((
             StatusCode::BAD_REQUEST,
             "Fecha de creación no encontrada en el blob de configuración".to_string(),
         ))
     }
 
-    pub async fn fetch_manifest_info(&self, repo: &str, tag: &str) -> impl IntoResponse {
-        let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, tag);
+    // Obtiene el manifiesto junto con su digest real (cabecera Docker-Content-Digest)
+    async fn fetch_manifest(
+        &self,
+        repo: &str,
+        reference: &str,
+    ) -> Result<(Manifest, String), (StatusCode, String)> {
+        let raw = self.fetch_raw_manifest(repo, reference).await?;
+        let manifest = raw.parse().map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Error de parseo del manifiesto {}: {}", reference, e),
+            )
+        })?;
+        Ok((manifest, raw.digest))
+    }
+
+    // Manifiesto tal cual lo guarda el Registry. Para copiarlo sin cambiar su digest
+    // hay que reenviar estos bytes, no volver a serializar el manifiesto.
+    pub async fn fetch_raw_manifest(&self, repo: &str, reference: &str) -> Result<RawManifest, (StatusCode, String)> {
+        let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, reference);
+        let resp = self
+            .send_request(Method::GET, &url, Some(manifest_headers()))
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err((
+                resp.status(),
+                format!("No se pudo obtener el manifiesto de {}: {}", reference, resp.status()),
+            ));
+        }
+
+        let header_digest = resp
+            .headers()
+            .get("Docker-Content-Digest")
+            .and_then(|h| h.to_str().ok())
+            .map(|d| d.to_string());
+        let content_type = resp
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .map(|c| c.to_string());
+        let body = resp.bytes().await.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error leyendo cuerpo: {}", e),
+            )
+        })?;
 
-        // Preparamos la cabecera específica para Manifiesto V2
+        // Si el Registry no envía la cabecera, el digest es el sha256 del cuerpo tal cual
+        let digest = header_digest
+            .unwrap_or_else(|| format!("sha256:{}", hex::encode(Sha256::digest(&body))));
+
+        Ok(RawManifest { body, content_type, digest })
+    }
+
+    // Sube un manifiesto con sus bytes originales y devuelve su digest
+    pub async fn put_manifest(
+        &self,
+        repo: &str,
+        reference: &str,
+        manifest: &RawManifest,
+    ) -> Result<String, (StatusCode, String)> {
+        let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, reference);
         let mut headers = HeaderMap::new();
+        let content_type = manifest.media_type().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
         headers.insert(
-            ACCEPT,
-            HeaderValue::from_static("application/vnd.docker.distribution.manifest.v2+json"),
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(&content_type).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
         );
+        let resp = self
+            .send_request_with_body(Method::PUT, &url, Some(headers), Some(manifest.body.clone()))
+            .await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let detail = resp.text().await.unwrap_or_default();
+            return Err((status, format!("El Registry rechazó el manifiesto {}: {} {}", reference, status, detail)));
+        }
+        self.invalidate_repository(repo);
+        Ok(resp
+            .headers()
+            .get("Docker-Content-Digest")
+            .and_then(|h| h.to_str().ok())
+            .map(|d| d.to_string())
+            .unwrap_or_else(|| manifest.digest.clone()))
+    }
 
-        // Llamada genérica pasando las cabeceras
-        match self
-            .fetch_from_registry::<ManifestV2>(&url, Some(headers))
-            .await
-        {
-            Ok(manifest) => ApiResponse::success(
-                "Manifiesto obtenido",
-                Some(serde_json::to_value(manifest).unwrap()),
-            ),
-            Err((status, msg)) => ApiResponse::error(status, &msg),
+    pub async fn blob_exists(&self, repo: &str, digest: &str) -> Result<bool, (StatusCode, String)> {
+        let url = format!("{}/v2/{}/blobs/{}", self.base_url, repo, digest);
+        let resp = self.send_request(Method::HEAD, &url, None).await?;
+        match resp.status() {
+            s if s.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            s => Err((s, format!("No se pudo comprobar el blob {}: {}", digest, s))),
+        }
+    }
+
+    // Monta un blob de otro repositorio del mismo Registry sin transferirlo. Si el
+    // Registry no lo monta abre una subida normal y devuelve su URL.
+    pub async fn mount_blob(&self, repo: &str, digest: &str, from: &str) -> Result<BlobUpload, (StatusCode, String)> {
+        let mut url = Url::parse(&format!("{}/v2/{}/blobs/uploads/", self.base_url, repo))
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        url.query_pairs_mut().append_pair("mount", digest).append_pair("from", from);
+        let resp = self.send_request(Method::POST, url.as_str(), None).await?;
+        match resp.status() {
+            StatusCode::CREATED => Ok(BlobUpload::Mounted),
+            StatusCode::ACCEPTED => Ok(BlobUpload::Started(self.upload_location(&resp)?)),
+            s => Err((s, format!("No se pudo montar el blob {}: {}", digest, s))),
+        }
+    }
+
+    // Abre una subida de blob y devuelve la URL a la que enviarlo
+    pub async fn start_upload(&self, repo: &str) -> Result<String, (StatusCode, String)> {
+        let url = format!("{}/v2/{}/blobs/uploads/", self.base_url, repo);
+        let resp = self.send_request(Method::POST, &url, None).await?;
+        if resp.status() != StatusCode::ACCEPTED {
+            return Err((resp.status(), format!("No se pudo iniciar la subida a {}: {}", repo, resp.status())));
+        }
+        self.upload_location(&resp)
+    }
+
+    fn upload_location(&self, resp: &Response) -> Result<String, (StatusCode, String)> {
+        let location = resp
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|h| h.to_str().ok())
+            .ok_or((StatusCode::BAD_GATEWAY, "El Registry no indicó dónde subir el blob".to_string()))?;
+        // La cabecera puede ser relativa al Registry
+        Url::parse(&self.base_url)
+            .and_then(|base| base.join(location))
+            .map(|u| u.to_string())
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Location no válida {}: {}", location, e)))
+    }
+
+    // Descarga un blob sin leerlo: el cuerpo se reenvía a otro Registry por streaming
+    pub async fn fetch_blob(&self, repo: &str, digest: &str) -> Result<Response, (StatusCode, String)> {
+        let url = format!("{}/v2/{}/blobs/{}", self.base_url, repo, digest);
+        let resp = self.send_request(Method::GET, &url, None).await?;
+        if !resp.status().is_success() {
+            return Err((resp.status(), format!("No se pudo leer el blob {}: {}", digest, resp.status())));
+        }
+        Ok(resp)
+    }
+
+    // Completa una subida monolítica. Un cuerpo en streaming no se puede repetir, así que
+    // no hay reintento tras un 401: el token ya lo obtuvo `start_upload`/`mount_blob`.
+    // La `location` la elige el Registry, pero la petición lleva sus credenciales:
+    // solo se envía a su mismo origen (esquema, host y puerto).
+    pub async fn upload_blob(
+        &self,
+        location: &str,
+        digest: &str,
+        size: u64,
+        body: Body,
+    ) -> Result<(), (StatusCode, String)> {
+        let mut url = Url::parse(location).map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+        let base = Url::parse(&self.base_url).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if url.origin() != base.origin() {
+            return Err((
+                StatusCode::BAD_GATEWAY,
+                format!("El Registry pidió subir el blob {} a otro servidor: {}", digest, location),
+            ));
+        }
+        url.query_pairs_mut().append_pair("digest", digest);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+        let authorization = self.authorization(&scope_for(&Method::PUT, url.as_str()));
+        let resp = self
+            .execute(Method::PUT, url.as_str(), Some(headers), Some(body), &authorization)
+            .await?;
+        if resp.status() != StatusCode::CREATED {
+            return Err((resp.status(), format!("El Registry rechazó el blob {}: {}", digest, resp.status())));
+        }
+        Ok(())
+    }
+
+    // Igual que `fetch_manifest`, pero si la referencia es un índice multiplataforma
+    // resuelve el manifiesto de imagen representativo. El digest devuelto es el de
+    // la referencia original.
+    async fn fetch_image_manifest(
+        &self,
+        repo: &str,
+        reference: &str,
+    ) -> Result<(ManifestV2, String), (StatusCode, String)> {
+        let (manifest, digest) = self.fetch_manifest(repo, reference).await?;
+        let child = match manifest {
+            Manifest::DockerV2(m) | Manifest::OciImage(m) => return Ok((m, digest)),
+            Manifest::DockerList(l) | Manifest::OciIndex(l) => l.preferred().cloned().ok_or((
+                StatusCode::BAD_REQUEST,
+                format!("El índice {} no contiene imágenes", reference),
+            ))?,
+        };
+        match self.fetch_manifest(repo, &child.digest).await? {
+            (Manifest::DockerV2(m) | Manifest::OciImage(m), _) => Ok((m, digest)),
+            _ => Err((
+                StatusCode::BAD_REQUEST,
+                format!("El manifiesto {} no es una imagen", child.digest),
+            )),
         }
-        .into_response()
+    }
+
+    pub async fn fetch_manifest_info(&self, repo: &str, reference: &str) -> impl IntoResponse {
+        let (manifest, digest) = match self.fetch_manifest(repo, reference).await {
+            Ok(m) => m,
+            Err((status, msg)) => return ApiResponse::error(status, &msg).into_response(),
+        };
+
+        // Los índices multiplataforma no tienen configuración ni capas propias,
+        // pero sí una lista de plataformas con su digest y tamaño
+        let platforms = match &manifest {
+            Manifest::DockerList(l) | Manifest::OciIndex(l) => self
+                .resolve_platforms(repo, l)
+                .await
+                .into_iter()
+                .map(|(p, _)| p)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let (config, layers, size_bytes) = match manifest.as_image() {
+            Some(image) => {
+                let config = match self.fetch_config_blob(repo, &image.config.digest).await {
+                    Ok(c) => c,
+                    Err((status, msg)) => return ApiResponse::error(status, &msg).into_response(),
+                };
+
+                // Asociamos cada capa con la instrucción que la generó (si el historial la tiene)
+                let mut commands = config.layer_commands().into_iter();
+                let layers = image
+                    .layers
+                    .iter()
+                    .map(|l| LayerInfo {
+                        digest: l.digest.clone(),
+                        media_type: l.media_type.clone(),
+                        size: l.size,
+                        created_by: commands.next().flatten(),
+                    })
+                    .collect();
+                (Some(config), layers, image.size_bytes())
+            }
+            None => (None, Vec::new(), platforms.iter().map(|p| p.size_bytes).sum()),
+        };
+
+        let info = ManifestInfo {
+            name: repo.to_string(),
+            reference: reference.to_string(),
+            digest,
+            media_type: manifest.media_type().to_string(),
+            size_bytes,
+            manifest,
+            config,
+            layers,
+            platforms,
+        };
+
+        ApiResponse::success("Manifiesto obtenido", Some(info)).into_response()
     }
 
     async fn fetch_config_blob(
         &self,
         repo: &str,
         digest: &str,
-    ) -> Result<Value, (StatusCode, String)> {
+    ) -> Result<ImageConfig, (StatusCode, String)> {
         let url = format!("{}/v2/{}/blobs/{}", self.base_url, repo, digest);
-        self.fetch_from_registry::<Value>(&url, None).await
+        self.fetch_from_registry::<ImageConfig>(&url, None).await
     }
 
-    async fn fetch_from_registry<T: DeserializeOwned>(
+    // Punto único por el que salen todas las peticiones al Registry.
+    // Usa el token Bearer en caché para el ámbito de la petición o, si no hay,
+    // la autenticación Basic. Si el Registry responde 401 con un reto Bearer
+    // obtenemos un token del token server y repetimos la petición una vez.
+    async fn send_request(
         &self,
+        method: Method,
         url: &str,
         extra_headers: Option<HeaderMap>,
-    ) -> Result<T, (StatusCode, String)> {
+    ) -> Result<Response, (StatusCode, String)> {
+        self.send_request_with_body(method, url, extra_headers, None).await
+    }
+
+    // Token Bearer en caché para el ámbito o, si no hay, las credenciales básicas
+    fn authorization(&self, scope: &str) -> String {
+        self.tokens
+            .get(scope)
+            .map(|token| format!("Bearer {}", token))
+            .unwrap_or_else(|| self.basic_auth.clone())
+    }
+
+    async fn send_request_with_body(
+        &self,
+        method: Method,
+        url: &str,
+        extra_headers: Option<HeaderMap>,
+        body: Option<Bytes>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let scope = scope_for(&method, url);
+        let authorization = self.authorization(&scope);
+
+        let resp = self
+            .execute(method.clone(), url, extra_headers.clone(), body.clone().map(Into::into), &authorization)
+            .await?;
+        if resp.status() != StatusCode::UNAUTHORIZED {
+            return Ok(resp);
+        }
+
+        let Some(challenge) = resp
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(BearerChallenge::parse)
+        else {
+            return Ok(resp);
+        };
+
+        let token = self.fetch_token(&challenge, &scope).await?;
+        self.execute(method, url, extra_headers, body.map(Into::into), &format!("Bearer {}", token))
+            .await
+    }
+
+    async fn execute(
+        &self,
+        method: Method,
+        url: &str,
+        extra_headers: Option<HeaderMap>,
+        body: Option<Body>,
+        authorization: &str,
+    ) -> Result<Response, (StatusCode, String)> {
         let mut request = self
             .client
-            .get(url)
-            .header(AUTHORIZATION, self.basic_auth.clone());
+            .request(method, url)
+            .header(AUTHORIZATION, authorization);
         if let Some(headers) = extra_headers {
             request = request.headers(headers);
         }
-        let resp = request.send().await.map_err(|e| {
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        request.send().await.map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Error de red: {}", e),
             )
-        })?;
+        })
+    }
+
+    // Pide al token server un token para el ámbito del reto y lo guarda en caché
+    async fn fetch_token(
+        &self,
+        challenge: &BearerChallenge,
+        scope: &str,
+    ) -> Result<String, (StatusCode, String)> {
+        let token_url = challenge.token_url(scope).ok_or((
+            StatusCode::BAD_GATEWAY,
+            format!("Realm de autenticación no válido: {}", challenge.realm),
+        ))?;
+        debug!("Solicitando token para {} a {}", scope, challenge.realm);
+
+        let resp = self
+            .client
+            .get(token_url)
+            .header(AUTHORIZATION, &self.basic_auth)
+            .send()
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Error de red con el token server: {}", e),
+                )
+            })?;
+        if !resp.status().is_success() {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                format!("El token server rechazó las credenciales: {}", resp.status()),
+            ));
+        }
+
+        let (token, expires_in) = resp
+            .json::<TokenResponse>()
+            .await
+            .ok()
+            .and_then(TokenResponse::into_token)
+            .ok_or((
+                StatusCode::BAD_GATEWAY,
+                "Respuesta del token server sin token".to_string(),
+            ))?;
+        self.tokens.insert(scope, token.clone(), expires_in);
+        Ok(token)
+    }
+
+    async fn fetch_from_registry<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        extra_headers: Option<HeaderMap>,
+    ) -> Result<T, (StatusCode, String)> {
+        let resp = self.send_request(Method::GET, url, extra_headers).await?;
+        Self::parse_response(resp, url).await
+    }
 
+    async fn parse_response<T: DeserializeOwned>(
+        resp: Response,
+        url: &str,
+    ) -> Result<T, (StatusCode, String)> {
         // Si el Registry devuelve 401 o cualquier error, lo mapeamos
         if !resp.status().is_success() {
             return Err((
                 resp.status(),
-                format!("Error al obtener el catálogo: {}", resp.status()),
+                format!("Error del Registry en {}: {}", url, resp.status()),
             ));
         }
 
@@ -159,82 +617,121 @@ impl RegistryClient {
         })
     }
 
-    pub async fn get_catalog(self) -> impl IntoResponse {
-        let catalog = match self.fetch_catalog_names().await {
-            Ok(c) => c,
-            Err((s, m)) => return ApiResponse::error(s, &m).into_response(),
+    // Nombre, número de tags y fecha del último push de un repositorio (con caché)
+    pub async fn fetch_repository_info(&self, repo_name: String) -> RepositoryInfo {
+        // 1. Entrada vigente en caché
+        let stale_fingerprint = match self.cache.lookup(&repo_name) {
+            CacheLookup::Fresh(info) => return info,
+            CacheLookup::Stale(fingerprint) => Some(fingerprint),
+            CacheLookup::Missing => None,
         };
 
-        // Usamos Arc para poder compartir el cliente en los hilos asíncronos
-        let client_arc = Arc::new(self);
-
-        let futures = catalog.repositories.into_iter().map(|repo_name| {
-            let client = client_arc.clone();
-
-            async move {
-                // 1. Acceso correcto a la caché
-                // DashMap devuelve un Ref; usamos .value() para llegar al RepositoryInfo
-                if let Some(cached_ref) = client.cache.get(&repo_name) {
-                    return cached_ref.value().clone();
-                }
+        // 2. Revalidación barata: si la lista de tags no ha cambiado, la entrada sigue valiendo
+        let tags = match self.fetch_tags(&repo_name).await {
+            Ok(t) => t.tags,
+            Err((_, m)) => {
+                debug!("Error obteniendo tags de {}: {}", repo_name, m);
+                return RepositoryInfo {
+                    name: repo_name,
+                    last_push: None,
+                    tag_count: 0,
+                };
+            }
+        };
+        let fingerprint = RepositoryCache::fingerprint(&tags);
+        if self.revalidate
+            && stale_fingerprint == Some(fingerprint)
+            && let Some(info) = self.cache.touch(&repo_name)
+        {
+            return info;
+        }
 
-                // 2. Trabajo pesado
-                let tags = client.fetch_tags(&repo_name).await.ok();
-                let count = tags.as_ref().map(|t| t.tags.len()).unwrap_or(0);
-                let mut last_date = None;
+        // 3. Trabajo pesado
+        let last_date = match tags.last() {
+            Some(last_tag) => self.fetch_creation_date(&repo_name, last_tag).await.ok(),
+            None => None,
+        };
 
-                if let Some(t_list) = tags
-                    && let Some(last_tag) = t_list.tags.last()
-                {
-                    // Usamos la auth almacenada en self (client)
-                    last_date = client.fetch_creation_date(&repo_name, last_tag).await.ok();
-                }
+        let info = RepositoryInfo {
+            name: repo_name,
+            last_push: last_date,
+            tag_count: tags.len(),
+        };
 
-                let info = RepositoryInfo {
-                    name: repo_name.clone(),
-                    last_push: last_date,
-                    tag_count: count,
-                };
+        // 4. Insertar en caché
+        self.cache.insert(info.clone(), fingerprint);
+        info
+    }
 
-                // 3. Insertar en caché
-                client.cache.insert(repo_name, info.clone());
-                info
+    // Invalida la caché (de un repositorio o completa). Si se indica un repositorio
+    // se recalcula en el momento y se devuelve su información actualizada.
+    pub async fn refresh_cache(&self, repo: Option<&str>) -> impl IntoResponse {
+        match repo {
+            Some(repo) => {
+                self.cache.invalidate(repo);
+                let info = self.fetch_repository_info(repo.to_string()).await;
+                ApiResponse::success(&format!("Caché de {} actualizada", repo), Some(info))
+                    .into_response()
             }
-        });
-
-        let enriched_data = futures::future::join_all(futures).await;
+            None => {
+                let removed = self.cache.clear();
+                ApiResponse::success(
+                    "Caché vaciada",
+                    Some(serde_json::json!({ "invalidated": removed })),
+                )
+                .into_response()
+            }
+        }
+    }
 
-        ApiResponse::success("Catálogo obtenido", Some(enriched_data)).into_response()
+    pub fn invalidate_repository(&self, repo: &str) {
+        if self.cache.invalidate(repo) {
+            debug!("Caché de {} invalidada", repo);
+        }
     }
 
-    // Función auxiliar interna para centralizar la lógica de reqwest
-    async fn fetch_registry_data(&self, url: &str) -> Result<Value, (StatusCode, String)> {
-        let resp = self
-            .client
-            .get(url)
-            .header(header::AUTHORIZATION, &self.basic_auth)
-            .send()
+    async fn fetch_repositories_info(&self, names: Vec<String>) -> Vec<RepositoryInfo> {
+        futures::stream::iter(names)
+            .map(|name| self.fetch_repository_info(name))
+            .buffered(REGISTRY_CONCURRENCY)
+            .collect()
             .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error de red: {}", e),
-                )
-            })?;
+    }
 
-        if !resp.status().is_success() {
-            return Err((
-                resp.status(),
-                format!("Error del Registry: {}", resp.status()),
-            ));
-        }
+    // `visible` descarta los repositorios a los que el usuario no tiene acceso
+    // antes de contar y paginar.
+    pub async fn get_catalog(&self, query: &CatalogQuery, visible: impl Fn(&str) -> bool) -> impl IntoResponse {
+        let catalog = match self.fetch_catalog_names().await {
+            Ok(c) => c,
+            Err((s, m)) => return ApiResponse::error(s, &m).into_response(),
+        };
 
-        resp.json::<Value>().await.map_err(|e| {
-            (
-                StatusCode::BAD_REQUEST,
-                format!("Error al parsear JSON: {}", e),
-            )
-        })
+        let mut names: Vec<String> = catalog
+            .repositories
+            .into_iter()
+            .filter(|name| query.matches(name) && visible(name))
+            .collect();
+        let total = names.len();
+
+        // Si se ordena por nombre solo enriquecemos la página pedida; para el resto
+        // de criterios necesitamos los datos de todos los repositorios filtrados.
+        let page = if query.sorts_by_name() {
+            query.order.sort_names(&mut names);
+            self.fetch_repositories_info(query.page_of(names)).await
+        } else {
+            let mut repositories = self.fetch_repositories_info(names).await;
+            query.sort(&mut repositories);
+            query.page_of(repositories)
+        };
+
+        let pagination = Pagination::new(query, total as i64, &query.base_path(&self.api_path));
+        PagedResponse::new(
+            StatusCode::OK,
+            "Catálogo obtenido",
+            serde_json::to_value(page).ok(),
+            pagination,
+        )
+        .into_response()
     }
 
     // 3. Obtener el Digest (necesario para borrar)
@@ -245,23 +742,10 @@ impl RegistryClient {
     ) -> Result<String, (StatusCode, String)> {
         let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, tag);
 
+        // IMPORTANTE: Sin la cabecera Accept, el Registry puede devolverte el digest v1 en lugar del v2
         let resp = self
-            .client
-            .head(&url)
-            .header(header::AUTHORIZATION, &self.basic_auth)
-            // IMPORTANTE: Sin esta cabecera, el Registry puede devolverte el digest v1 en lugar del v2
-            .header(
-                header::ACCEPT,
-                "application/vnd.docker.distribution.manifest.v2+json",
-            )
-            .send()
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error de red: {}", e),
-                )
-            })?;
+            .send_request(Method::HEAD, &url, Some(manifest_headers()))
+            .await?;
 
         if !resp.status().is_success() {
             return Err((
@@ -283,34 +767,181 @@ impl RegistryClient {
         Ok(digest.to_string())
     }
 
-    pub async fn delete_tag(&self, repo: &str, tag: &str, auth_header: &str) -> impl IntoResponse {
-        // 1. Primero necesitamos el Digest
-        let digest = match self.get_manifest_digest(repo, tag).await {
-            Ok(d) => d,
-            Err((status, msg)) => return ApiResponse::<Value>::error(status, &msg).into_response(),
-        };
+    // 4. Borrar el manifiesto al que apunta el tag usando las credenciales del servidor
+    // Borra el manifiesto al que apunta `tag` y devuelve su digest
+    pub async fn delete_manifest(&self, repo: &str, tag: &str) -> Result<String, (StatusCode, String)> {
+        // Primero necesitamos el Digest
+        let digest = self.get_manifest_digest(repo, tag).await?;
+
+        // Ahora ejecutamos el borrado real usando el digest
+        self.delete_digest(repo, &digest).await?;
+        Ok(digest)
+    }
+
+    // Añade `tag` al manifiesto de `source` (tag o digest) reenviando sus bytes, de modo
+    // que el digest no cambia. Si `tag` ya apunta a otro manifiesto hace falta `force`.
+    // La comprobación no es atómica: la API del Registry no tiene PUT condicional, así
+    // que un push del mismo tag entre la comprobación y el PUT queda sobrescrito.
+    pub async fn retag(
+        &self,
+        repo: &str,
+        source: &str,
+        tag: &str,
+        force: bool,
+    ) -> Result<String, (StatusCode, String)> {
+        let manifest = self.fetch_raw_manifest(repo, source).await?;
+        match self.get_manifest_digest(repo, tag).await {
+            Ok(current) if current == manifest.digest => return Ok(current),
+            Ok(current) if !force => {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("El tag {} ya existe y apunta a {}", tag, current),
+                ))
+            }
+            Ok(_) => {}
+            Err((StatusCode::NOT_FOUND, _)) => {}
+            Err(e) => return Err(e),
+        }
+        self.put_manifest(repo, tag, &manifest).await
+    }
 
-        // 2. Ahora ejecutamos el borrado real usando el digest
+    // Como en `retag`: `tag` puede no existir o apuntar ya a `digest`; si apunta a
+    // otro manifiesto hace falta `force`
+    pub async fn check_tag_overwrite(
+        &self,
+        repo: &str,
+        tag: &str,
+        digest: &str,
+        force: bool,
+    ) -> Result<(), (StatusCode, String)> {
+        match self.get_manifest_digest(repo, tag).await {
+            Ok(current) if current != digest && !force => Err((
+                StatusCode::CONFLICT,
+                format!("El tag {} ya existe y apunta a {}", tag, current),
+            )),
+            Ok(_) | Err((StatusCode::NOT_FOUND, _)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Borra un manifiesto por digest (y con él todos los tags que apuntan a él)
+    pub async fn delete_digest(&self, repo: &str, digest: &str) -> Result<(), (StatusCode, String)> {
         let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, digest);
+        let resp = self.send_request(Method::DELETE, &url, None).await?;
 
-        let resp_result = self
-            .client
-            .delete(&url)
-            .header(header::AUTHORIZATION, auth_header)
-            .send()
-            .await;
+        match resp.status() {
+            s if s.is_success() => {
+                self.invalidate_repository(repo);
+                Ok(())
+            }
+            // El Registry responde 405 (UNSUPPORTED) cuando el borrado está deshabilitado
+            StatusCode::METHOD_NOT_ALLOWED => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "El Registry no permite borrar manifiestos. Habilita REGISTRY_STORAGE_DELETE_ENABLED=true".to_string(),
+            )),
+            s => Err((s, format!("El Registry no permitió el borrado de {}: {}", digest, s))),
+        }
+    }
+
+    // Resuelve cada manifiesto hijo de un índice para conocer su tamaño real.
+    // Devuelve también el digest del blob de configuración de cada plataforma.
+    async fn resolve_platforms(
+        &self,
+        repo: &str,
+        list: &ManifestList,
+    ) -> Vec<(PlatformDetail, Option<String>)> {
+        let futures = list.images().map(|child| async move {
+            let platform = child.platform.clone();
+            let image = match self.fetch_manifest(repo, &child.digest).await {
+                Ok((Manifest::DockerV2(m) | Manifest::OciImage(m), _)) => Some(m),
+                Ok(_) => None,
+                Err(e) => {
+                    debug!("Error obteniendo manifiesto hijo {}: {}", child.digest, e.1);
+                    None
+                }
+            };
+            let detail = PlatformDetail {
+                os: platform.as_ref().map(|p| p.os.clone()).unwrap_or_else(|| "unknown".into()),
+                architecture: platform
+                    .as_ref()
+                    .map(|p| p.architecture.clone())
+                    .unwrap_or_else(|| "unknown".into()),
+                variant: platform.and_then(|p| p.variant),
+                digest: child.digest.clone(),
+                size_bytes: image.as_ref().map(|m| m.size_bytes()).unwrap_or(child.size),
+            };
+            (detail, image.map(|m| m.config.digest))
+        });
+        futures::future::join_all(futures).await
+    }
 
-        match resp_result {
-            Ok(resp) if resp.status().is_success() => {
-                ApiResponse::<Value>::success("Tag eliminado correctamente", None).into_response()
+    // Detalle de un tag: digest del manifiesto, tamaño, plataformas y datos del blob de configuración
+    async fn fetch_tag_detail(&self, repo: &str, tag_name: String) -> TagDetail {
+        let (manifest, digest) = match self.fetch_manifest(repo, &tag_name).await {
+            Ok(m) => m,
+            Err(e) => {
+                tracing::error!("Error fetch_manifest para {}: {}", tag_name, e.1);
+                return TagDetail::empty(tag_name);
+            }
+        };
+
+        // Para un índice el tamaño es la suma de todas sus plataformas y la fecha,
+        // arquitectura y SO son los de la plataforma representativa.
+        let (size, config_digest, platforms) = match &manifest {
+            Manifest::DockerV2(m) | Manifest::OciImage(m) => {
+                (m.size_bytes(), Some(m.config.digest.clone()), Vec::new())
+            }
+            Manifest::DockerList(l) | Manifest::OciIndex(l) => {
+                let resolved = self.resolve_platforms(repo, l).await;
+                let preferred = l.preferred().map(|p| p.digest.clone());
+                let config_digest = resolved
+                    .iter()
+                    .find(|(p, _)| Some(&p.digest) == preferred.as_ref())
+                    .and_then(|(_, c)| c.clone());
+                let size = resolved.iter().map(|(p, _)| p.size_bytes).sum();
+                (size, config_digest, resolved.into_iter().map(|(p, _)| p).collect())
+            }
+        };
+
+        let Some(config_digest) = config_digest else {
+            let mut detail = TagDetail::basic(tag_name, digest, size);
+            detail.platforms = platforms;
+            return detail;
+        };
+
+        let config_blob = self.fetch_config_blob(repo, &config_digest).await;
+        debug!("Config blob para {}: {:?}", tag_name, config_blob);
+
+        match config_blob {
+            Ok(c) => {
+                // Una imagen simple soporta exactamente la plataforma de su configuración
+                let platforms = if manifest.as_image().is_some() {
+                    vec![PlatformDetail {
+                        os: c.os.clone().unwrap_or_else(|| "unknown".into()),
+                        architecture: c.architecture.clone().unwrap_or_else(|| "unknown".into()),
+                        variant: c.variant.clone(),
+                        digest: digest.clone(),
+                        size_bytes: size,
+                    }]
+                } else {
+                    platforms
+                };
+                TagDetail {
+                    name: tag_name,
+                    digest,
+                    size_bytes: size,
+                    created_at: c.created,
+                    architecture: c.architecture,
+                    os: c.os,
+                    platforms,
+                }
+            }
+            Err(e) => {
+                debug!("Error obteniendo config blob para {}: {}", tag_name, e.1);
+                let mut detail = TagDetail::basic(tag_name, digest, size);
+                detail.platforms = platforms;
+                detail
             }
-            Ok(resp) => ApiResponse::error(
-                resp.status(),
-                "El Registry no permitió el borrado (¿está habilitado?)",
-            )
-            .into_response(),
-            Err(e) => ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
-                .into_response(),
         }
     }
 
@@ -321,76 +952,285 @@ impl RegistryClient {
             Err((s, m)) => return ApiResponse::<Value>::error(s, &m).into_response(),
         };
 
-        let repo_name = repo.to_string();
-        let futures = tag_list.tags.into_iter().map(|tag_name| {
-            // Clonamos el cliente directamente (ya tiene Arc interno para la caché)
-            let client = self.clone();
-            let repo = repo_name.clone();
-
-            async move {
-                let url = format!("{}/v2/{}/manifests/{}", client.base_url, repo, tag_name);
-
-                // Construimos las cabeceras de forma más segura
-                let mut headers = HeaderMap::new();
-                headers.insert(
-                    header::ACCEPT,
-                    HeaderValue::from_static(
-                        "application/vnd.docker.distribution.manifest.v2+json",
-                    ),
-                );
-
-                // Intentamos obtener el manifiesto
-                match client
-                    .fetch_from_registry::<ManifestV2>(&url, Some(headers))
-                    .await
-                {
-                    Ok(m) => {
-                        // Si el manifiesto funciona, intentamos el config blob para la fecha
-                        let size: u64 =
-                            m.layers.iter().map(|l| l.size).sum::<u64>() + m.config.size;
-
-                        // Aquí llamamos a fetch_config_blob que ya tienes en tu registry_client.rs
-                        let config_blob = client.fetch_config_blob(&repo, &m.config.digest).await;
-                        debug!("Config blob para {}: {:?}", tag_name, config_blob);
-
-                        match config_blob {
-                            Ok(c) => TagDetail {
-                                name: tag_name,
-                                digest: m.config.digest,
-                                size_bytes: size,
-                                created_at: c
-                                    .get("created")
-                                    .and_then(|v| v.as_str())
-                                    .map(|s| s.to_string()),
-                                architecture: c
-                                    .get("architecture")
-                                    .and_then(|v| v.as_str())
-                                    .map(|s| s.to_string()),
-                                os: c.get("os").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                            },
-                            Err(e) => {
-                                debug!("Error obteniendo config blob para {}: {}", tag_name, e.1);
-                                TagDetail::basic(tag_name, m.config.digest, size)
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // ESTO ES LO QUE ESTÁ PASANDO: El fetch del manifiesto falla
-                        tracing::error!("Error fetch_manifest para {}: {}", tag_name, e.1);
-                        TagDetail::empty(tag_name)
-                    }
-                }
-            }
-        });
-
-        let enriched_tags = futures::future::join_all(futures).await;
+        // 2. Enriquecer cada tag de forma concurrente
+        let enriched_tags = self.fetch_tag_details(repo, tag_list.tags).await;
 
         ApiResponse::success(
-            &format!("Tags de {} obtenidos", repo_name),
+            &format!("Tags de {} obtenidos", repo),
             Some(enriched_tags),
         )
         .into_response()
     }
 
+    pub async fn list_repositories(&self) -> Result<Vec<String>, (StatusCode, String)> {
+        Ok(self.fetch_catalog_names().await?.repositories)
+    }
+
+    // Detalle de todos los tags de un repositorio
+    pub async fn list_tag_details(&self, repo: &str) -> Result<Vec<TagDetail>, (StatusCode, String)> {
+        let tag_list = self.fetch_tags(repo).await?;
+        Ok(self.fetch_tag_details(repo, tag_list.tags).await)
+    }
+
+    async fn fetch_tag_details(&self, repo: &str, names: Vec<String>) -> Vec<TagDetail> {
+        futures::stream::iter(names)
+            .map(|tag_name| self.fetch_tag_detail(repo, tag_name))
+            .buffered(REGISTRY_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    pub async fn get_tags_page(&self, repo: &str, query: &TagQuery) -> impl IntoResponse {
+        let filter = match query.filter() {
+            Ok(f) => f,
+            Err(m) => return ApiResponse::error(StatusCode::BAD_REQUEST, &m).into_response(),
+        };
+        let tag_list = match self.fetch_tags(repo).await {
+            Ok(t) => t,
+            Err((s, m)) => return ApiResponse::error(s, &m).into_response(),
+        };
+
+        let mut names: Vec<String> = tag_list
+            .tags
+            .into_iter()
+            .filter(|name| filter.matches(name))
+            .collect();
+        let total = names.len();
+
+        // Por nombre solo enriquecemos los tags de la página; por fecha o tamaño
+        // hace falta el manifiesto de todos los tags que pasan el filtro.
+        let page = if query.sorts_by_name() {
+            query.order.sort_names(&mut names);
+            self.fetch_tag_details(repo, query.page_of(names)).await
+        } else {
+            let mut tags = self.fetch_tag_details(repo, names).await;
+            query.sort(&mut tags);
+            query.page_of(tags)
+        };
+
+        let base_path = query.base_path(&format!("{}/{}/tags", self.api_path, url_encode(repo)));
+        let pagination = Pagination::new(query, total as i64, &base_path);
+        PagedResponse::new(
+            StatusCode::OK,
+            &format!("Tags de {} obtenidos", repo),
+            serde_json::to_value(page).ok(),
+            pagination,
+        )
+        .into_response()
+    }
+
     // Implementación de apoyo para limpiar el código anterior
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::OCI_MANIFEST;
+    use axum::{Router, http::HeaderMap as AxumHeaderMap, routing};
+
+    // Levanta un Registry falso en un puerto efímero y devuelve su URL base
+    async fn spawn_registry(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn digest_headers() -> AxumHeaderMap {
+        let mut headers = AxumHeaderMap::new();
+        headers.insert("Docker-Content-Digest", "sha256:abc".parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_upload_blob_stays_on_the_registry() {
+        let client = RegistryClient::new("http://127.0.0.1:1".into(), "user:password".into());
+        for location in ["http://attacker.example/upload", "https://127.0.0.1:1/upload", "http://127.0.0.1:2/upload"] {
+            let (status, _) = client
+                .upload_blob(location, "sha256:abc", 0, Body::from(Vec::new()))
+                .await
+                .unwrap_err();
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(
+            next_link(r#"</v2/_catalog?last=b&n=2>; rel="next""#),
+            Some("/v2/_catalog?last=b&n=2".to_string())
+        );
+        assert_eq!(
+            next_link(r#"<https://r.example/v2/a/tags/list?last=x>; rel="prev", <https://r.example/v2/a/tags/list?last=z>; rel=next"#),
+            Some("https://r.example/v2/a/tags/list?last=z".to_string())
+        );
+        assert_eq!(next_link(r#"</v2/_catalog?last=b>; rel="prev""#), None);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_follows_link_header() {
+        let router = Router::new().route(
+            "/v2/_catalog",
+            routing::get(|axum::extract::RawQuery(query): axum::extract::RawQuery| async move {
+                let mut headers = AxumHeaderMap::new();
+                match query.as_deref() {
+                    Some("n=2") => {
+                        headers.insert("Link", r#"</v2/_catalog?last=b&n=2>; rel="next""#.parse().unwrap());
+                        (headers, r#"{"repositories":["a","b"]}"#)
+                    }
+                    Some("last=b&n=2") => {
+                        headers.insert("Link", r#"</v2/_catalog?last=d&n=2>; rel="next""#.parse().unwrap());
+                        (headers, r#"{"repositories":["c","d"]}"#)
+                    }
+                    _ => (headers, r#"{"repositories":["e"]}"#),
+                }
+            }),
+        );
+        let base_url = spawn_registry(router).await;
+        let client = RegistryClient::new(base_url.clone(), "dXNlcjpwYXNz".into()).with_pagination(2, 10);
+        let catalog = client.fetch_catalog_names().await.unwrap();
+        assert_eq!(catalog.repositories, vec!["a", "b", "c", "d", "e"]);
+
+        // Con el límite de páginas alcanzado devolvemos lo acumulado
+        let client = RegistryClient::new(base_url, "dXNlcjpwYXNz".into()).with_pagination(2, 2);
+        let catalog = client.fetch_catalog_names().await.unwrap();
+        assert_eq!(catalog.repositories, vec!["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_manifest_returns_digest() {
+        let router = Router::new().route(
+            "/v2/app/manifests/{reference}",
+            routing::head(|| async { digest_headers() })
+                .delete(|| async { StatusCode::ACCEPTED }),
+        );
+        let client = RegistryClient::new(spawn_registry(router).await, "dXNlcjpwYXNz".into());
+        assert_eq!(client.delete_manifest("app", "1.0").await.unwrap(), "sha256:abc");
+    }
+
+    #[tokio::test]
+    async fn test_delete_manifest_when_delete_disabled() {
+        let router = Router::new().route(
+            "/v2/app/manifests/{reference}",
+            routing::head(|| async { digest_headers() })
+                .delete(|| async { StatusCode::METHOD_NOT_ALLOWED }),
+        );
+        let client = RegistryClient::new(spawn_registry(router).await, "dXNlcjpwYXNz".into());
+        let (status, message) = client.delete_manifest("app", "1.0").await.unwrap_err();
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert!(message.contains("REGISTRY_STORAGE_DELETE_ENABLED"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_manifest_computes_digest_without_header() {
+        let body = r#"{"schemaVersion":2,"mediaType":"application/vnd.docker.distribution.manifest.v2+json","config":{"mediaType":"application/vnd.docker.container.image.v1+json","size":10,"digest":"sha256:cfg"},"layers":[{"mediaType":"application/vnd.docker.image.rootfs.diff.tar.gzip","size":90,"digest":"sha256:l1"}]}"#;
+        let router = Router::new().route(
+            "/v2/app/manifests/{reference}",
+            routing::get(move || async move { body }),
+        );
+        let client = RegistryClient::new(spawn_registry(router).await, "dXNlcjpwYXNz".into());
+        let (manifest, digest) = client.fetch_manifest("app", "1.0").await.unwrap();
+        assert_eq!(manifest.as_image().unwrap().layers[0].size, 90);
+        assert_eq!(digest, format!("sha256:{}", hex::encode(Sha256::digest(body.as_bytes()))));
+    }
+
+    #[tokio::test]
+    async fn test_tag_detail_for_oci_index() {
+        let index = r#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.index.v1+json","manifests":[{"mediaType":"application/vnd.oci.image.manifest.v1+json","size":300,"digest":"sha256:amd","platform":{"architecture":"amd64","os":"linux"}}]}"#;
+        let image = r#"{"schemaVersion":2,"config":{"mediaType":"application/vnd.oci.image.config.v1+json","size":10,"digest":"sha256:cfg"},"layers":[{"mediaType":"application/vnd.oci.image.layer.v1.tar+gzip","size":90,"digest":"sha256:l1"}]}"#;
+        let router = Router::new()
+            .route(
+                "/v2/app/manifests/{reference}",
+                routing::get(move |axum::extract::Path(reference): axum::extract::Path<String>| async move {
+                    let mut headers = AxumHeaderMap::new();
+                    if reference == "sha256:amd" {
+                        headers.insert("Content-Type", OCI_MANIFEST.parse().unwrap());
+                        (headers, image)
+                    } else {
+                        headers.insert("Docker-Content-Digest", "sha256:index".parse().unwrap());
+                        (headers, index)
+                    }
+                }),
+            )
+            .route(
+                "/v2/app/blobs/{digest}",
+                routing::get(|| async { r#"{"created":"2024-01-01T00:00:00Z","architecture":"amd64","os":"linux"}"# }),
+            );
+        let client = RegistryClient::new(spawn_registry(router).await, "dXNlcjpwYXNz".into());
+        let detail = client.fetch_tag_detail("app", "1.0".to_string()).await;
+        assert_eq!(detail.digest, "sha256:index");
+        assert_eq!(detail.size_bytes, 100);
+        assert_eq!(detail.architecture.as_deref(), Some("amd64"));
+        assert_eq!(detail.created_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(detail.platforms.len(), 1);
+        assert_eq!(detail.platforms[0].digest, "sha256:amd");
+        assert_eq!(detail.platforms[0].size_bytes, 100);
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_flow_is_cached() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let realm = format!("{}/token", base_url);
+        let counter = token_requests.clone();
+
+        let router = Router::new()
+            .route(
+                "/v2/_catalog",
+                routing::get(move |headers: AxumHeaderMap| async move {
+                    let mut response_headers = AxumHeaderMap::new();
+                    if headers.get("Authorization").and_then(|h| h.to_str().ok()) == Some("Bearer tok") {
+                        return (StatusCode::OK, response_headers, r#"{"repositories":["app"]}"#);
+                    }
+                    let challenge = format!(
+                        r#"Bearer realm="{}",service="test-registry",scope="registry:catalog:*""#,
+                        realm
+                    );
+                    response_headers.insert("WWW-Authenticate", challenge.parse().unwrap());
+                    (StatusCode::UNAUTHORIZED, response_headers, "")
+                }),
+            )
+            .route(
+                "/token",
+                routing::get(move |axum::extract::RawQuery(query): axum::extract::RawQuery, headers: AxumHeaderMap| async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    assert_eq!(query.as_deref(), Some("service=test-registry&scope=registry%3Acatalog%3A*"));
+                    assert_eq!(headers.get("Authorization").unwrap(), "Basic dXNlcjpwYXNz");
+                    r#"{"token":"tok","expires_in":300}"#
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let client = RegistryClient::new(base_url, "dXNlcjpwYXNz".into());
+        assert_eq!(client.fetch_catalog_names().await.unwrap().repositories, vec!["app"]);
+        assert_eq!(client.fetch_catalog_names().await.unwrap().repositories, vec!["app"]);
+        assert_eq!(token_requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repository_info_revalidates_with_tag_list() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let manifest_requests = Arc::new(AtomicUsize::new(0));
+        let counter = manifest_requests.clone();
+        let router = Router::new()
+            .route("/v2/app/tags/list", routing::get(|| async { r#"{"name":"app","tags":["1.0"]}"# }))
+            .route(
+                "/v2/app/manifests/{reference}",
+                routing::get(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    StatusCode::NOT_FOUND
+                }),
+            );
+        let client = RegistryClient::new(spawn_registry(router).await, "dXNlcjpwYXNz".into())
+            .with_cache(Duration::ZERO, true);
+
+        assert_eq!(client.fetch_repository_info("app".into()).await.tag_count, 1);
+        // La entrada caduca al instante, pero la lista de tags no cambia: no se leen manifiestos
+        assert_eq!(client.fetch_repository_info("app".into()).await.tag_count, 1);
+        assert_eq!(manifest_requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use super::manifest::Manifest;
+
+// Sobre de notificaciones que envía el Registry
+// (application/vnd.docker.distribution.events.v1+json)
+#[derive(Deserialize, Debug)]
+pub struct EventEnvelope {
+    pub events: Vec<RegistryEvent>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RegistryEvent {
+    pub id: String,
+    pub timestamp: String,
+    pub action: String,
+    pub target: EventTarget,
+    #[serde(default)]
+    pub actor: EventActor,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventTarget {
+    #[serde(rename = "mediaType", default)]
+    pub media_type: Option<String>,
+    pub digest: Option<String>,
+    pub repository: String,
+    pub tag: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct EventActor {
+    pub name: Option<String>,
+}
+
+impl RegistryEvent {
+    // Solo interesan los eventos sobre manifiestos; los de blobs (capas) son ruido
+    pub fn is_manifest_event(&self) -> bool {
+        self.target.tag.is_some()
+            || self
+                .target
+                .media_type
+                .as_deref()
+                .is_some_and(Manifest::is_manifest_type)
+            || (self.action == "delete" && self.target.media_type.is_none())
+    }
+
+    // Push y delete cambian el contenido del repositorio; pull no
+    pub fn changes_repository(&self) -> bool {
+        matches!(self.action.as_str(), "push" | "delete")
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Activity {
+    pub id: String,
+    pub timestamp: String,
+    pub action: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+    pub actor: Option<String>,
+}
+
+impl From<&RegistryEvent> for Activity {
+    fn from(event: &RegistryEvent) -> Self {
+        Self {
+            id: event.id.clone(),
+            timestamp: event.timestamp.clone(),
+            action: event.action.clone(),
+            repository: event.target.repository.clone(),
+            tag: event.target.tag.clone(),
+            digest: event.target.digest.clone(),
+            actor: event.actor.name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_envelope() {
+        let body = r#"{"events":[
+            {"id":"1","timestamp":"2024-01-01T00:00:00Z","action":"push",
+             "target":{"mediaType":"application/vnd.docker.distribution.manifest.v2+json","size":708,"digest":"sha256:abc","length":708,"repository":"team/app","url":"http://r/v2/team/app/manifests/sha256:abc","tag":"1.0"},
+             "request":{"id":"r","addr":"10.0.0.1","host":"r","method":"PUT","useragent":"docker"},
+             "actor":{"name":"ci"},"source":{"addr":"r:5000","instanceID":"i"}},
+            {"id":"2","timestamp":"2024-01-01T00:00:00Z","action":"push",
+             "target":{"mediaType":"application/vnd.docker.image.rootfs.diff.tar.gzip","digest":"sha256:layer","repository":"team/app"}},
+            {"id":"3","timestamp":"2024-01-01T00:00:00Z","action":"pull",
+             "target":{"mediaType":"application/vnd.oci.image.index.v1+json","digest":"sha256:idx","repository":"team/app"}}
+        ]}"#;
+        let envelope: EventEnvelope = serde_json::from_str(body).unwrap();
+        let [push, layer, pull] = &envelope.events[..] else { panic!("expected three events") };
+        assert!(push.is_manifest_event() && push.changes_repository());
+        assert_eq!(Activity::from(push).actor.as_deref(), Some("ci"));
+        assert!(!layer.is_manifest_event());
+        assert!(pull.is_manifest_event() && !pull.changes_repository());
+    }
+}
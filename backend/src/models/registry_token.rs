@@ -0,0 +1,210 @@
+use dashmap::DashMap;
+use reqwest::{Method, Url};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+// Margen para renovar el token antes de que caduque en el servidor
+const EXPIRY_MARGIN: Duration = Duration::from_secs(10);
+// Vida por defecto según la especificación del token server de Docker
+const DEFAULT_EXPIRES_IN: u64 = 60;
+
+// Reto `WWW-Authenticate: Bearer realm="...",service="...",scope="..."`
+#[derive(Debug, PartialEq)]
+pub struct BearerChallenge {
+    pub realm: String,
+    pub service: Option<String>,
+    pub scope: Option<String>,
+}
+
+impl BearerChallenge {
+    pub fn parse(header: &str) -> Option<Self> {
+        let (scheme, params) = header.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+
+        let mut realm = None;
+        let mut service = None;
+        let mut scope = None;
+        for (key, value) in split_params(params) {
+            match key.to_ascii_lowercase().as_str() {
+                "realm" => realm = Some(value),
+                "service" => service = Some(value),
+                "scope" => scope = Some(value),
+                _ => {}
+            }
+        }
+        Some(Self { realm: realm?, service, scope })
+    }
+
+    // URL del token server con `service` y un `scope` por cada ámbito pedido
+    pub fn token_url(&self, fallback_scope: &str) -> Option<Url> {
+        let mut url = Url::parse(&self.realm).ok()?;
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(service) = &self.service {
+                query.append_pair("service", service);
+            }
+            for scope in self.scope.as_deref().unwrap_or(fallback_scope).split(' ') {
+                if !scope.is_empty() {
+                    query.append_pair("scope", scope);
+                }
+            }
+        }
+        Some(url)
+    }
+}
+
+// Separa `clave="valor",clave=valor` respetando las comas dentro de comillas
+// (los scopes como `repository:app:pull,push` las llevan).
+fn split_params(params: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut key = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut in_quotes = false;
+
+    for c in params.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '=' if !in_value && !in_quotes => in_value = true,
+            ',' if !in_quotes => {
+                if !key.trim().is_empty() {
+                    result.push((key.trim().to_string(), value.clone()));
+                }
+                key.clear();
+                value.clear();
+                in_value = false;
+            }
+            c if in_value => value.push(c),
+            c => key.push(c),
+        }
+    }
+    if !key.trim().is_empty() {
+        result.push((key.trim().to_string(), value));
+    }
+    result
+}
+
+// Ámbito que necesita una petición según la especificación de token auth:
+// `registry:catalog:*` para el catálogo y `repository:<nombre>:<acciones>` para el resto.
+pub fn scope_for(method: &Method, url: &str) -> String {
+    let path = Url::parse(url)
+        .map(|u| u.path().to_string())
+        .unwrap_or_default();
+    let path = path.trim_start_matches('/').trim_start_matches("v2/");
+    if path.starts_with("_catalog") {
+        return "registry:catalog:*".to_string();
+    }
+
+    let segments: Vec<&str> = path.split('/').collect();
+    let name = segments
+        .iter()
+        .position(|s| matches!(*s, "manifests" | "blobs" | "tags"))
+        .map(|i| segments[..i].join("/"))
+        .unwrap_or_else(|| path.to_string());
+    let actions = match *method {
+        Method::GET | Method::HEAD => "pull",
+        Method::DELETE => "delete",
+        _ => "pull,push",
+    };
+    format!("repository:{}:{}", name, actions)
+}
+
+#[derive(Deserialize)]
+pub struct TokenResponse {
+    pub token: Option<String>,
+    pub access_token: Option<String>,
+    pub expires_in: Option<u64>,
+}
+
+impl TokenResponse {
+    pub fn into_token(self) -> Option<(String, Duration)> {
+        let expires_in = Duration::from_secs(self.expires_in.unwrap_or(DEFAULT_EXPIRES_IN));
+        self.token
+            .or(self.access_token)
+            .map(|token| (token, expires_in))
+    }
+}
+
+// Tokens Bearer por ámbito (repositorio + acciones) con su caducidad
+#[derive(Default)]
+pub struct TokenCache {
+    tokens: DashMap<String, (String, Instant)>,
+}
+
+impl TokenCache {
+    pub fn get(&self, scope: &str) -> Option<String> {
+        let entry = self.tokens.get(scope)?;
+        let (token, expires_at) = entry.value();
+        (Instant::now() < *expires_at).then(|| token.clone())
+    }
+
+    pub fn insert(&self, scope: &str, token: String, expires_in: Duration) {
+        let expires_at = Instant::now() + expires_in.saturating_sub(EXPIRY_MARGIN);
+        self.tokens.insert(scope.to_string(), (token, expires_at));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_challenge_with_commas_in_scope() {
+        let challenge = BearerChallenge::parse(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:samalba/my-app:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(challenge.realm, "https://auth.docker.io/token");
+        assert_eq!(challenge.service.as_deref(), Some("registry.docker.io"));
+        assert_eq!(challenge.scope.as_deref(), Some("repository:samalba/my-app:pull,push"));
+    }
+
+    #[test]
+    fn test_parse_basic_challenge_is_ignored() {
+        assert_eq!(BearerChallenge::parse(r#"Basic realm="Registry Realm""#), None);
+    }
+
+    #[test]
+    fn test_token_url() {
+        let challenge = BearerChallenge {
+            realm: "https://auth.example.com/token".into(),
+            service: Some("registry".into()),
+            scope: None,
+        };
+        let url = challenge.token_url("repository:team/app:pull").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://auth.example.com/token?service=registry&scope=repository%3Ateam%2Fapp%3Apull"
+        );
+    }
+
+    #[test]
+    fn test_scope_for() {
+        assert_eq!(scope_for(&Method::GET, "http://r/v2/_catalog?n=100"), "registry:catalog:*");
+        assert_eq!(
+            scope_for(&Method::GET, "http://r/v2/team/app/manifests/latest"),
+            "repository:team/app:pull"
+        );
+        assert_eq!(scope_for(&Method::HEAD, "http://r/v2/app/tags/list"), "repository:app:pull");
+        assert_eq!(
+            scope_for(&Method::DELETE, "http://r/v2/app/manifests/sha256:abc"),
+            "repository:app:delete"
+        );
+        assert_eq!(
+            scope_for(&Method::POST, "http://r/v2/app/blobs/uploads/"),
+            "repository:app:pull,push"
+        );
+    }
+
+    #[test]
+    fn test_token_cache_expiry() {
+        let cache = TokenCache::default();
+        cache.insert("a", "token".into(), Duration::from_secs(300));
+        cache.insert("b", "token".into(), Duration::from_secs(5));
+        assert_eq!(cache.get("a").as_deref(), Some("token"));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("c"), None);
+    }
+}
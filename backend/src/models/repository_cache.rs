@@ -0,0 +1,119 @@
+use dashmap::DashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+use super::repository_info::RepositoryInfo;
+
+struct CachedRepository {
+    info: RepositoryInfo,
+    // Huella de la lista de tags para revalidar sin volver a leer manifiestos
+    fingerprint: u64,
+    fetched_at: Instant,
+}
+
+// Caché de `RepositoryInfo` con caducidad por entrada
+pub struct RepositoryCache {
+    entries: DashMap<String, CachedRepository>,
+    ttl: Duration,
+}
+
+pub enum CacheLookup {
+    Fresh(RepositoryInfo),
+    // Caducada: se devuelve la huella para poder revalidar
+    Stale(u64),
+    Missing,
+}
+
+impl RepositoryCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+        }
+    }
+
+    pub fn fingerprint(tags: &[String]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        tags.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn lookup(&self, name: &str) -> CacheLookup {
+        match self.entries.get(name) {
+            Some(entry) if entry.fetched_at.elapsed() < self.ttl => CacheLookup::Fresh(entry.info.clone()),
+            Some(entry) => CacheLookup::Stale(entry.fingerprint),
+            None => CacheLookup::Missing,
+        }
+    }
+
+    pub fn insert(&self, info: RepositoryInfo, fingerprint: u64) {
+        self.entries.insert(
+            info.name.clone(),
+            CachedRepository {
+                info,
+                fingerprint,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    // La lista de tags no ha cambiado: renovamos la entrada sin recalcularla
+    pub fn touch(&self, name: &str) -> Option<RepositoryInfo> {
+        let mut entry = self.entries.get_mut(name)?;
+        entry.fetched_at = Instant::now();
+        Some(entry.info.clone())
+    }
+
+    pub fn invalidate(&self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn clear(&self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str) -> RepositoryInfo {
+        RepositoryInfo {
+            name: name.to_string(),
+            last_push: None,
+            tag_count: 1,
+        }
+    }
+
+    #[test]
+    fn test_lookup_respects_ttl() {
+        let cache = RepositoryCache::new(Duration::from_secs(60));
+        cache.insert(info("app"), 1);
+        assert!(matches!(cache.lookup("app"), CacheLookup::Fresh(_)));
+        assert!(matches!(cache.lookup("other"), CacheLookup::Missing));
+
+        let expired = RepositoryCache::new(Duration::ZERO);
+        expired.insert(info("app"), 42);
+        assert!(matches!(expired.lookup("app"), CacheLookup::Stale(42)));
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let cache = RepositoryCache::new(Duration::from_secs(60));
+        cache.insert(info("a"), 1);
+        cache.insert(info("b"), 2);
+        assert!(cache.invalidate("a"));
+        assert!(!cache.invalidate("a"));
+        assert_eq!(cache.clear(), 1);
+        assert!(matches!(cache.lookup("b"), CacheLookup::Missing));
+    }
+
+    #[test]
+    fn test_fingerprint_depends_on_tags() {
+        let tags = vec!["1.0".to_string(), "latest".to_string()];
+        assert_eq!(RepositoryCache::fingerprint(&tags), RepositoryCache::fingerprint(&tags.clone()));
+        assert_ne!(RepositoryCache::fingerprint(&tags), RepositoryCache::fingerprint(&tags[..1]));
+    }
+}
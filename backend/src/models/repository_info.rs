@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 
 #[derive(Serialize, Clone)]
@@ -6,3 +7,12 @@ pub struct RepositoryInfo {
     pub last_push: Option<String>,
     pub tag_count: usize,
 }
+
+impl RepositoryInfo {
+    pub fn last_push_time(&self) -> Option<DateTime<Utc>> {
+        self.last_push
+            .as_deref()
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.with_timezone(&Utc))
+    }
+}
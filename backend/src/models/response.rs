@@ -17,6 +17,7 @@ use super::paginable::Paginable;
 use crate::constants::DEFAULT_LIMIT;
 use crate::constants::DEFAULT_PAGE;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum CustomResponse<T>
 where
@@ -28,6 +29,7 @@ where
     Paged(PagedResponse),
 }
 
+#[allow(dead_code)]
 impl<T> CustomResponse<T>
 where
     T: Serialize,
@@ -50,6 +52,7 @@ where
 }
 
 
+#[allow(dead_code)]
 pub type PdfResponse = (HeaderMap, Vec<u8>);
 
 
@@ -78,6 +81,7 @@ impl<T> ApiResponse<T> where T: Serialize {
 }
 
 impl ApiResponse<Value> {
+    #[allow(dead_code)]
     pub fn ok(msg: &str) -> Self {
         Self::new(StatusCode::OK, msg, None)
     }
@@ -162,6 +166,8 @@ impl Pagination {
         let limit = params.limit().unwrap_or(DEFAULT_LIMIT);
         let page = params.page().unwrap_or(DEFAULT_PAGE);
         let total_pages = (count as f32 / limit as f32).ceil() as u32;
+        // La ruta base puede traer ya parámetros (orden, filtros...)
+        let separator = if base_path.contains('?') { '&' } else { '?' };
 
         Self {
             page,
@@ -169,12 +175,12 @@ impl Pagination {
             pages: total_pages,
             records: count,
             prev: if page > 1 {
-                Some(format!("{}?page={}&limit={}", base_path, page - 1, limit))
+                Some(format!("{}{}page={}&limit={}", base_path, separator, page - 1, limit))
             } else {
                 None
             },
             next: if page < total_pages {
-                Some(format!("{}?page={}&limit={}", base_path, page + 1, limit))
+                Some(format!("{}{}page={}&limit={}", base_path, separator, page + 1, limit))
             } else {
                 None
             },
@@ -209,11 +215,13 @@ impl IntoResponse for PagedResponse {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct EmptyResponse {
     pub status: StatusCode,
     pub message: String,
 }
+#[allow(dead_code)]
 impl EmptyResponse {
     pub fn create(status: StatusCode, message: &str) -> Response<Body> {
         Response::builder()
@@ -275,6 +283,14 @@ mod tests {
         assert_eq!(pagination.prev, Some("/test?page=9&limit=10".to_string()));
         assert_eq!(pagination.next, None);
     }
+
+    #[test]
+    fn test_pagination_base_path_with_query() {
+        let params = TestParams { page: Some(2), limit: Some(10) };
+        let pagination = Pagination::new(&params, 100, "/test?sort=name");
+        assert_eq!(pagination.prev, Some("/test?sort=name&page=1&limit=10".to_string()));
+        assert_eq!(pagination.next, Some("/test?sort=name&page=3&limit=10".to_string()));
+    }
 }
 
 
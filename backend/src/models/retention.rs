@@ -0,0 +1,444 @@
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tracing::info;
+use super::glob::glob_to_regex;
+use super::registry_client::RegistryClient;
+use super::tag_detail::TagDetail;
+
+fn default_protected() -> Vec<String> {
+    vec!["latest".to_string()]
+}
+
+// Regla de retención para los repositorios que casan con `repository` (glob).
+// Un tag se borra solo si no lo conserva ninguna de las reglas:
+// - es `latest` o su nombre está en `protected`
+// - casa con alguna expresión de `keep_regex` (p.ej. semver)
+// - está entre los `keep_last` más recientes por `created_at`
+// - es más reciente que `older_than_days`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetentionPolicy {
+    pub repository: String,
+    #[serde(default)]
+    pub keep_last: Option<usize>,
+    #[serde(default)]
+    pub keep_regex: Vec<String>,
+    #[serde(default)]
+    pub older_than_days: Option<u32>,
+    #[serde(default = "default_protected")]
+    pub protected: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepReason {
+    Protected,
+    MatchesRegex,
+    KeepLast,
+    TooRecent,
+    UnknownDate,
+    UnknownDigest,
+    // Otro tag conservado apunta al mismo manifiesto: borrar el digest lo eliminaría también
+    SharedDigest,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct KeptTag {
+    pub tag: String,
+    pub reason: KeepReason,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PlannedDeletion {
+    pub digest: String,
+    pub tags: Vec<String>,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RetentionPlan {
+    pub repository: String,
+    pub policy: RetentionPolicy,
+    pub delete: Vec<PlannedDeletion>,
+    pub keep: Vec<KeptTag>,
+    pub reclaimable_bytes: u64,
+    // Si falla algo que impide decidir, el repositorio se salta entero
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RetentionResult {
+    pub repository: String,
+    pub deleted: Vec<PlannedDeletion>,
+    pub errors: Vec<String>,
+}
+
+impl RetentionPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        glob_to_regex(&self.repository).map_err(|e| format!("Patrón de repositorio no válido: {}", e))?;
+        for expression in &self.keep_regex {
+            Regex::new(expression).map_err(|e| format!("Expresión regular no válida {}: {}", expression, e))?;
+        }
+        // Sin ninguno de los dos criterios la política borraría todo lo no protegido
+        if self.keep_last.is_none() && self.older_than_days.is_none() {
+            return Err(format!(
+                "La política de {} necesita keep_last u older_than_days",
+                self.repository
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn matches_repository(&self, repo: &str) -> bool {
+        glob_to_regex(&self.repository).is_ok_and(|re| re.is_match(repo))
+    }
+
+    pub fn plan(&self, repo: &str, tags: &[TagDetail], now: DateTime<Utc>) -> RetentionPlan {
+        // Un tag sin digest podría apuntar al mismo manifiesto que un candidato,
+        // y borrarlo se llevaría el tag por delante
+        let unresolved: Vec<&str> = tags
+            .iter()
+            .filter(|t| !t.digest.starts_with("sha256:"))
+            .map(|t| t.name.as_str())
+            .collect();
+        if !unresolved.is_empty() {
+            return RetentionPlan {
+                repository: repo.to_string(),
+                policy: self.clone(),
+                delete: Vec::new(),
+                keep: unresolved
+                    .iter()
+                    .map(|tag| KeptTag { tag: tag.to_string(), reason: KeepReason::UnknownDigest })
+                    .collect(),
+                reclaimable_bytes: 0,
+                error: Some(format!(
+                    "No se pudo obtener el digest de {}; no se borra nada en {}",
+                    unresolved.join(", "),
+                    repo
+                )),
+            };
+        }
+
+        let keep_regex: Vec<Regex> = self
+            .keep_regex
+            .iter()
+            .filter_map(|r| Regex::new(r).ok())
+            .collect();
+
+        // Los `keep_last` tags más recientes
+        let mut by_date: Vec<&TagDetail> = tags.iter().filter(|t| t.created_time().is_some()).collect();
+        by_date.sort_by_key(|t| std::cmp::Reverse(t.created_time()));
+        let newest: Vec<&str> = by_date
+            .iter()
+            .take(self.keep_last.unwrap_or(0))
+            .map(|t| t.name.as_str())
+            .collect();
+        let cutoff = self.older_than_days.map(|days| now - Duration::days(days.into()));
+
+        let mut keep = Vec::new();
+        let mut candidates: Vec<&TagDetail> = Vec::new();
+        for tag in tags {
+            let reason = if tag.name == "latest" || self.protected.contains(&tag.name) {
+                Some(KeepReason::Protected)
+            } else if keep_regex.iter().any(|r| r.is_match(&tag.name)) {
+                Some(KeepReason::MatchesRegex)
+            } else if tag.created_time().is_none() {
+                Some(KeepReason::UnknownDate)
+            } else if newest.contains(&tag.name.as_str()) {
+                Some(KeepReason::KeepLast)
+            } else if cutoff.is_some_and(|c| tag.created_time().is_some_and(|t| t > c)) {
+                Some(KeepReason::TooRecent)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => keep.push(KeptTag { tag: tag.name.clone(), reason }),
+                None => candidates.push(tag),
+            }
+        }
+
+        // Borrar un manifiesto elimina todos los tags que apuntan a él, así que un
+        // digest solo se borra si ninguno de sus tags se conserva.
+        let kept_digests: Vec<&str> = tags
+            .iter()
+            .filter(|t| keep.iter().any(|k| k.tag == t.name))
+            .map(|t| t.digest.as_str())
+            .collect();
+        let mut delete: BTreeMap<String, PlannedDeletion> = BTreeMap::new();
+        for tag in candidates {
+            if kept_digests.contains(&tag.digest.as_str()) {
+                keep.push(KeptTag { tag: tag.name.clone(), reason: KeepReason::SharedDigest });
+                continue;
+            }
+            delete
+                .entry(tag.digest.clone())
+                .or_insert_with(|| PlannedDeletion {
+                    digest: tag.digest.clone(),
+                    tags: Vec::new(),
+                    size_bytes: tag.size_bytes,
+                })
+                .tags
+                .push(tag.name.clone());
+        }
+
+        let delete: Vec<PlannedDeletion> = delete.into_values().collect();
+        RetentionPlan {
+            repository: repo.to_string(),
+            policy: self.clone(),
+            reclaimable_bytes: delete.iter().map(|d| d.size_bytes).sum(),
+            delete,
+            keep,
+            error: None,
+        }
+    }
+}
+
+// Huella de lo que borrarían los planes. `apply` la compara con la de la
+// simulación revisada para no borrar algo distinto de lo que se vio.
+pub fn plan_hash(plans: &[RetentionPlan]) -> String {
+    let deletions: Vec<_> = plans.iter().map(|p| (&p.repository, &p.delete)).collect();
+    let content = serde_json::to_vec(&deletions).unwrap_or_default();
+    hex::encode(Sha256::digest(&content))
+}
+
+// Políticas de retención persistidas en un fichero JSON
+pub struct RetentionStore {
+    path: Option<PathBuf>,
+    policies: RwLock<Vec<RetentionPolicy>>,
+}
+
+impl RetentionStore {
+    // Un fichero que no se puede leer es un error: el siguiente guardado lo sobrescribiría
+    pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+        let policies: Vec<RetentionPolicy> = match path.as_ref().filter(|p| p.exists()) {
+            Some(p) => {
+                let content = std::fs::read_to_string(p)
+                    .map_err(|e| format!("No se pudo leer {}: {}", p.display(), e))?;
+                let policies: Vec<RetentionPolicy> = serde_json::from_str(&content)
+                    .map_err(|e| format!("Políticas de retención no válidas en {}: {}", p.display(), e))?;
+                for policy in &policies {
+                    policy
+                        .validate()
+                        .map_err(|e| format!("Políticas de retención no válidas en {}: {}", p.display(), e))?;
+                }
+                policies
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path,
+            policies: RwLock::new(policies),
+        })
+    }
+
+    pub fn policies(&self) -> Vec<RetentionPolicy> {
+        self.policies.read().unwrap().clone()
+    }
+
+    pub fn replace(&self, policies: Vec<RetentionPolicy>) -> Result<(), String> {
+        for policy in &policies {
+            policy.validate()?;
+        }
+        if let Some(path) = &self.path {
+            let content = serde_json::to_string_pretty(&policies).map_err(|e| e.to_string())?;
+            std::fs::write(path, content).map_err(|e| format!("No se pudo guardar {}: {}", path.display(), e))?;
+        }
+        *self.policies.write().unwrap() = policies;
+        Ok(())
+    }
+
+    // Primera política que se aplica al repositorio
+    pub fn policy_for(&self, repo: &str) -> Option<RetentionPolicy> {
+        self.policies
+            .read()
+            .unwrap()
+            .iter()
+            .find(|p| p.matches_repository(repo))
+            .cloned()
+    }
+}
+
+// Calcula los planes de retención de todos los repositorios con política
+// (o solo de `only`, si se indica)
+pub async fn plan_retention(
+    client: &RegistryClient,
+    store: &RetentionStore,
+    only: Option<&str>,
+) -> Result<Vec<RetentionPlan>, (axum::http::StatusCode, String)> {
+    let repositories = match only {
+        Some(repo) => vec![repo.to_string()],
+        None => client.list_repositories().await?,
+    };
+
+    let now = Utc::now();
+    let mut plans = Vec::new();
+    for repo in repositories {
+        let Some(policy) = store.policy_for(&repo) else {
+            continue;
+        };
+        let tags = client.list_tag_details(&repo).await?;
+        plans.push(policy.plan(&repo, &tags, now));
+    }
+    Ok(plans)
+}
+
+// Ejecuta los borrados de los planes
+pub async fn apply_retention(client: &RegistryClient, plans: Vec<RetentionPlan>) -> Vec<RetentionResult> {
+    let mut results = Vec::new();
+    for plan in plans {
+        let mut result = RetentionResult {
+            repository: plan.repository.clone(),
+            deleted: Vec::new(),
+            errors: plan.error.into_iter().collect(),
+        };
+        for deletion in plan.delete {
+            match client.delete_digest(&plan.repository, &deletion.digest).await {
+                Ok(()) => {
+                    info!("Retención: borrado {}@{} ({:?})", plan.repository, deletion.digest, deletion.tags);
+                    result.deleted.push(deletion);
+                }
+                Err((_, message)) => result.errors.push(format!("{}: {}", deletion.digest, message)),
+            }
+        }
+        results.push(result);
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, digest: &str, days_ago: Option<i64>, now: DateTime<Utc>) -> TagDetail {
+        let mut detail = TagDetail::basic(name.to_string(), digest.to_string(), 10);
+        detail.created_at = days_ago.map(|d| (now - Duration::days(d)).to_rfc3339());
+        detail
+    }
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            repository: "team/*".to_string(),
+            keep_last: Some(2),
+            keep_regex: vec![r"^v\d+\.\d+\.\d+$".to_string()],
+            older_than_days: Some(30),
+            protected: default_protected(),
+        }
+    }
+
+    fn deleted_tags(plan: &RetentionPlan) -> Vec<&str> {
+        let mut tags: Vec<&str> = plan.delete.iter().flat_map(|d| d.tags.iter().map(String::as_str)).collect();
+        tags.sort();
+        tags
+    }
+
+    #[test]
+    fn test_plan_applies_every_rule() {
+        let now = Utc::now();
+        let tags = vec![
+            tag("latest", "sha256:l", Some(100), now),
+            tag("v1.0.0", "sha256:v", Some(200), now),
+            tag("ci-1", "sha256:1", Some(90), now),
+            tag("ci-2", "sha256:2", Some(60), now),
+            tag("ci-3", "sha256:3", Some(10), now),
+            tag("ci-4", "sha256:4", Some(5), now),
+            tag("ci-5", "sha256:5", Some(1), now),
+        ];
+        let plan = policy().plan("team/app", &tags, now);
+        assert_eq!(deleted_tags(&plan), vec!["ci-1", "ci-2"]);
+        let reason = |name: &str| plan.keep.iter().find(|k| k.tag == name).map(|k| k.reason);
+        assert_eq!(reason("latest"), Some(KeepReason::Protected));
+        assert_eq!(reason("v1.0.0"), Some(KeepReason::MatchesRegex));
+        assert_eq!(reason("ci-5"), Some(KeepReason::KeepLast));
+        assert_eq!(reason("ci-3"), Some(KeepReason::TooRecent));
+        assert_eq!(plan.reclaimable_bytes, 20);
+    }
+
+    #[test]
+    fn test_plan_skips_repository_with_unresolved_tags() {
+        let now = Utc::now();
+        let tags = vec![
+            tag("ci-1", "sha256:1", Some(90), now),
+            tag("broken", "n/a", None, now),
+        ];
+        let plan = policy().plan("team/app", &tags, now);
+        assert!(plan.delete.is_empty());
+        assert!(plan.error.is_some());
+        assert_eq!(plan.keep[0].reason, KeepReason::UnknownDigest);
+    }
+
+    #[test]
+    fn test_latest_is_kept_with_custom_protected() {
+        let now = Utc::now();
+        let tags = vec![tag("latest", "sha256:l", Some(100), now), tag("stable", "sha256:s", Some(100), now)];
+        let mut policy = policy();
+        policy.keep_last = None;
+        policy.protected = vec!["stable".to_string()];
+        let plan = policy.plan("team/app", &tags, now);
+        assert!(plan.delete.is_empty());
+        assert!(plan.keep.iter().all(|k| k.reason == KeepReason::Protected));
+    }
+
+    #[test]
+    fn test_plan_hash_changes_with_deletions() {
+        let now = Utc::now();
+        let tags = vec![tag("ci-1", "sha256:1", Some(90), now), tag("ci-2", "sha256:2", Some(60), now)];
+        let mut policy = policy();
+        policy.keep_last = None;
+        let before = plan_hash(&[policy.plan("team/app", &tags, now)]);
+        assert_eq!(before, plan_hash(&[policy.plan("team/app", &tags, now)]));
+        let after = plan_hash(&[policy.plan("team/app", &tags[..1], now)]);
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn test_plan_never_deletes_digest_shared_with_kept_tag() {
+        let now = Utc::now();
+        let tags = vec![
+            tag("latest", "sha256:same", Some(100), now),
+            tag("ci-1", "sha256:same", Some(100), now),
+            tag("ci-2", "sha256:other", Some(100), now),
+            tag("ci-3", "sha256:other", Some(100), now),
+        ];
+        let mut policy = policy();
+        policy.keep_last = None;
+        let plan = policy.plan("team/app", &tags, now);
+        assert_eq!(plan.delete.len(), 1);
+        assert_eq!(plan.delete[0].digest, "sha256:other");
+        assert_eq!(deleted_tags(&plan), vec!["ci-2", "ci-3"]);
+        assert!(plan.keep.iter().any(|k| k.tag == "ci-1" && k.reason == KeepReason::SharedDigest));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(policy().validate().is_ok());
+        let mut without_criteria = policy();
+        without_criteria.keep_last = None;
+        without_criteria.older_than_days = None;
+        assert!(without_criteria.validate().is_err());
+        let mut bad_regex = policy();
+        bad_regex.keep_regex = vec!["(".to_string()];
+        assert!(bad_regex.validate().is_err());
+    }
+
+    #[test]
+    fn test_policy_defaults_protect_latest() {
+        let policy: RetentionPolicy = serde_json::from_str(r#"{"repository":"*","keep_last":5}"#).unwrap();
+        assert_eq!(policy.protected, vec!["latest"]);
+        assert!(policy.matches_repository("team/app"));
+    }
+
+    #[test]
+    fn test_invalid_file_fails_to_load() {
+        let path = std::env::temp_dir().join(format!("retention-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "[{\"repository\": \"app/*\"}]").unwrap();
+        assert!(RetentionStore::load(Some(path.clone())).is_err());
+        // El fichero sigue intacto
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[{\"repository\": \"app/*\"}]");
+        std::fs::remove_file(path).unwrap();
+    }
+}
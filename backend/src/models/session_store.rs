@@ -0,0 +1,163 @@
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+// Sesión de login: agrupa el JWT de acceso (claim `sid`) y su refresh token
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub username: String,
+    // Solo se guarda el hash del refresh token vigente
+    refresh_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshError {
+    Unknown,
+    Expired,
+    // Se ha presentado un refresh token ya rotado: la sesión se revoca entera
+    Reused,
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn random_secret() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+// Sesiones activas en memoria. Reiniciar el servidor cierra todas las sesiones.
+pub struct SessionStore {
+    sessions: DashMap<String, Session>,
+    pub access_ttl: Duration,
+    pub refresh_ttl: Duration,
+    // Marca las cookies como `Secure` (desactivar solo en desarrollo sobre http)
+    pub secure_cookies: bool,
+}
+
+impl SessionStore {
+    pub fn new(access_ttl: Duration, refresh_ttl: Duration, secure_cookies: bool) -> Self {
+        Self {
+            sessions: DashMap::new(),
+            access_ttl,
+            refresh_ttl,
+            secure_cookies,
+        }
+    }
+
+    // Abre una sesión y devuelve su id y el refresh token (`<id>.<secreto>`)
+    pub fn create(&self, username: &str) -> (String, String) {
+        let now = Utc::now();
+        self.sessions.retain(|_, session| session.expires_at > now);
+
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let secret = random_secret();
+        self.sessions.insert(
+            id.clone(),
+            Session {
+                id: id.clone(),
+                username: username.to_string(),
+                refresh_hash: hash_secret(&secret),
+                expires_at: now + self.refresh_ttl,
+            },
+        );
+        (id.clone(), format!("{}.{}", id, secret))
+    }
+
+    // Canjea un refresh token por uno nuevo; el anterior deja de valer
+    pub fn rotate(&self, refresh_token: &str) -> Result<(Session, String), RefreshError> {
+        let (id, secret) = refresh_token.split_once('.').ok_or(RefreshError::Unknown)?;
+        let mut session = self.sessions.get_mut(id).ok_or(RefreshError::Unknown)?;
+        if session.expires_at <= Utc::now() {
+            drop(session);
+            self.sessions.remove(id);
+            return Err(RefreshError::Expired);
+        }
+        if session.refresh_hash != hash_secret(secret) {
+            warn!("Refresh token reuse for session {} of {}", id, session.username);
+            drop(session);
+            self.sessions.remove(id);
+            return Err(RefreshError::Reused);
+        }
+        let secret = random_secret();
+        session.refresh_hash = hash_secret(&secret);
+        session.expires_at = Utc::now() + self.refresh_ttl;
+        Ok((session.clone(), format!("{}.{}", id, secret)))
+    }
+
+    pub fn is_active(&self, id: &str) -> bool {
+        self.sessions
+            .get(id)
+            .is_some_and(|session| session.expires_at > Utc::now())
+    }
+
+    // Id de sesión de un refresh token, sin validarlo
+    pub fn session_id(refresh_token: &str) -> Option<&str> {
+        refresh_token.split_once('.').map(|(id, _)| id)
+    }
+
+    pub fn revoke(&self, id: &str) -> bool {
+        self.sessions.remove(id).is_some()
+    }
+
+    // Cierra todas las sesiones de un usuario (p.ej. al deshabilitarlo)
+    pub fn revoke_user(&self, username: &str) -> usize {
+        self.revoke_others(username, None)
+    }
+
+    // Cierra las sesiones de un usuario salvo `keep`, la desde la que él mismo hace el cambio
+    pub fn revoke_others(&self, username: &str, keep: Option<&str>) -> usize {
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|id, session| session.username != username || keep == Some(id.as_str()));
+        let revoked = before - self.sessions.len();
+        if revoked > 0 {
+            info!("Revoked {} sessions of {}", revoked, username);
+        }
+        revoked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> SessionStore {
+        SessionStore::new(Duration::minutes(15), Duration::days(7), true)
+    }
+
+    #[test]
+    fn test_rotation_invalidates_previous_token() {
+        let store = store();
+        let (id, first) = store.create("admin");
+        assert!(store.is_active(&id));
+
+        let (session, second) = store.rotate(&first).unwrap();
+        assert_eq!(session.username, "admin");
+        assert_ne!(first, second);
+
+        // Reutilizar el token rotado revoca la sesión, también para el token nuevo
+        assert_eq!(store.rotate(&first).unwrap_err(), RefreshError::Reused);
+        assert!(!store.is_active(&id));
+        assert_eq!(store.rotate(&second).unwrap_err(), RefreshError::Unknown);
+    }
+
+    #[test]
+    fn test_expired_and_revoked_sessions() {
+        let expired = SessionStore::new(Duration::minutes(15), Duration::seconds(-1), true);
+        let (_, token) = expired.create("admin");
+        assert_eq!(expired.rotate(&token).unwrap_err(), RefreshError::Expired);
+
+        let store = store();
+        let (id, token) = store.create("admin");
+        store.create("admin");
+        assert_eq!(SessionStore::session_id(&token), Some(id.as_str()));
+        assert!(store.revoke(&id));
+        assert_eq!(store.rotate(&token).unwrap_err(), RefreshError::Unknown);
+        assert_eq!(store.revoke_user("admin"), 1);
+        assert_eq!(store.rotate("garbage").unwrap_err(), RefreshError::Unknown);
+    }
+}
@@ -0,0 +1,31 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn apply(&self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    // Orden de una lista de nombres, antes de paginar
+    pub fn sort_names(&self, names: &mut [String]) {
+        names.sort_by(|a, b| self.apply(a.cmp(b)));
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,13 +9,31 @@ pub struct TagDetail {
     pub created_at: Option<String>,
     pub architecture: Option<String>,
     pub os: Option<String>,
+    pub platforms: Vec<PlatformDetail>,
+}
+
+// Una plataforma soportada por el tag. Las imágenes simples tienen una sola;
+// los manifest lists / índices OCI tienen una por manifiesto hijo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformDetail {
+    pub os: String,
+    pub architecture: String,
+    pub variant: Option<String>,
+    pub digest: String,
+    pub size_bytes: u64,
 }
 
 impl TagDetail {
     pub fn empty(name: String) -> Self {
-        Self { name, digest: "n/a".into(), size_bytes: 0, created_at: None, architecture: None, os: None }
+        Self { name, digest: "n/a".into(), size_bytes: 0, created_at: None, architecture: None, os: None, platforms: Vec::new() }
     }
     pub fn basic(name: String, digest: String, size_bytes: u64) -> Self {
-        Self { name, digest, size_bytes, created_at: None, architecture: None, os: None }
+        Self { name, digest, size_bytes, created_at: None, architecture: None, os: None, platforms: Vec::new() }
+    }
+    pub fn created_time(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+            .as_deref()
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.with_timezone(&Utc))
     }
 }
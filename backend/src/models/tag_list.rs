@@ -1,6 +1,15 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Deserialize, Serialize)]
 pub struct TagList {
+    // El Registry devuelve `"tags": null` cuando se han borrado todos los tags
+    #[serde(default, deserialize_with = "null_as_empty")]
     pub tags: Vec<String>,
 }
+
+fn null_as_empty<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<Vec<String>>::deserialize(deserializer)?.unwrap_or_default())
+}
@@ -0,0 +1,154 @@
+use regex::Regex;
+use serde::Deserialize;
+use super::Paginable;
+use super::paginable::QueryParams;
+use super::glob::glob_to_regex;
+use super::sort_order::SortOrder;
+use super::tag_detail::TagDetail;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSort {
+    #[default]
+    Name,
+    CreatedAt,
+    SizeBytes,
+}
+
+impl TagSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagSort::Name => "name",
+            TagSort::CreatedAt => "created_at",
+            TagSort::SizeBytes => "size_bytes",
+        }
+    }
+}
+
+// Parámetros de `GET /api/v1/registry/{repo}/tags?page=&limit=&sort=&order=&q=&regex=`.
+// `q` es un patrón glob y `regex` una expresión regular; si vienen los dos se aplican ambos.
+#[derive(Deserialize, Debug, Default)]
+pub struct TagQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub sort: TagSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub q: Option<String>,
+    pub regex: Option<String>,
+}
+
+impl Paginable for TagQuery {
+    fn page(&self) -> Option<u32> {
+        self.page
+    }
+
+    fn limit(&self) -> Option<u32> {
+        self.limit
+    }
+}
+
+pub struct TagFilter {
+    glob: Option<Regex>,
+    regex: Option<Regex>,
+}
+
+impl TagFilter {
+    pub fn matches(&self, name: &str) -> bool {
+        self.glob.as_ref().is_none_or(|g| g.is_match(name))
+            && self.regex.as_ref().is_none_or(|r| r.is_match(name))
+    }
+}
+
+impl TagQuery {
+    pub fn filter(&self) -> Result<TagFilter, String> {
+        let glob = match self.q.as_deref().filter(|q| !q.is_empty()) {
+            Some(q) => Some(glob_to_regex(q).map_err(|e| format!("Patrón glob no válido: {}", e))?),
+            None => None,
+        };
+        let regex = match self.regex.as_deref().filter(|r| !r.is_empty()) {
+            Some(r) => Some(Regex::new(r).map_err(|e| format!("Expresión regular no válida: {}", e))?),
+            None => None,
+        };
+        Ok(TagFilter { glob, regex })
+    }
+
+    // Ordenar por nombre no necesita el manifiesto, así que se puede paginar
+    // antes de enriquecer los tags.
+    pub fn sorts_by_name(&self) -> bool {
+        self.sort == TagSort::Name
+    }
+
+    pub fn sort(&self, tags: &mut [TagDetail]) {
+        tags.sort_by(|a, b| {
+            let ordering = match self.sort {
+                TagSort::Name => a.name.cmp(&b.name),
+                TagSort::CreatedAt => a.created_time().cmp(&b.created_time()),
+                TagSort::SizeBytes => a.size_bytes.cmp(&b.size_bytes),
+            };
+            self.order.apply(ordering.then_with(|| a.name.cmp(&b.name)))
+        });
+    }
+
+    pub fn base_path(&self, path: &str) -> String {
+        QueryParams::new()
+            .add("sort", self.sort.as_str())
+            .add("order", self.order.as_str())
+            .add_opt("q", self.q.as_deref())
+            .add_opt("regex", self.regex.as_deref())
+            .path(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, created_at: Option<&str>, size_bytes: u64) -> TagDetail {
+        let mut detail = TagDetail::basic(name.to_string(), "sha256:x".to_string(), size_bytes);
+        detail.created_at = created_at.map(|c| c.to_string());
+        detail
+    }
+
+    #[test]
+    fn test_filter_glob_and_regex() {
+        let query = TagQuery {
+            q: Some("v1.*".into()),
+            regex: Some(r"^v\d+\.\d+\.\d+$".into()),
+            ..Default::default()
+        };
+        let filter = query.filter().unwrap();
+        assert!(filter.matches("v1.2.3"));
+        assert!(!filter.matches("v1.2.3-rc1"));
+        assert!(!filter.matches("v2.0.0"));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_rejected() {
+        let query = TagQuery { regex: Some("(".into()), ..Default::default() };
+        assert!(query.filter().is_err());
+    }
+
+    #[test]
+    fn test_sort_by_created_at_desc() {
+        let query = TagQuery { sort: TagSort::CreatedAt, order: SortOrder::Desc, ..Default::default() };
+        let mut tags = vec![
+            tag("old", Some("2023-01-01T00:00:00Z"), 1),
+            tag("new", Some("2024-01-01T00:00:00.123456789Z"), 1),
+            tag("unknown", None, 1),
+        ];
+        query.sort(&mut tags);
+        let names: Vec<_> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["new", "old", "unknown"]);
+    }
+
+    #[test]
+    fn test_sort_by_size() {
+        let query = TagQuery { sort: TagSort::SizeBytes, ..Default::default() };
+        let mut tags = vec![tag("b", None, 30), tag("a", None, 10), tag("c", None, 20)];
+        query.sort(&mut tags);
+        let names: Vec<_> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "c", "b"]);
+    }
+}
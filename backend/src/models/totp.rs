@@ -0,0 +1,241 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const DIGITS: usize = 6;
+const STEP: u64 = 30;
+const RECOVERY_CODES: usize = 10;
+// Vida del paso intermedio del login y códigos erróneos que admite
+const CHALLENGE_TTL: Duration = Duration::from_secs(300);
+const CHALLENGE_ATTEMPTS: u32 = 5;
+
+fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
+
+// Los códigos de recuperación se aceptan con o sin guiones y en cualquier caja
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+// Segundo factor de una cuenta, guardado junto a ella en el fichero de usuarios.
+// El secreto tiene que estar en claro para poder calcular los códigos.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TotpSettings {
+    secret: String,
+    // Falso hasta que el usuario confirma el alta con un primer código
+    pub enabled: bool,
+    #[serde(default)]
+    recovery_codes: Vec<String>,
+    // Último paso de 30 s aceptado: un código no se puede usar dos veces
+    #[serde(default)]
+    last_step: u64,
+}
+
+impl TotpSettings {
+    pub fn generate() -> Self {
+        Self {
+            secret: Secret::generate_secret().to_encoded().to_string(),
+            enabled: false,
+            recovery_codes: Vec::new(),
+            last_step: 0,
+        }
+    }
+
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    fn totp(&self, issuer: Option<&str>, username: &str) -> Result<TOTP, String> {
+        let secret = Secret::Encoded(self.secret.clone()).to_bytes().map_err(|e| format!("{:?}", e))?;
+        TOTP::new(
+            Algorithm::SHA1,
+            DIGITS,
+            1,
+            STEP,
+            secret,
+            issuer.map(str::to_string),
+            username.to_string(),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    // URI `otpauth://` para dar de alta la cuenta en la app de autenticación
+    pub fn otpauth_uri(&self, issuer: &str, username: &str) -> Result<String, String> {
+        Ok(self.totp(Some(issuer), username)?.get_url())
+    }
+
+    // Comprueba un código TOTP (admite un paso de desfase) o de recuperación.
+    // Los códigos de recuperación se gastan al usarlos.
+    pub fn verify(&mut self, code: &str) -> bool {
+        let code = code.trim();
+        if code.len() == DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+            return self.verify_totp(code, now());
+        }
+        let hash = hash_code(&normalize(code));
+        match self.recovery_codes.iter().position(|h| *h == hash) {
+            Some(index) => {
+                self.recovery_codes.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn verify_totp(&mut self, code: &str, time: u64) -> bool {
+        let Ok(totp) = self.totp(None, "") else {
+            return false;
+        };
+        let step = time / STEP;
+        let matched = [step.saturating_sub(1), step, step + 1]
+            .into_iter()
+            .filter(|s| *s > self.last_step)
+            .find(|s| totp.generate(s * STEP) == code);
+        match matched {
+            Some(step) => {
+                self.last_step = step;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Genera códigos de recuperación nuevos y devuelve su valor en claro, que no se vuelve a mostrar
+    pub fn reset_recovery_codes(&mut self) -> Vec<String> {
+        let codes: Vec<String> = (0..RECOVERY_CODES)
+            .map(|_| {
+                let raw = uuid::Uuid::new_v4().simple().to_string();
+                format!("{}-{}-{}", &raw[0..4], &raw[4..8], &raw[8..12])
+            })
+            .collect();
+        self.recovery_codes = codes.iter().map(|c| hash_code(&normalize(c))).collect();
+        codes
+    }
+
+    pub fn recovery_codes_left(&self) -> usize {
+        self.recovery_codes.len()
+    }
+}
+
+struct Challenge {
+    username: String,
+    expires_at: Instant,
+    attempts: u32,
+}
+
+// Logins pendientes del código TOTP: la contraseña ya se ha comprobado
+// pero todavía no se ha emitido el JWT
+pub struct TotpProvider {
+    pub issuer: String,
+    challenges: DashMap<String, Challenge>,
+}
+
+impl TotpProvider {
+    pub fn new(issuer: &str) -> Self {
+        Self {
+            issuer: issuer.to_string(),
+            challenges: DashMap::new(),
+        }
+    }
+
+    pub fn challenge(&self, username: &str) -> String {
+        let now = Instant::now();
+        self.challenges.retain(|_, c| c.expires_at > now);
+        let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        self.challenges.insert(
+            token.clone(),
+            Challenge {
+                username: username.to_string(),
+                expires_at: now + CHALLENGE_TTL,
+                attempts: 0,
+            },
+        );
+        token
+    }
+
+    // Usuario de un paso pendiente y vigente
+    pub fn pending(&self, token: &str) -> Option<String> {
+        self.challenges
+            .get(token)
+            .filter(|c| c.expires_at > Instant::now())
+            .map(|c| c.username.clone())
+    }
+
+    // Código erróneo: tras varios fallos hay que volver a empezar con la contraseña
+    pub fn failed(&self, token: &str) {
+        let exhausted = self.challenges.get_mut(token).is_some_and(|mut c| {
+            c.attempts += 1;
+            c.attempts >= CHALLENGE_ATTEMPTS
+        });
+        if exhausted {
+            self.challenges.remove(token);
+        }
+    }
+
+    pub fn complete(&self, token: &str) {
+        self.challenges.remove(token);
+    }
+}
+
+#[cfg(test)]
+pub fn current_code(settings: &TotpSettings) -> String {
+    settings.totp(None, "").unwrap().generate(now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_single_use() {
+        let mut settings = TotpSettings::generate();
+        let uri = settings.otpauth_uri("registryui", "admin").unwrap();
+        assert!(uri.starts_with("otpauth://totp/registryui:admin?"));
+        assert!(uri.contains(&format!("secret={}", settings.secret())));
+
+        let time = 1_700_000_000;
+        let code = settings.totp(None, "").unwrap().generate(time);
+        assert!(settings.verify_totp(&code, time));
+        // El mismo código no se acepta otra vez
+        assert!(!settings.verify_totp(&code, time));
+        let next = settings.totp(None, "").unwrap().generate(time + STEP);
+        assert!(settings.verify_totp(&next, time));
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let mut settings = TotpSettings::generate();
+        let codes = settings.reset_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        assert!(settings.verify(&codes[0].to_uppercase()));
+        assert!(!settings.verify(&codes[0]));
+        assert!(settings.verify(&codes[1].replace('-', "")));
+        assert_eq!(settings.recovery_codes_left(), RECOVERY_CODES - 2);
+        assert!(!settings.verify("not-a-code"));
+    }
+
+    #[test]
+    fn test_challenge_attempts() {
+        let provider = TotpProvider::new("registryui");
+        let token = provider.challenge("admin");
+        assert_eq!(provider.pending(&token).as_deref(), Some("admin"));
+        for _ in 0..CHALLENGE_ATTEMPTS {
+            provider.failed(&token);
+        }
+        assert_eq!(provider.pending(&token), None);
+
+        let token = provider.challenge("admin");
+        provider.complete(&token);
+        assert_eq!(provider.pending(&token), None);
+        assert_eq!(provider.pending("unknown"), None);
+    }
+}
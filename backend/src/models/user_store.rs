@@ -0,0 +1,501 @@
+use axum::http::StatusCode;
+use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use super::totp::TotpSettings;
+
+// Roles ordenados de menos a más permisos: cada rol incluye los anteriores
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    pub fn allows(&self, required: Role) -> bool {
+        *self >= required
+    }
+}
+
+pub fn parse_role(value: &str) -> Result<Role, String> {
+    serde_json::from_value(serde_json::Value::String(value.trim().to_lowercase()))
+        .map_err(|_| format!("Rol desconocido: '{}'", value))
+}
+
+// Convierte `grupo=rol,grupo=rol` (OIDC_ROLE_MAPPING, LDAP_ROLE_MAPPING) en la tabla de roles
+pub fn parse_role_mapping(value: &str) -> Result<Vec<(String, Role)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (group, role) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Entrada de mapeo de roles no válida: '{}'", entry))?;
+            Ok((group.trim().to_string(), parse_role(role)?))
+        })
+        .collect()
+}
+
+// Rol más alto de los grupos del mapeo a los que pertenece el usuario,
+// o `default_role` si no pertenece a ninguno
+pub fn mapped_role(
+    mapping: &[(String, Role)],
+    default_role: Option<Role>,
+    is_member: impl Fn(&str) -> bool,
+) -> Option<Role> {
+    mapping
+        .iter()
+        .filter(|(group, _)| is_member(group))
+        .map(|(_, role)| *role)
+        .max()
+        .or(default_role)
+}
+
+// Cuenta tal y como se guarda en el fichero de usuarios
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Account {
+    pub username: String,
+    pub hashed_password: String,
+    pub role: Role,
+    // Grupos para las reglas de acceso por repositorio
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub disabled: bool,
+    pub created_at: DateTime<Utc>,
+    // Proveedor externo (p.ej. `oidc`) que autentica la cuenta; las cuentas
+    // externas no tienen contraseña local
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    // Identificador estable del usuario en el proveedor externo (en OIDC, `iss` y `sub`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    // Nombre que da el proveedor; puede cambiar sin que cambie la cuenta
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    // Segundo factor; hasta confirmarlo el alta queda pendiente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp: Option<TotpSettings>,
+}
+
+impl Account {
+    pub fn totp_enabled(&self) -> bool {
+        self.totp.as_ref().is_some_and(|totp| totp.enabled)
+    }
+
+    pub fn new(username: &str, hashed_password: &str, role: Role) -> Self {
+        Self {
+            username: username.to_string(),
+            hashed_password: hashed_password.to_string(),
+            role,
+            groups: Vec::new(),
+            disabled: false,
+            created_at: Utc::now(),
+            provider: None,
+            subject: None,
+            display_name: None,
+            totp: None,
+        }
+    }
+}
+
+// Lo que devuelve la API de una cuenta: nunca el hash de la contraseña
+#[derive(Serialize, Debug, Clone)]
+pub struct AccountView {
+    pub username: String,
+    pub role: Role,
+    pub groups: Vec<String>,
+    pub disabled: bool,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub totp_enabled: bool,
+}
+
+impl From<&Account> for AccountView {
+    fn from(account: &Account) -> Self {
+        Self {
+            username: account.username.clone(),
+            role: account.role,
+            groups: account.groups.clone(),
+            disabled: account.disabled,
+            created_at: account.created_at,
+            provider: account.provider.clone(),
+            display_name: account.display_name.clone(),
+            totp_enabled: account.totp_enabled(),
+        }
+    }
+}
+
+// bcrypt tarda a propósito: se calcula fuera de los hilos del runtime
+async fn hash_password(password: &str) -> Result<String, (StatusCode, String)> {
+    if password.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "La contraseña no puede estar vacía".to_string()));
+    }
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || hash(password, DEFAULT_COST))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+// Comprueba la contraseña fuera de los hilos del runtime. Un hash corrupto
+// se trata como contraseña incorrecta.
+pub async fn verify_password(account: &Account, password: &str) -> bool {
+    let (password, hashed_password) = (password.to_string(), account.hashed_password.clone());
+    match tokio::task::spawn_blocking(move || verify(password, &hashed_password)).await {
+        Ok(Ok(valid)) => valid,
+        Ok(Err(e)) => {
+            error!("Invalid password hash for {}: {}", account.username, e);
+            false
+        }
+        Err(e) => {
+            error!("Password check for {} failed: {}", account.username, e);
+            false
+        }
+    }
+}
+
+// Escribe en un temporal del mismo directorio y lo renombra: quien lea el
+// fichero ve la versión anterior o la nueva, nunca una a medias
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, content)?;
+    std::fs::File::open(&tmp)?.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+// Cuentas de usuario persistidas en un fichero JSON
+pub struct UserStore {
+    path: Option<PathBuf>,
+    accounts: RwLock<BTreeMap<String, Account>>,
+    // Serializa los cambios mientras se guarda el fichero, sin bloquear las lecturas
+    writer: Mutex<()>,
+}
+
+impl UserStore {
+    // Sin fichero se empieza sin cuentas; un fichero ilegible o no válido es un
+    // error, porque seguir con la lista vacía lo sobrescribiría en el primer cambio
+    pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+        let accounts: Vec<Account> = match path.as_ref().filter(|p| p.exists()) {
+            Some(p) => {
+                let content = std::fs::read_to_string(p)
+                    .map_err(|e| format!("No se pudo leer {}: {}", p.display(), e))?;
+                serde_json::from_str(&content).map_err(|e| format!("Usuarios no válidos en {}: {}", p.display(), e))?
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path,
+            accounts: RwLock::new(
+                accounts
+                    .into_iter()
+                    .map(|a| (a.username.clone(), a))
+                    .collect(),
+            ),
+            writer: Mutex::new(()),
+        })
+    }
+
+    // Si todavía no hay ninguna cuenta, crea el administrador inicial
+    // (el antiguo usuario único de USERNAME/HASHED_PASSWORD)
+    pub async fn bootstrap_admin(&self, username: &str, hashed_password: &str) -> Result<bool, String> {
+        if !self.accounts.read().unwrap().is_empty() {
+            return Ok(false);
+        }
+        info!("Creating initial admin account: {}", username);
+        self.update(|accounts| {
+            accounts.insert(username.to_string(), Account::new(username, hashed_password, Role::Admin));
+            Ok(())
+        })
+        .await
+        .map_err(|(_, message)| message)?;
+        Ok(true)
+    }
+
+    #[cfg(test)]
+    pub fn insert(&self, account: Account) {
+        self.accounts.write().unwrap().insert(account.username.clone(), account);
+    }
+
+    pub fn get(&self, username: &str) -> Option<Account> {
+        self.accounts.read().unwrap().get(username).cloned()
+    }
+
+    // Cuenta habilitada con ese nombre (las deshabilitadas no pueden autenticarse)
+    pub fn active(&self, username: &str) -> Option<Account> {
+        self.get(username).filter(|a| !a.disabled)
+    }
+
+    pub fn list(&self) -> Vec<AccountView> {
+        self.accounts.read().unwrap().values().map(AccountView::from).collect()
+    }
+
+    pub async fn create(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<AccountView, (StatusCode, String)> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "El nombre de usuario no puede estar vacío".to_string()));
+        }
+        let account = Account::new(username, &hash_password(password).await?, role);
+        let view = AccountView::from(&account);
+        self.update(|accounts| {
+            if accounts.contains_key(username) {
+                return Err((StatusCode::CONFLICT, format!("El usuario '{}' ya existe", username)));
+            }
+            accounts.insert(username.to_string(), account);
+            Ok(())
+        })
+        .await?;
+        Ok(view)
+    }
+
+    // Crea o actualiza la cuenta de un usuario autenticado por un proveedor externo.
+    // Con `subject`, la cuenta es la ligada a ese identificador aunque el proveedor
+    // devuelva otro nombre, y un nombre ya ligado a otro `subject` se rechaza. Las
+    // cuentas creadas antes de guardar el `subject` se ligan en su siguiente login.
+    // El rol y los grupos se sincronizan en cada login; una cuenta deshabilitada
+    // o local con el mismo nombre no se toca.
+    pub async fn upsert_external(
+        &self,
+        username: &str,
+        provider: &str,
+        subject: Option<&str>,
+        role: Role,
+        groups: Vec<String>,
+    ) -> Result<Account, (StatusCode, String)> {
+        let mut result = None;
+        self.update(|accounts| {
+            let key = subject
+                .and_then(|subject| {
+                    accounts.values().find(|a| {
+                        a.provider.as_deref() == Some(provider) && a.subject.as_deref() == Some(subject)
+                    })
+                })
+                .map(|a| a.username.clone())
+                .unwrap_or_else(|| username.to_string());
+            let account = accounts.entry(key).or_insert_with(|| {
+                info!("Creating {} account: {}", provider, username);
+                let mut account = Account::new(username, "", role);
+                account.provider = Some(provider.to_string());
+                account.subject = subject.map(String::from);
+                account
+            });
+            if account.provider.as_deref() != Some(provider) {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("Ya existe una cuenta local llamada '{}'", username),
+                ));
+            }
+            match (account.subject.as_deref(), subject) {
+                (Some(bound), Some(subject)) if bound != subject => {
+                    return Err((
+                        StatusCode::CONFLICT,
+                        format!("La cuenta '{}' pertenece a otro usuario de {}", username, provider),
+                    ));
+                }
+                (None, Some(subject)) => {
+                    info!("Binding {} account {} to {}", provider, account.username, subject);
+                    account.subject = Some(subject.to_string());
+                }
+                _ => {}
+            }
+            if account.disabled {
+                return Err((StatusCode::FORBIDDEN, format!("La cuenta '{}' está deshabilitada", username)));
+            }
+            if subject.is_some() {
+                account.display_name = Some(username.to_string());
+            }
+            account.role = role;
+            account.groups = groups;
+            result = Some(account.clone());
+            Ok(())
+        })
+        .await?;
+        Ok(result.unwrap())
+    }
+
+    pub async fn set_disabled(&self, username: &str, disabled: bool) -> Result<AccountView, (StatusCode, String)> {
+        self.modify(username, |account| account.disabled = disabled).await
+    }
+
+    pub async fn set_role(&self, username: &str, role: Role) -> Result<AccountView, (StatusCode, String)> {
+        self.modify(username, |account| account.role = role).await
+    }
+
+    pub async fn set_groups(&self, username: &str, groups: Vec<String>) -> Result<AccountView, (StatusCode, String)> {
+        self.modify(username, |account| account.groups = groups).await
+    }
+
+    pub async fn reset_password(&self, username: &str, password: &str) -> Result<AccountView, (StatusCode, String)> {
+        let hashed_password = hash_password(password).await?;
+        self.modify(username, |account| account.hashed_password = hashed_password).await
+    }
+
+    async fn modify(
+        &self,
+        username: &str,
+        change: impl FnOnce(&mut Account),
+    ) -> Result<AccountView, (StatusCode, String)> {
+        let mut view = None;
+        self.update(|accounts| {
+            let account = accounts
+                .get_mut(username)
+                .ok_or_else(|| (StatusCode::NOT_FOUND, format!("El usuario '{}' no existe", username)))?;
+            change(account);
+            view = Some(AccountView::from(&*account));
+            Ok(())
+        })
+        .await?;
+        Ok(view.unwrap())
+    }
+
+    // Cambio sobre una cuenta que puede fallar; si falla no se guarda nada
+    pub async fn update_account<T>(
+        &self,
+        username: &str,
+        change: impl FnOnce(&mut Account) -> Result<T, (StatusCode, String)>,
+    ) -> Result<T, (StatusCode, String)> {
+        let mut result = None;
+        self.update(|accounts| {
+            let account = accounts
+                .get_mut(username)
+                .ok_or_else(|| (StatusCode::NOT_FOUND, format!("El usuario '{}' no existe", username)))?;
+            result = Some(change(account)?);
+            Ok(())
+        })
+        .await?;
+        Ok(result.unwrap())
+    }
+
+    // Aplica el cambio sobre una copia y solo lo da por bueno si se ha podido guardar.
+    // El fichero se escribe fuera de los hilos del runtime y sin retener `accounts`.
+    async fn update(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<String, Account>) -> Result<(), (StatusCode, String)>,
+    ) -> Result<(), (StatusCode, String)> {
+        let _writer = self.writer.lock().await;
+        let mut updated = self.accounts.read().unwrap().clone();
+        change(&mut updated)?;
+        if let Some(path) = self.path.clone() {
+            let list: Vec<&Account> = updated.values().collect();
+            let content = serde_json::to_string_pretty(&list)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            tokio::task::spawn_blocking(move || {
+                write_atomic(&path, &content).map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("No se pudo guardar {}: {}", path.display(), e),
+                    )
+                })
+            })
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+        }
+        *self.accounts.write().unwrap() = updated;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_hierarchy() {
+        assert!(Role::Admin.allows(Role::Editor));
+        assert!(Role::Editor.allows(Role::Viewer));
+        assert!(!Role::Viewer.allows(Role::Editor));
+        assert!(!Role::Editor.allows(Role::Admin));
+    }
+
+    #[test]
+    fn test_parse_role_mapping() {
+        let mapping = parse_role_mapping("ops=admin, devs=Editor").unwrap();
+        assert_eq!(mapping, vec![("ops".to_string(), Role::Admin), ("devs".to_string(), Role::Editor)]);
+        assert!(parse_role_mapping("ops=root").is_err());
+        assert!(parse_role_mapping("ops").is_err());
+        assert_eq!(mapped_role(&mapping, None, |g| g == "devs"), Some(Role::Editor));
+        assert_eq!(mapped_role(&mapping, Some(Role::Viewer), |_| false), Some(Role::Viewer));
+    }
+
+    #[tokio::test]
+    async fn test_store_persists_accounts() {
+        let path = std::env::temp_dir().join(format!("users-{}.json", uuid::Uuid::new_v4()));
+        let store = UserStore::load(Some(path.clone())).unwrap();
+        assert!(store.bootstrap_admin("admin", "hash").await.unwrap());
+        assert!(!store.bootstrap_admin("other", "hash").await.unwrap());
+        store.set_disabled("admin", true).await.unwrap();
+        assert_eq!(
+            store.set_role("ghost", Role::Viewer).await.unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+        // El temporal de la escritura atómica no se queda en el directorio
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let reloaded = UserStore::load(Some(path.clone())).unwrap();
+        let admin = reloaded.get("admin").unwrap();
+        assert_eq!(admin.role, Role::Admin);
+        assert!(admin.disabled);
+        assert!(reloaded.active("admin").is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_external_accounts_follow_the_subject() {
+        let store = UserStore::load(None).unwrap();
+        let groups = Vec::new;
+        let alice = store.upsert_external("alice", "oidc", Some("idp 1"), Role::Viewer, groups()).await.unwrap();
+        assert_eq!(alice.subject.as_deref(), Some("idp 1"));
+        // Otro usuario del proveedor que dice llamarse `alice` no entra en su cuenta
+        let error = store.upsert_external("alice", "oidc", Some("idp 2"), Role::Admin, groups()).await.unwrap_err();
+        assert_eq!(error.0, StatusCode::CONFLICT);
+        // Si el proveedor le cambia el nombre, sigue siendo la misma cuenta
+        let renamed = store.upsert_external("alice.smith", "oidc", Some("idp 1"), Role::Editor, groups()).await.unwrap();
+        assert_eq!(renamed.username, "alice");
+        assert_eq!(renamed.display_name.as_deref(), Some("alice.smith"));
+        assert_eq!(renamed.role, Role::Editor);
+        assert_eq!(store.list().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_file_fails_to_load() {
+        let path = std::env::temp_dir().join(format!("users-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(UserStore::load(Some(path.clone())).is_err());
+        // El fichero sigue intacto
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{not json");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_rejects_duplicates_and_empty_passwords() {
+        let store = UserStore::load(None).unwrap();
+        store.bootstrap_admin("admin", "hash").await.unwrap();
+        assert_eq!(
+            store.create("admin", "secret", Role::Viewer).await.unwrap_err().0,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            store.create("viewer", "", Role::Viewer).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(store.list().len(), 1);
+    }
+}
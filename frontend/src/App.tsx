@@ -1,34 +1,92 @@
-import { useState } from 'react'
-import reactLogo from './assets/react.svg'
-import viteLogo from '/vite.svg'
+import { useCallback, useEffect, useState, type FormEvent } from 'react'
+import { ApiError, fetchRepositories, login, type RepositoryInfo } from './api'
+import RepositoryView from './components/RepositoryView'
 import './App.css'
 
+function LoginForm({ onLogin }: { onLogin: () => void }) {
+  const [username, setUsername] = useState('')
+  const [password, setPassword] = useState('')
+  const [error, setError] = useState<string | null>(null)
+
+  const submit = async (event: FormEvent) => {
+    event.preventDefault()
+    try {
+      const result = await login(username, password)
+      if (result?.mfa_required) {
+        setError('Esta cuenta tiene 2FA: inicia sesión desde la API')
+        return
+      }
+      onLogin()
+    } catch (e) {
+      setError((e as Error).message)
+    }
+  }
+
+  return (
+    <form className="login" onSubmit={submit}>
+      <input placeholder="Usuario" value={username} onChange={(e) => setUsername(e.target.value)} />
+      <input placeholder="Contraseña" type="password" value={password} onChange={(e) => setPassword(e.target.value)} />
+      <button type="submit">Entrar</button>
+      {error && <p className="error">{error}</p>}
+    </form>
+  )
+}
+
 function App() {
-  const [count, setCount] = useState(0)
+  const [repositories, setRepositories] = useState<RepositoryInfo[] | null>(null)
+  const [selected, setSelected] = useState<string | null>(null)
+  const [loggedOut, setLoggedOut] = useState(false)
+  const [error, setError] = useState<string | null>(null)
+
+  const load = useCallback(() => {
+    fetchRepositories()
+      .then((repositories) => {
+        setLoggedOut(false)
+        setRepositories(repositories)
+      })
+      .catch((e: Error) => {
+        if (e instanceof ApiError && e.status === 401) {
+          setLoggedOut(true)
+        } else {
+          setError(e.message)
+        }
+      })
+  }, [])
+
+  useEffect(load, [load])
+
+  if (loggedOut) return <LoginForm onLogin={load} />
+  if (error) return <p className="error">{error}</p>
+  if (repositories === null) return <p>Cargando repositorios…</p>
 
   return (
-    <>
-      <div>
-        <a href="https://vite.dev" target="_blank">
-          <img src={viteLogo} className="logo" alt="Vite logo" />
-        </a>
-        <a href="https://react.dev" target="_blank">
-          <img src={reactLogo} className="logo react" alt="React logo" />
-        </a>
-      </div>
-      <h1>Vite + React</h1>
-      <div className="card">
-        <button onClick={() => setCount((count) => count + 1)}>
-          count is {count}
-        </button>
-        <p>
-          Edit <code>src/App.tsx</code> and save to test HMR
-        </p>
-      </div>
-      <p className="read-the-docs">
-        Click on the Vite and React logos to learn more
-      </p>
-    </>
+    <div className="layout">
+      <nav className="repositories">
+        <h2>Repositorios</h2>
+        <ul>
+          {repositories.map((repository) => (
+            <li key={repository.name}>
+              <button
+                className={repository.name === selected ? 'selected' : ''}
+                onClick={() => setSelected(repository.name)}
+              >
+                {repository.name} <span className="muted">({repository.tag_count})</span>
+              </button>
+            </li>
+          ))}
+        </ul>
+      </nav>
+      <main>
+        {selected ? (
+          <>
+            <h2>{selected}</h2>
+            <RepositoryView key={selected} repository={selected} />
+          </>
+        ) : (
+          <p className="muted">Selecciona un repositorio</p>
+        )}
+      </main>
+    </div>
   )
 }
 
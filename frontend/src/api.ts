@@ -0,0 +1,78 @@
+// Cliente mínimo de la API del backend (`/api/v1`). La sesión viaja en la cookie `token`.
+
+const API_BASE = '/api/v1'
+
+export interface ApiResponse<T> {
+  status: number
+  message: string
+  data: T | null
+}
+
+export interface RepositoryInfo {
+  name: string
+  last_push: string | null
+  tag_count: number
+}
+
+// Una plataforma soportada por el tag: una para imágenes simples,
+// una por manifiesto hijo en los manifest lists / índices OCI
+export interface PlatformDetail {
+  os: string
+  architecture: string
+  variant: string | null
+  digest: string
+  size_bytes: number
+}
+
+export interface TagDetail {
+  name: string
+  digest: string
+  size_bytes: number
+  created_at: string | null
+  architecture: string | null
+  os: string | null
+  platforms: PlatformDetail[]
+}
+
+export class ApiError extends Error {
+  status: number
+
+  constructor(status: number, message: string) {
+    super(message)
+    this.status = status
+  }
+}
+
+async function request<T>(path: string, init?: RequestInit): Promise<T | null> {
+  const response = await fetch(`${API_BASE}${path}`, {
+    credentials: 'include',
+    headers: { 'Content-Type': 'application/json' },
+    ...init,
+  })
+  const body = (await response.json().catch(() => null)) as ApiResponse<T> | null
+  if (!response.ok) {
+    throw new ApiError(response.status, body?.message ?? response.statusText)
+  }
+  return body?.data ?? null
+}
+
+export async function login(username: string, password: string): Promise<{ mfa_required?: boolean } | null> {
+  return request<{ mfa_required?: boolean }>('/auth/login', {
+    method: 'POST',
+    body: JSON.stringify({ username, hashed_password: password }),
+  })
+}
+
+export async function fetchRepositories(): Promise<RepositoryInfo[]> {
+  return (await request<RepositoryInfo[]>('/registry/?limit=100')) ?? []
+}
+
+// `?repository=` admite nombres con `/`, que la ruta `/{repo}/tags` no acepta
+export async function fetchTags(repository: string): Promise<TagDetail[]> {
+  return (await request<TagDetail[]>(`/registry/?repository=${encodeURIComponent(repository)}`)) ?? []
+}
+
+export function platformLabel(platform: Pick<PlatformDetail, 'os' | 'architecture' | 'variant'>): string {
+  const label = `${platform.os}/${platform.architecture}`
+  return platform.variant ? `${label}/${platform.variant}` : label
+}
@@ -0,0 +1,25 @@
+import { platformLabel, type TagDetail } from '../api'
+
+// Plataformas del tag; las imágenes antiguas sin `platforms` usan os/architecture del config
+function PlatformBadges({ tag }: { tag: TagDetail }) {
+  const platforms = tag.platforms.length > 0
+    ? tag.platforms
+    : tag.os && tag.architecture
+      ? [{ os: tag.os, architecture: tag.architecture, variant: null, digest: tag.digest, size_bytes: tag.size_bytes }]
+      : []
+
+  if (platforms.length === 0) {
+    return <span className="muted">—</span>
+  }
+  return (
+    <span className="platforms">
+      {platforms.map((platform) => (
+        <span key={platform.digest + platformLabel(platform)} className="badge" title={platform.digest}>
+          {platformLabel(platform)}
+        </span>
+      ))}
+    </span>
+  )
+}
+
+export default PlatformBadges
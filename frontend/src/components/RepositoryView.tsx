@@ -0,0 +1,67 @@
+import { useEffect, useState } from 'react'
+import { fetchTags, type TagDetail } from '../api'
+import PlatformBadges from './PlatformBadges'
+
+function formatSize(bytes: number): string {
+  const units = ['B', 'KB', 'MB', 'GB']
+  let size = bytes
+  let unit = 0
+  while (size >= 1024 && unit < units.length - 1) {
+    size /= 1024
+    unit += 1
+  }
+  return `${size.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`
+}
+
+function RepositoryView({ repository }: { repository: string }) {
+  const [tags, setTags] = useState<TagDetail[]>([])
+  const [error, setError] = useState<string | null>(null)
+  const [loading, setLoading] = useState(true)
+
+  useEffect(() => {
+    let cancelled = false
+    fetchTags(repository)
+      .then((tags) => {
+        if (!cancelled) setTags(tags)
+      })
+      .catch((e: Error) => {
+        if (!cancelled) setError(e.message)
+      })
+      .finally(() => {
+        if (!cancelled) setLoading(false)
+      })
+    return () => {
+      cancelled = true
+    }
+  }, [repository])
+
+  if (loading) return <p>Cargando tags de {repository}…</p>
+  if (error) return <p className="error">{error}</p>
+
+  return (
+    <table className="tags">
+      <thead>
+        <tr>
+          <th>Tag</th>
+          <th>Digest</th>
+          <th>Tamaño</th>
+          <th>Creado</th>
+          <th>Plataformas</th>
+        </tr>
+      </thead>
+      <tbody>
+        {tags.map((tag) => (
+          <tr key={tag.name}>
+            <td>{tag.name}</td>
+            <td className="digest" title={tag.digest}>{tag.digest.slice(0, 19)}</td>
+            <td>{formatSize(tag.size_bytes)}</td>
+            <td>{tag.created_at ? new Date(tag.created_at).toLocaleString() : '—'}</td>
+            <td><PlatformBadges tag={tag} /></td>
+          </tr>
+        ))}
+      </tbody>
+    </table>
+  )
+}
+
+export default RepositoryView
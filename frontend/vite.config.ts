@@ -4,4 +4,10 @@ import react from '@vitejs/plugin-react-swc'
 // https://vite.dev/config/
 export default defineConfig({
   plugins: [react()],
+  // En desarrollo la API la sirve el backend (PORT, 3000 por defecto)
+  server: {
+    proxy: {
+      '/api': 'http://localhost:3000',
+    },
+  },
 })